@@ -0,0 +1,69 @@
+// Compares the lookups on a MultiThreadedStore with the lookups on the
+// FrozenStore it freezes into, for the same table and the same searches.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rotonda_store::generator::Generator;
+use rotonda_store::{
+    prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+};
+use routecore::addr::Prefix;
+
+const PREFIXES: usize = 100_000;
+const SEARCHES: usize = 1_000;
+
+fn options() -> MatchOptions {
+    MatchOptions {
+        match_type: MatchType::LongestMatch,
+        include_all_records: false,
+        include_less_specifics: false,
+        include_more_specifics: false,
+    }
+}
+
+fn lookups(c: &mut Criterion) {
+    let store = MultiThreadedStore::<PrefixAs>::new().unwrap();
+    for record in Generator::new(1)
+        .v4_prefixes(PREFIXES)
+        .v6_prefixes(0)
+        .generate()
+    {
+        store
+            .insert(&record.prefix, record.meta.into_owned())
+            .unwrap();
+    }
+    // The searches are the first addresses of the prefixes of a second
+    // table, so they spread over the address space like the table does.
+    let searches = Generator::new(2)
+        .v4_prefixes(SEARCHES)
+        .v6_prefixes(0)
+        .generate()
+        .into_iter()
+        .map(|record| Prefix::new(record.prefix.addr(), 32).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("longest_match");
+
+    // `match_prefix` on the multi-threaded store goes through
+    // `match_prefix_by_store_direct`.
+    group.bench_function("multi_threaded", |b| {
+        let guard = &epoch::pin();
+        b.iter(|| {
+            for search in &searches {
+                black_box(store.match_prefix(search, &options(), guard));
+            }
+        })
+    });
+
+    let frozen = store.freeze().unwrap();
+    group.bench_function("frozen", |b| {
+        b.iter(|| {
+            for search in &searches {
+                black_box(frozen.match_prefix(search, &options()));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
@@ -0,0 +1,477 @@
+// Compares the stores and their strides on the same table.
+//
+// For every store in the matrix this inserts the table, reports the insert
+// throughput, the memory the store allocated and the number of nodes, and
+// then times single lookups for every match type, reporting percentiles of
+// their latencies. The results are written as JSON to stdout.
+//
+// The table is either a CSV file, or generated with a distribution of
+// prefix lengths like that of the DFZ by the generator:
+//
+//   cargo bench --features csv,generator --bench stores -- \
+//       --dataset <file.csv>
+//   cargo bench --features csv,generator --bench stores -- \
+//       --prefixes 500000 --seed 7
+//
+// The strides of the multi-threaded store are fixed at compile time, so
+// the matrix has a store type for each of them. The single-threaded store
+// takes its strides at run time.
+use rotonda_store::generator::Generator;
+use rotonda_store::loader::Loader;
+use rotonda_store::prelude::*;
+use rotonda_store::{
+    MatchOptions, MatchType, PrefixAs, SingleThreadedStore,
+};
+use serde_json::{json, Value};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use std::{env, process};
+
+//------------ Allocations --------------------------------------------------
+
+// Keeps track of the bytes allocated on the heap, so that the memory of a
+// store is measured the same way for every store.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocated() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+//------------ Stores -------------------------------------------------------
+
+// What the harness needs from a store.
+trait BenchStore {
+    fn stride_sizes(&self) -> (Vec<u8>, Vec<u8>);
+    fn add(
+        &mut self,
+        prefix: &Prefix,
+        meta: PrefixAs,
+    ) -> Result<(), Box<dyn Error>>;
+    // Returns whether the lookup found a prefix.
+    fn find(&self, prefix: &Prefix, options: &MatchOptions) -> bool;
+    fn prefix_count(&self) -> usize;
+    fn node_count(&self) -> usize;
+}
+
+// Every multi-threaded store gets a module of its own, since the fields
+// with the strides are only visible there.
+macro_rules! multi_threaded_store {
+    ($name:ident, $v4:tt, $v6:tt) => {
+        // Not every method the macro generates is used here.
+        #[allow(dead_code)]
+        mod $name {
+            use super::BenchStore;
+            use rotonda_store::prelude::*;
+            use rotonda_store::{MatchOptions, PrefixAs};
+            use std::error::Error;
+
+            #[create_store(($v4, $v6))]
+            struct Store;
+
+            pub fn new() -> Result<Box<dyn BenchStore>, Box<dyn Error>> {
+                Ok(Box::new(Store::<PrefixAs>::new()?))
+            }
+
+            impl BenchStore for Store<PrefixAs> {
+                fn stride_sizes(&self) -> (Vec<u8>, Vec<u8>) {
+                    let sizes = |sizes: &[u8]| {
+                        sizes
+                            .iter()
+                            .take_while(|s| **s > 0)
+                            .copied()
+                            .collect()
+                    };
+                    (
+                        sizes(&self.v4.store.get_stride_sizes()[..]),
+                        sizes(&self.v6.store.get_stride_sizes()[..]),
+                    )
+                }
+
+                fn add(
+                    &mut self,
+                    prefix: &Prefix,
+                    meta: PrefixAs,
+                ) -> Result<(), Box<dyn Error>> {
+                    self.insert(prefix, meta)
+                }
+
+                fn find(
+                    &self,
+                    prefix: &Prefix,
+                    options: &MatchOptions,
+                ) -> bool {
+                    let guard = &epoch::pin();
+                    self.match_prefix(prefix, options, guard).prefix.is_some()
+                }
+
+                fn prefix_count(&self) -> usize {
+                    self.prefixes_len()
+                }
+
+                fn node_count(&self) -> usize {
+                    self.nodes_len()
+                }
+            }
+        }
+    };
+}
+
+// The strides of the default store.
+multi_threaded_store!(
+    multi_default,
+    [5, 5, 4, 3, 3, 3, 3, 3, 3, 3],
+    [
+        4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+        4, 4, 4, 4, 4, 4, 4, 4, 4, 4
+    ]
+);
+multi_threaded_store!(
+    multi_4,
+    [4, 4, 4, 4, 4, 4, 4, 4],
+    [
+        4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
+        4, 4, 4, 4, 4, 4, 4, 4, 4, 4
+    ]
+);
+multi_threaded_store!(
+    multi_5,
+    [5, 5, 5, 5, 4, 4, 4],
+    [
+        5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5,
+        5, 5, 4, 4
+    ]
+);
+
+impl BenchStore for SingleThreadedStore<PrefixAs> {
+    fn stride_sizes(&self) -> (Vec<u8>, Vec<u8>) {
+        let strides = self.strides();
+        (strides.v4.to_vec(), strides.v6.to_vec())
+    }
+
+    fn add(
+        &mut self,
+        prefix: &Prefix,
+        meta: PrefixAs,
+    ) -> Result<(), Box<dyn Error>> {
+        self.insert(prefix, meta)
+    }
+
+    fn find(&self, prefix: &Prefix, options: &MatchOptions) -> bool {
+        self.match_prefix(prefix, options).prefix.is_some()
+    }
+
+    fn prefix_count(&self) -> usize {
+        self.prefixes_len()
+    }
+
+    fn node_count(&self) -> usize {
+        self.nodes_len()
+    }
+}
+
+type NewStore = Box<dyn Fn() -> Result<Box<dyn BenchStore>, Box<dyn Error>>>;
+
+// The stores to compare, with a name for each.
+fn matrix() -> Vec<(&'static str, String, NewStore)> {
+    let multi: [(&str, NewStore); 3] = [
+        ("default", Box::new(multi_default::new)),
+        ("4", Box::new(multi_4::new)),
+        ("5", Box::new(multi_5::new)),
+    ];
+    let mut matrix = multi
+        .into_iter()
+        .map(|(name, new)| ("multi_threaded", name.to_string(), new))
+        .collect::<Vec<_>>();
+
+    let single: [(&[u8], &[u8]); 5] = [
+        (&[4], &[4]),
+        (&[5, 5, 4, 3, 3, 3, 3, 3, 3, 3], &[4]),
+        (&[3, 4, 5, 4], &[4]),
+        (&[6, 6, 6, 6, 8], &[8]),
+        (&[8], &[8]),
+    ];
+    for (v4, v6) in single {
+        let new = move || -> Result<Box<dyn BenchStore>, Box<dyn Error>> {
+            Ok(Box::new(SingleThreadedStore::<PrefixAs>::new(
+                v4.to_vec(),
+                v6.to_vec(),
+            )))
+        };
+        let name = format!("{:?}/{:?}", v4, v6);
+        matrix.push(("single_threaded", name, Box::new(new)));
+    }
+    matrix
+}
+
+//------------ Table --------------------------------------------------------
+
+// About five IPv4 prefixes for every IPv6 prefix, like the DFZ.
+fn synthetic_table(seed: u64, count: usize) -> Vec<(Prefix, PrefixAs)> {
+    Generator::new(seed)
+        .v4_prefixes(count - count / 6)
+        .v6_prefixes(count / 6)
+        .generate()
+        .into_iter()
+        .map(|record| (record.prefix, record.meta.into_owned()))
+        .collect()
+}
+
+fn load_table(path: &str) -> Result<Vec<(Prefix, PrefixAs)>, Box<dyn Error>> {
+    let mut header = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut header)?;
+    let loader = if header.split(',').any(|col| col.trim() == "prefix") {
+        Loader::csv()
+    } else {
+        // The files in data/ have a header line that isn't a useful one.
+        Loader::csv()
+            .columns(&["ip", "len", "asn"])
+            .prefix_column("ip")
+            .len_column("len")
+    };
+    let records: Vec<PrefixRecord<PrefixAs>> = loader.load_file(path)?;
+    Ok(records
+        .into_iter()
+        .map(|record| (record.prefix, record.meta.into_owned()))
+        .collect())
+}
+
+// Half of the lookups are for prefixes in the table, half for prefixes
+// from another table, that mostly aren't.
+fn lookups(
+    seed: u64,
+    table: &[(Prefix, PrefixAs)],
+    count: usize,
+) -> Vec<Prefix> {
+    let mut lookups = synthetic_table(seed.wrapping_add(1), count / 2)
+        .into_iter()
+        .map(|(prefix, _)| prefix)
+        .collect::<Vec<_>>();
+    // A large prime as the step spreads the picks over the table.
+    let mut idx = 0;
+    while lookups.len() < count {
+        idx = (idx + 7_919) % table.len();
+        lookups.push(table[idx].0);
+    }
+    lookups
+}
+
+//------------ Running ------------------------------------------------------
+
+struct Config {
+    dataset: Option<String>,
+    prefixes: usize,
+    lookups: usize,
+    seed: u64,
+}
+
+impl Config {
+    fn from_args() -> Result<Self, String> {
+        let mut config = Config {
+            dataset: None,
+            prefixes: 100_000,
+            lookups: 10_000,
+            seed: 0x2545_f491_4f6c_dd1d,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--dataset" => config.dataset = Some(value()?),
+                "--prefixes" => config.prefixes = number(&value()?)?,
+                "--lookups" => config.lookups = number(&value()?)?,
+                "--seed" => config.seed = number(&value()?)? as u64,
+                // Added by cargo bench.
+                "--bench" => {}
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        // The percentiles need at least one lookup.
+        if config.lookups == 0 {
+            return Err("--lookups has to be at least 1".to_string());
+        }
+        Ok(config)
+    }
+}
+
+fn number(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number {}", value))
+}
+
+fn percentiles(mut nanos: Vec<u64>) -> Value {
+    nanos.sort_unstable();
+    let at = |percentile: usize| nanos[(nanos.len() - 1) * percentile / 100];
+    json!({
+        "p50_ns": at(50),
+        "p90_ns": at(90),
+        "p99_ns": at(99),
+        "max_ns": at(100),
+        "mean_ns": nanos.iter().sum::<u64>() / nanos.len() as u64,
+    })
+}
+
+fn run(
+    new: &NewStore,
+    table: &[(Prefix, PrefixAs)],
+    lookups: &[Prefix],
+) -> Result<Value, Box<dyn Error>> {
+    let before = allocated();
+    let mut store = new()?;
+
+    let start = Instant::now();
+    for (prefix, meta) in table {
+        store.add(prefix, *meta)?;
+    }
+    let insert = start.elapsed();
+    let memory = allocated().saturating_sub(before);
+
+    let mut results = serde_json::Map::new();
+    for (name, match_type) in [
+        ("exact", MatchType::ExactMatch),
+        ("longest", MatchType::LongestMatch),
+        ("empty", MatchType::EmptyMatch),
+    ] {
+        let options = MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        };
+        let mut found = 0;
+        let mut nanos = Vec::with_capacity(lookups.len());
+        for prefix in lookups {
+            let start = Instant::now();
+            found += store.find(prefix, &options) as usize;
+            nanos.push(start.elapsed().as_nanos() as u64);
+        }
+        let mut res = percentiles(nanos);
+        res["found"] = json!(found);
+        results.insert(name.into(), res);
+    }
+
+    let (v4, v6) = store.stride_sizes();
+    Ok(json!({
+        "strides": { "v4": v4, "v6": v6 },
+        "prefixes": store.prefix_count(),
+        "nodes": store.node_count(),
+        "memory_bytes": memory,
+        "insert": {
+            "duration_ms": insert.as_millis() as u64,
+            "per_second": per_second(table.len(), insert),
+        },
+        "lookups": results,
+    }))
+}
+
+fn per_second(count: usize, duration: Duration) -> u64 {
+    (count as f64 / duration.as_secs_f64().max(1e-9)) as u64
+}
+
+fn main() {
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(2);
+        }
+    };
+
+    let table = match &config.dataset {
+        Some(path) => match load_table(path) {
+            Ok(table) => table,
+            Err(err) => {
+                eprintln!("Error: can't load {}: {}", path, err);
+                process::exit(2);
+            }
+        },
+        None => synthetic_table(config.seed, config.prefixes),
+    };
+    if table.is_empty() {
+        eprintln!("Error: the table is empty");
+        process::exit(2);
+    }
+    let lookups = lookups(config.seed, &table, config.lookups);
+
+    let mut results = vec![];
+    for (store, name, new) in matrix() {
+        eprintln!("{} {}...", store, name);
+        match run(&new, &table, &lookups) {
+            Ok(mut res) => {
+                res["store"] = json!(store);
+                res["name"] = json!(name);
+                results.push(res);
+            }
+            Err(err) => {
+                eprintln!("Error: {} {}: {}", store, name, err);
+                process::exit(1);
+            }
+        }
+    }
+
+    let v4 = table
+        .iter()
+        .filter(|(prefix, _)| prefix.addr().is_ipv4())
+        .count();
+    let report = json!({
+        "dataset": {
+            "source": config.dataset.as_deref().unwrap_or("synthetic"),
+            "seed": config.seed,
+            "v4": v4,
+            "v6": table.len() - v4,
+        },
+        "lookups": lookups.len(),
+        "results": results,
+    });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
@@ -2,6 +2,9 @@ extern crate rustc_version;
 use rustc_version::{Version, version};
 
 fn main() {
+    // The model tests build with `--cfg loom`, see src/sync.rs.
+    println!("cargo:rustc-check-cfg=cfg(loom)");
+
     let version = version().expect("Failed to get rustc version.");
     if version < Version::parse("1.51.0").unwrap() {
         eprintln!(
@@ -1,7 +1,7 @@
 use rotonda_store::prelude::*;
 
 use rotonda_store::{
-    AddressFamily, MatchOptions, MatchType, MultiThreadedStore,
+    IpAddressFamily, MatchOptions, MatchType, MultiThreadedStore,
 };
 
 use routecore::addr::Prefix;
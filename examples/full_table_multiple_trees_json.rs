@@ -1,12 +1,12 @@
 // extern crate self as roto;
 use rotonda_store::prelude::*;
 
+use rotonda_store::loader::Loader;
 use rotonda_store::{MatchOptions, MatchType, PrefixAs};
 // use routecore::addr::Prefix;
 use routecore::bgp::PrefixRecord;
 use routecore::record::Record;
 use std::error::Error;
-use std::fs::File;
 use std::process;
 
 #[create_store((
@@ -21,23 +21,13 @@ fn main() -> Result<(), Box<dyn Error>> {
     fn load_prefixes(
         pfxs: &mut Vec<PrefixRecord<PrefixAs>>,
     ) -> Result<(), Box<dyn Error>> {
-        let file = File::open(CSV_FILE_PATH)?;
-        let mut rdr = csv::Reader::from_reader(file);
-        for result in rdr.records() {
-            let record = result?;
-            let ip: Vec<_> = record[0]
-                .split('.')
-                .map(|o| -> u8 { o.parse().unwrap() })
-                .collect();
-            let net = std::net::Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]);
-            let len: u8 = record[1].parse().unwrap();
-            let asn: u32 = record[2].parse().unwrap();
-            let pfx = PrefixRecord::<PrefixAs>::new_with_local_meta(
-                Prefix::new(net.into(), len)?,
-                PrefixAs(asn),
-            );
-            pfxs.push(pfx);
-        }
+        pfxs.extend(
+            Loader::csv()
+                .columns(&["ip", "len", "asn"])
+                .prefix_column("ip")
+                .len_column("len")
+                .load_file(CSV_FILE_PATH)?,
+        );
         Ok(())
     }
 
@@ -2,7 +2,7 @@ use rotonda_store::PrefixAs;
 use rotonda_store::prelude::*;
 use rotonda_store::{MatchOptions, MatchType, MultiThreadedStore};
 
-use rotonda_store::AddressFamily;
+use rotonda_store::IpAddressFamily;
 use routecore::addr::Prefix;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
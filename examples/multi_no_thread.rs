@@ -1,7 +1,7 @@
 use log::trace;
 
 use rotonda_store::{
-    addr::Prefix, epoch, AddressFamily, MatchOptions, MultiThreadedStore,
+    addr::Prefix, epoch, IpAddressFamily, MatchOptions, MultiThreadedStore,
 };
 
 use rotonda_store::PrefixAs;
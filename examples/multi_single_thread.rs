@@ -3,7 +3,7 @@ use std::time::Duration;
 use std::thread;
 
 use rotonda_store::{
-    addr::Prefix, AddressFamily, MultiThreadedStore,
+    addr::Prefix, IpAddressFamily, MultiThreadedStore,
 };
 
 use rotonda_store::PrefixAs;
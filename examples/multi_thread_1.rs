@@ -1,7 +1,7 @@
 use std::{sync::Arc, thread};
 
 use rotonda_store::{
-    addr::Prefix, AddressFamily, MatchOptions, MultiThreadedStore, epoch
+    addr::Prefix, IpAddressFamily, MatchOptions, MultiThreadedStore, epoch
 };
 
 use routecore::record::NoMeta;
@@ -3,7 +3,7 @@ use std::time::Duration;
 use std::{sync::Arc, thread};
 
 use rotonda_store::{
-    addr::Prefix, epoch, AddressFamily, MatchOptions, MultiThreadedStore,
+    addr::Prefix, epoch, IpAddressFamily, MatchOptions, MultiThreadedStore,
 };
 
 use rotonda_store::PrefixAs;
@@ -1,5 +1,6 @@
 use rotonda_store::prelude::*;
 
+use rotonda_store::loader::Loader;
 use rotonda_store::PrefixAs;
 use rotonda_macros::create_store;
 // use routecore::addr::Prefix;
@@ -8,8 +9,6 @@ use routecore::record::Record;
 use std::env;
 use std::error::Error;
 use std::ffi::OsString;
-use std::fs::File;
-use std::net::{IpAddr, Ipv4Addr};
 use std::process;
 
 #[create_store((
@@ -28,30 +27,14 @@ fn get_first_arg() -> Result<OsString, Box<dyn Error>> {
 fn load_prefixes(
     pfxs: &mut Vec<PrefixRecord<PrefixAs>>,
 ) -> Result<(), Box<dyn Error>> {
-    // Build the CSV reader and iterate over each record.
     let file_path = get_first_arg()?;
-    let file = File::open(file_path)?;
-    let mut rdr = csv::Reader::from_reader(file);
-    for result in rdr.records() {
-        // The iterator yields Result<StringRecord, Error>, so we check the
-        // error here.
-        let record = result?;
-        // let pfx = Prefix::<u32, PrefixAs>::new_with_meta(net, len, asn);
-        let ip: Vec<_> = record[0]
-            .split('.')
-            .map(|o| -> u8 { o.parse().unwrap() })
-            .collect();
-        let net = IpAddr::V4(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]));
-        let len: u8 = record[1].parse().unwrap();
-        let asn: u32 = record[2].parse().unwrap();
-        let pfx = PrefixRecord::<PrefixAs>::new_with_local_meta(
-            Prefix::new(net, len)?,
-            PrefixAs(asn),
-        );
-        pfxs.push(pfx);
-        // trie.insert(&pfx);
-        // println!("{:?}", pfx);
-    }
+    pfxs.extend(
+        Loader::csv()
+            .columns(&["ip", "len", "asn"])
+            .prefix_column("ip")
+            .len_column("len")
+            .load_file(file_path)?,
+    );
     Ok(())
 }
 
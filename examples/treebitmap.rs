@@ -1,4 +1,4 @@
-use rotonda_store::{MatchOptions, MatchType, MultiThreadedStore, AddressFamily};
+use rotonda_store::{MatchOptions, MatchType, MultiThreadedStore, IpAddressFamily};
 use routecore::{addr::Prefix, record::NoMeta};
 use rotonda_store::prelude::*;
 
@@ -1,3 +1,5 @@
+use crate::synth_int::U256;
+
 //------------ AddressFamily (trait) ----------------------------------------
 /// The address family of an IP address as a Trait.
 ///
@@ -5,6 +7,15 @@
 /// be able to only take the amount of memory needs. Useful when building
 /// trees with large amounts of addresses/prefixes. Used by rotonda-store for
 /// this purpose.
+///
+/// Nothing in this trait is specific to IP, the tree bitmap only needs to
+/// be able to take apart and put together the bits of a key. Any fixed
+/// length bit string can be used as a key, e.g. AS numbers or MPLS labels
+/// in an `u32`, or 48-bit MAC addresses left-aligned in an `u64`. The
+/// conversions to and from IP addresses live in [`IpAddressFamily`].
+///
+/// The [`crate::BitPrefixStore`] and the
+/// [`crate::MultiThreadedBitPrefixStore`] take such keys.
 pub trait AddressFamily:
     std::fmt::Binary
     + std::fmt::Debug
@@ -37,15 +48,55 @@ pub trait AddressFamily:
 
     fn truncate_to_len(self, len: u8) -> Self;
 
+    /// Returns the `len` bits of self from `start_bit` (counted from the
+    /// left) onwards, shifted all the way to the right, for use as an
+    /// index. A `len` of zero returns zero.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `len` is larger than the number of bits in an
+    /// `usize`, so that an index never silently loses bits.
+    fn get_index(self, start_bit: u8, len: u8) -> usize;
+}
+
+//------------ IpAddressFamily (trait) --------------------------------------
+/// An address family that holds an IP address, possibly preceded by
+/// something else, like a Route Distinguisher.
+///
+/// This adds the conversions from and to the [`std::net::IpAddr`] and
+/// [`routecore::addr::Prefix`] types to the [`AddressFamily`]. The stores
+/// that take and return `Prefix`es require their families to implement
+/// this trait.
+pub trait IpAddressFamily: AddressFamily {
+    /// The number of bits in front of the IP address in the byte
+    /// representation of the family, e.g. for a Route Distinguisher. The
+    /// length of a prefix in the tree is its IP prefix length plus this
+    /// offset.
+    const IP_OFFSET: u8 = 0;
+
     fn from_ipaddr(net: std::net::IpAddr) -> Self;
 
+    /// The IP address part of self, i.e. without anything in front of it,
+    /// like a Route Distinguisher.
     fn into_ipaddr(self) -> std::net::IpAddr;
 
-    // temporary function, this will botch IPv6 completely.
-    fn dangerously_truncate_to_u32(self) -> u32;
+    /// Turns self with a length as stored in the tree into a public IP
+    /// prefix, leaving out anything in front of the IP address.
+    fn into_prefix(self, len: u8) -> routecore::addr::Prefix {
+        let len = len - Self::IP_OFFSET;
+        routecore::addr::Prefix::new(self.into_ipaddr(), len)
+            .unwrap_or_else(|p| panic!("can't convert {:?} into prefix.", p))
+    }
+}
 
-    // temporary function, this will botch IPv6 completely.
-    fn dangerously_truncate_to_usize(self) -> usize;
+// An index can't have more bits than an usize, truncating it would make
+// different keys end up at the same index.
+fn assert_index_len(len: u8) {
+    assert!(
+        u32::from(len) <= usize::BITS,
+        "can't fit an index of {} bits in an usize",
+        len
+    );
 }
 
 //-------------- Ipv4 Type --------------------------------------------------
@@ -115,6 +166,15 @@ impl AddressFamily for IPv4 {
         (res, len + nibble_len)
     }
 
+    fn get_index(self, start_bit: u8, len: u8) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        ((self << start_bit) >> (32 - len)) as usize
+    }
+}
+
+impl IpAddressFamily for IPv4 {
     fn from_ipaddr(addr: std::net::IpAddr) -> u32 {
         // Well, this is awkward.
         if let std::net::IpAddr::V4(addr) = addr {
@@ -130,16 +190,6 @@ impl AddressFamily for IPv4 {
     fn into_ipaddr(self) -> std::net::IpAddr {
         std::net::IpAddr::V4(std::net::Ipv4Addr::from(self))
     }
-
-    fn dangerously_truncate_to_u32(self) -> u32 {
-        // not dangerous at all.
-        self as u32
-    }
-
-    fn dangerously_truncate_to_usize(self) -> usize {
-        // not dangerous at all.
-        self as usize
-    }
 }
 
 //-------------- Ipv6 Type --------------------------------------------------
@@ -215,6 +265,16 @@ impl AddressFamily for IPv6 {
     //     }
     // }
 
+    fn get_index(self, start_bit: u8, len: u8) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        assert_index_len(len);
+        ((self << start_bit) >> (128 - len)) as usize
+    }
+}
+
+impl IpAddressFamily for IPv6 {
     fn from_ipaddr(net: std::net::IpAddr) -> u128 {
         if let std::net::IpAddr::V6(addr) = net {
             addr.octets()[15] as u128
@@ -241,15 +301,417 @@ impl AddressFamily for IPv6 {
     fn into_ipaddr(self) -> std::net::IpAddr {
         std::net::IpAddr::V6(std::net::Ipv6Addr::from(self))
     }
+}
+
+//-------------- 64-bit Type ------------------------------------------------
+
+/// Exactly fitting 64 bits (8 octets), for keys that aren't IP addresses.
+impl AddressFamily for u64 {
+    const BITMASK: u64 = 0x1u64.rotate_right(1);
+    const BITS: u8 = 64;
+
+    fn fmt_net(net: Self) -> String {
+        format!("{:#018x}", net)
+    }
+
+    fn get_nibble(net: Self, start_bit: u8, len: u8) -> u32 {
+        ((net << start_bit) >> ((64 - len) % 64)) as u32
+    }
+
+    fn add_nibble(self, len: u8, nibble: u32, nibble_len: u8) -> (Self, u8) {
+        let res = self | ((nibble as u64) << (64 - len - nibble_len));
+        (res, len + nibble_len)
+    }
+
+    fn truncate_to_len(self, len: u8) -> Self {
+        match len {
+            0 => 0,
+            1..=63 => (self >> (64 - len)) << (64 - len),
+            64 => self,
+            _ => panic!("Can't truncate to more than 64 bits"),
+        }
+    }
+
+    fn get_index(self, start_bit: u8, len: u8) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        assert_index_len(len);
+        ((self << start_bit) >> (64 - len)) as usize
+    }
+}
+
+//-------------- Route Distinguisher ----------------------------------------
+
+/// A Route Distinguisher for BGP/MPLS IP VPNs (RFC 4364).
+///
+/// Holds the eight octets of the RD in network byte order, the first two
+/// of which are the type of the RD.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RouteDistinguisher([u8; 8]);
+
+impl RouteDistinguisher {
+    pub fn new(bytes: [u8; 8]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+
+    /// The type of the RD, 0, 1 and 2 are defined in RFC 4364.
+    pub fn rd_type(&self) -> u16 {
+        u16::from_be_bytes([self.0[0], self.0[1]])
+    }
+}
+
+impl From<u64> for RouteDistinguisher {
+    fn from(rd: u64) -> Self {
+        Self(rd.to_be_bytes())
+    }
+}
+
+impl From<RouteDistinguisher> for u64 {
+    fn from(rd: RouteDistinguisher) -> Self {
+        u64::from_be_bytes(rd.0)
+    }
+}
+
+impl std::fmt::Display for RouteDistinguisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let b = self.0;
+        match self.rd_type() {
+            0 => write!(
+                f,
+                "{}:{}",
+                u16::from_be_bytes([b[2], b[3]]),
+                u32::from_be_bytes([b[4], b[5], b[6], b[7]])
+            ),
+            1 => write!(
+                f,
+                "{}:{}",
+                std::net::Ipv4Addr::new(b[2], b[3], b[4], b[5]),
+                u16::from_be_bytes([b[6], b[7]])
+            ),
+            2 => write!(
+                f,
+                "{}:{}",
+                u32::from_be_bytes([b[2], b[3], b[4], b[5]]),
+                u16::from_be_bytes([b[6], b[7]])
+            ),
+            _ => write!(f, "{:#018x}", u64::from_be_bytes(b)),
+        }
+    }
+}
+
+// The operators the AddressFamily trait needs, for the VPN types that wrap
+// an integer (synthetic or not) that implements them.
+macro_rules! impl_vpn_af_ops {
+    ( $af: ident ) => {
+        impl std::ops::BitAnd for $af {
+            type Output = Self;
+            fn bitand(self, rhs: Self) -> Self::Output {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl std::ops::BitOr for $af {
+            type Output = Self;
+            fn bitor(self, rhs: Self) -> Self::Output {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl std::ops::Shr<u8> for $af {
+            type Output = Self;
+            fn shr(self, rhs: u8) -> Self::Output {
+                Self(self.0 >> rhs)
+            }
+        }
+
+        impl std::ops::Shl<u8> for $af {
+            type Output = Self;
+            fn shl(self, rhs: u8) -> Self::Output {
+                Self(self.0 << rhs)
+            }
+        }
+
+        impl std::ops::Sub for $af {
+            type Output = Self;
+            fn sub(self, rhs: Self) -> Self::Output {
+                Self(self.0 - rhs.0)
+            }
+        }
+
+        impl std::fmt::Display for $af {
+            fn fmt(
+                &self,
+                f: &mut std::fmt::Formatter<'_>,
+            ) -> std::fmt::Result {
+                write!(f, "{}", <$af as AddressFamily>::fmt_net(*self))
+            }
+        }
+    };
+}
+
+//-------------- VPNv4 Type -------------------------------------------------
+
+/// An IPv4 address preceded by a Route Distinguisher, for VPN-IPv4
+/// prefixes (RFC 4364).
+///
+/// The 64 bits of the RD followed by the 32 bits of the IPv4 address are
+/// stored left-aligned in an u128, so a VPN-IPv4 prefix has a length of 64
+/// plus the length of its IPv4 prefix in the tree.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct VpnIPv4(u128);
+
+impl VpnIPv4 {
+    pub fn new(rd: RouteDistinguisher, addr: std::net::Ipv4Addr) -> Self {
+        Self((u64::from(rd) as u128) << 64 | (u32::from(addr) as u128) << 32)
+    }
+
+    pub fn rd(&self) -> RouteDistinguisher {
+        RouteDistinguisher::from((self.0 >> 64) as u64)
+    }
+
+    pub fn addr(&self) -> std::net::Ipv4Addr {
+        std::net::Ipv4Addr::from((self.0 >> 32) as u32)
+    }
+}
+
+impl_vpn_af_ops!(VpnIPv4);
+
+impl std::ops::Shl for VpnIPv4 {
+    type Output = Self;
+    // A shift by the full 128 bits or more leaves nothing, instead of
+    // overflowing.
+    fn shl(self, rhs: Self) -> Self::Output {
+        match u32::try_from(rhs.0) {
+            Ok(rhs) => Self(self.0.checked_shl(rhs).unwrap_or(0)),
+            Err(_) => Self(0),
+        }
+    }
+}
+
+impl From<u32> for VpnIPv4 {
+    fn from(value: u32) -> Self {
+        Self(value as u128)
+    }
+}
+
+impl From<u16> for VpnIPv4 {
+    fn from(value: u16) -> Self {
+        Self(value as u128)
+    }
+}
+
+impl std::fmt::Binary for VpnIPv4 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Binary::fmt(&self.0, f)
+    }
+}
+
+impl Zero for VpnIPv4 {
+    fn zero() -> Self {
+        Self(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl AddressFamily for VpnIPv4 {
+    const BITMASK: Self = Self(0x1u128.rotate_right(1));
+    const BITS: u8 = 96;
+
+    fn fmt_net(net: Self) -> String {
+        format!("{}:{}", net.rd(), net.addr())
+    }
+
+    fn get_nibble(net: Self, start_bit: u8, len: u8) -> u32 {
+        if len == 0 {
+            return 0;
+        }
+        ((net.0 << start_bit) >> (128 - len)) as u32
+    }
+
+    fn add_nibble(self, len: u8, nibble: u32, nibble_len: u8) -> (Self, u8) {
+        let res = self.0 | (nibble as u128) << (128 - len - nibble_len);
+        (Self(res), len + nibble_len)
+    }
+
+    fn truncate_to_len(self, len: u8) -> Self {
+        match len {
+            0 => Self(0),
+            1..=96 => Self((self.0 >> (128 - len)) << (128 - len)),
+            _ => panic!("Can't truncate to more than 96 bits"),
+        }
+    }
+
+    fn get_index(self, start_bit: u8, len: u8) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        assert_index_len(len);
+        ((self.0 << start_bit) >> (128 - len)) as usize
+    }
+}
+
+impl IpAddressFamily for VpnIPv4 {
+    const IP_OFFSET: u8 = 64;
+
+    // There's no RD in an IpAddr, so this ends up in the all zeros RD.
+    fn from_ipaddr(net: std::net::IpAddr) -> Self {
+        if let std::net::IpAddr::V4(addr) = net {
+            Self::new(RouteDistinguisher::default(), addr)
+        } else {
+            panic!("Can't convert IPv6 to VPN-IPv4");
+        }
+    }
+
+    fn into_ipaddr(self) -> std::net::IpAddr {
+        std::net::IpAddr::V4(self.addr())
+    }
+}
 
-    fn dangerously_truncate_to_u32(self) -> u32 {
-        // this will chop off the high bits.
-        self as u32
+//-------------- VPNv6 Type -------------------------------------------------
+
+/// An IPv6 address preceded by a Route Distinguisher, for VPN-IPv6
+/// prefixes (RFC 4659).
+///
+/// The 64 bits of the RD followed by the 128 bits of the IPv6 address are
+/// stored left-aligned in an U256, so a VPN-IPv6 prefix has a length of 64
+/// plus the length of its IPv6 prefix in the tree.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct VpnIPv6(U256);
+
+impl VpnIPv6 {
+    pub fn new(rd: RouteDistinguisher, addr: std::net::Ipv6Addr) -> Self {
+        let addr = u128::from(addr);
+        Self(U256((u64::from(rd) as u128) << 64 | addr >> 64, addr << 64))
     }
 
-    fn dangerously_truncate_to_usize(self) -> usize {
-        // this will chop off the high bits.
-        self as usize
+    pub fn rd(&self) -> RouteDistinguisher {
+        RouteDistinguisher::from((self.0 .0 >> 64) as u64)
+    }
+
+    pub fn addr(&self) -> std::net::Ipv6Addr {
+        std::net::Ipv6Addr::from(self.0 .0 << 64 | self.0 .1 >> 64)
+    }
+}
+
+impl_vpn_af_ops!(VpnIPv6);
+
+impl std::ops::Shl for VpnIPv6 {
+    type Output = Self;
+    // A shift by the full 256 bits or more leaves nothing, instead of
+    // truncating the shift to its lowest eight bits.
+    fn shl(self, rhs: Self) -> Self::Output {
+        match (rhs.0 .0, u8::try_from(rhs.0 .1)) {
+            (0, Ok(rhs)) => Self(self.0 << rhs),
+            _ => Self(U256(0, 0)),
+        }
+    }
+}
+
+impl From<u32> for VpnIPv6 {
+    fn from(value: u32) -> Self {
+        Self(U256(0, value as u128))
+    }
+}
+
+impl From<u16> for VpnIPv6 {
+    fn from(value: u16) -> Self {
+        Self(U256(0, value as u128))
+    }
+}
+
+impl std::fmt::Binary for VpnIPv6 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:0128b}{:0128b}", self.0 .0, self.0 .1)
+    }
+}
+
+impl Ord for VpnIPv6 {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.0 .0, self.0 .1).cmp(&(other.0 .0, other.0 .1))
+    }
+}
+
+impl PartialOrd for VpnIPv6 {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Zero for VpnIPv6 {
+    fn zero() -> Self {
+        Self(U256(0, 0))
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl AddressFamily for VpnIPv6 {
+    const BITMASK: Self = Self(U256(0x1u128.rotate_right(1), 0));
+    const BITS: u8 = 192;
+
+    fn fmt_net(net: Self) -> String {
+        format!("{}:{}", net.rd(), net.addr())
+    }
+
+    // Nibbles are never longer than 32 bits, so they always come out of
+    // the high half after shifting.
+    fn get_nibble(net: Self, start_bit: u8, len: u8) -> u32 {
+        if len == 0 {
+            return 0;
+        }
+        ((net.0 << start_bit).0 >> (128 - len)) as u32
+    }
+
+    fn add_nibble(self, len: u8, nibble: u32, nibble_len: u8) -> (Self, u8) {
+        let shift = (256 - len as u16 - nibble_len as u16) as u8;
+        let res = self.0 | U256(0, nibble as u128) << shift;
+        (Self(res), len + nibble_len)
+    }
+
+    fn truncate_to_len(self, len: u8) -> Self {
+        match len {
+            0 => Self::zero(),
+            1..=192 => {
+                let shift = (256 - len as u16) as u8;
+                Self((self.0 >> shift) << shift)
+            }
+            _ => panic!("Can't truncate to more than 192 bits"),
+        }
+    }
+
+    fn get_index(self, start_bit: u8, len: u8) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        assert_index_len(len);
+        ((self.0 << start_bit).0 >> (128 - len)) as usize
+    }
+}
+
+impl IpAddressFamily for VpnIPv6 {
+    const IP_OFFSET: u8 = 64;
+
+    // There's no RD in an IpAddr, so this ends up in the all zeros RD.
+    fn from_ipaddr(net: std::net::IpAddr) -> Self {
+        if let std::net::IpAddr::V6(addr) = net {
+            Self::new(RouteDistinguisher::default(), addr)
+        } else {
+            panic!("Can't convert IPv4 to VPN-IPv6");
+        }
+    }
+
+    fn into_ipaddr(self) -> std::net::IpAddr {
+        std::net::IpAddr::V6(self.addr())
     }
 }
 
@@ -0,0 +1,760 @@
+//! The command language of the cli.
+//!
+//! Every line typed at the prompt or read from a query file is parsed into
+//! a [`Command`], which is then executed against the store, writing its
+//! output in a [`Format`] to a writer. A command is a name followed by
+//! its arguments and options, separated by whitespace. A line that only
+//! holds a prefix looks up its longest match, with its less- and
+//! more-specifics.
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use rotonda_store::prelude::*;
+use rotonda_store::{MatchType, PrefixAs};
+use routecore::addr::PrefixError;
+use routecore::bgp::RecordSet;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::store::Store;
+
+//------------ Help ----------------------------------------------------------
+
+/// The name, the synopsis and a description of all the commands.
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "exact",
+        "exact <prefix> [-l] [-m] [--limit <n>]",
+        "Looks up the prefix itself.",
+    ),
+    (
+        "lmp",
+        "lmp <prefix> [-l] [-m] [--limit <n>]",
+        "Looks up the longest matching prefix.",
+    ),
+    (
+        "match",
+        "match <prefix> [--type exact|longest|empty] [-l] [-m] \
+         [--limit <n>]",
+        "Looks up the prefix with a match type, longest by default.",
+    ),
+    (
+        "more",
+        "more <prefix> [--limit <n>]",
+        "Lists the more-specifics of the prefix.",
+    ),
+    (
+        "less",
+        "less <prefix> [--limit <n>]",
+        "Lists the less-specifics of the prefix.",
+    ),
+    (
+        "lookup",
+        "lookup <addr> [-l] [--limit <n>]",
+        "Looks up the longest matching prefix of an address.",
+    ),
+    (
+        "insert",
+        "insert <prefix> <asn>",
+        "Inserts the prefix with its origin AS.",
+    ),
+    ("remove", "remove <prefix>", "Removes the prefix."),
+    ("count", "count [v4|v6]", "Counts the prefixes."),
+    ("nodes", "nodes", "Counts the nodes of the trees."),
+    ("stats", "stats", "Shows the nodes and prefixes per stride."),
+    ("strides", "strides", "Shows the strides of the trees."),
+    (
+        "dump",
+        "dump [v4|v6] [> <file>]",
+        "Writes the prefixes as CSV, to the screen or to a file.",
+    ),
+    ("help", "help [<command>]", "Shows the commands, or one command."),
+    ("quit", "quit", "Leaves the cli."),
+];
+
+/// The options of the lookup commands.
+pub const OPTIONS: &[&str] = &["--less", "--limit", "--more", "--type"];
+
+/// The values of the `--type` option.
+pub const MATCH_TYPES: &[&str] = &["empty", "exact", "longest"];
+
+const OPTIONS_HELP: &str = "\
+Options:
+  -l, --less         include the less-specifics of the prefix
+  -m, --more         include the more-specifics of the prefix
+  --limit <n>        show at most n less- or more-specifics
+  --type <type>      exact, longest or empty
+A line with only a prefix is the same as `lmp <prefix> -l -m`.";
+
+fn write_help(
+    out: &mut dyn Write,
+    command: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        None => {
+            writeln!(out, "Commands:")?;
+            for (_, synopsis, descr) in COMMANDS {
+                writeln!(out, "  {}\n      {}", synopsis, descr)?;
+            }
+            writeln!(out, "{}", OPTIONS_HELP)?;
+        }
+        Some(name) => {
+            let (_, synopsis, descr) = COMMANDS
+                .iter()
+                .find(|(cmd, _, _)| *cmd == name)
+                .ok_or_else(|| format!("Error: unknown command {}", name))?;
+            writeln!(out, "{}\n    {}", synopsis, descr)?;
+        }
+    }
+    Ok(())
+}
+
+//------------ Command -------------------------------------------------------
+
+/// A parsed line of input.
+#[derive(Debug)]
+pub enum Command {
+    /// Looks up a prefix, with the options for the query.
+    Match {
+        prefix: Prefix,
+        options: MatchOptions,
+        limit: Option<usize>,
+    },
+    MoreSpecifics {
+        prefix: Prefix,
+        limit: Option<usize>,
+    },
+    LessSpecifics {
+        prefix: Prefix,
+        limit: Option<usize>,
+    },
+    Insert {
+        prefix: Prefix,
+        asn: u32,
+    },
+    Remove(Prefix),
+    Count(AfSelector),
+    Nodes,
+    Stats,
+    Strides,
+    /// Dumps the prefixes to the file, or to the output if there is none.
+    Dump {
+        af: AfSelector,
+        file: Option<String>,
+    },
+    Help(Option<String>),
+    Quit,
+}
+
+impl Command {
+    /// Parses a line of input.
+    ///
+    /// Returns `Ok(None)` for an empty line. The error is a message for
+    /// the user.
+    pub fn parse(line: &str) -> Result<Option<Self>, String> {
+        let mut words = line.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let args = Args::parse(words)?;
+
+        let command = match name {
+            "exact" | "lmp" | "match" => {
+                let match_type = match (name, &args.match_type) {
+                    ("exact", None) => MatchType::ExactMatch,
+                    ("match", Some(match_type)) => match_type.clone(),
+                    (_, None) => MatchType::LongestMatch,
+                    (_, Some(_)) => {
+                        return Err(format!(
+                            "Error: {} doesn't take a --type",
+                            name
+                        ))
+                    }
+                };
+                let prefix = parse_prefix(args.single(name)?)?;
+                Command::Match {
+                    prefix,
+                    options: args.match_options(match_type),
+                    limit: args.limit,
+                }
+            }
+            "more" | "less" => {
+                args.only_limit(name)?;
+                let prefix = parse_prefix(args.single(name)?)?;
+                if name == "more" {
+                    Command::MoreSpecifics {
+                        prefix,
+                        limit: args.limit,
+                    }
+                } else {
+                    Command::LessSpecifics {
+                        prefix,
+                        limit: args.limit,
+                    }
+                }
+            }
+            "lookup" => {
+                if args.more || args.match_type.is_some() {
+                    return Err(
+                        "Error: lookup only takes --less and --limit".into()
+                    );
+                }
+                let addr = args.single(name)?;
+                let addr = addr.parse::<IpAddr>().map_err(|err| {
+                    format!("Error: can't parse address {}: {}", addr, err)
+                })?;
+                let len = if addr.is_ipv4() { 32 } else { 128 };
+                Command::Match {
+                    prefix: Prefix::new(addr, len)
+                        .map_err(|err| format!("Error: {}", err))?,
+                    options: args.match_options(MatchType::LongestMatch),
+                    limit: args.limit,
+                }
+            }
+            "insert" => {
+                args.no_options(name)?;
+                match args.words[..] {
+                    [prefix, asn] => Command::Insert {
+                        prefix: parse_prefix(prefix)?,
+                        asn: asn.trim_start_matches("AS").parse().map_err(
+                            |_| format!("Error: invalid AS number {}", asn),
+                        )?,
+                    },
+                    _ => return Err(usage(name)),
+                }
+            }
+            "remove" => {
+                args.no_options(name)?;
+                Command::Remove(parse_prefix(args.single(name)?)?)
+            }
+            "count" => {
+                args.no_options(name)?;
+                Command::Count(args.af(name)?)
+            }
+            "dump" => {
+                if args.has_options() {
+                    return Err(usage(name));
+                }
+                Command::Dump {
+                    af: args.af(name)?,
+                    file: args.file.map(str::to_string),
+                }
+            }
+            "nodes" | "stats" | "strides" | "quit" | "exit" => {
+                args.no_options(name)?;
+                if !args.words.is_empty() {
+                    return Err(usage(name));
+                }
+                match name {
+                    "nodes" => Command::Nodes,
+                    "stats" => Command::Stats,
+                    "strides" => Command::Strides,
+                    _ => Command::Quit,
+                }
+            }
+            "help" => {
+                args.no_options(name)?;
+                match args.words[..] {
+                    [] => Command::Help(None),
+                    [command] => Command::Help(Some(command.to_string())),
+                    _ => return Err(usage(name)),
+                }
+            }
+            // A bare prefix, as the cli has always understood it.
+            _ if name.contains('/') && args.words.is_empty() => {
+                Command::Match {
+                    prefix: parse_prefix(name)?,
+                    options: MatchOptions {
+                        match_type: MatchType::LongestMatch,
+                        include_all_records: false,
+                        include_less_specifics: true,
+                        include_more_specifics: true,
+                    },
+                    limit: args.limit,
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Error: unknown command {}, try help",
+                    name
+                ))
+            }
+        };
+        Ok(Some(command))
+    }
+}
+
+fn usage(name: &str) -> String {
+    match COMMANDS.iter().find(|(cmd, _, _)| *cmd == name) {
+        Some((_, synopsis, _)) => format!("Usage: {}", synopsis),
+        None => format!("Usage: {}", name),
+    }
+}
+
+fn parse_prefix(s: &str) -> Result<Prefix, String> {
+    let (addr, len) = s.split_once('/').ok_or_else(|| {
+        format!("Error: can't parse prefix {}, add a /<len> part", s)
+    })?;
+    let addr = addr.parse::<IpAddr>().map_err(|err| {
+        format!("Error: can't parse address {}: {}", addr, err)
+    })?;
+    let len = len.parse::<u8>().map_err(|_| {
+        format!("Error: can't parse prefix length {}", len)
+    })?;
+    match Prefix::new(addr, len) {
+        Ok(prefix) => Ok(prefix),
+        Err(PrefixError::NonZeroHost) => {
+            let prefix = Prefix::new_relaxed(addr, len)
+                .map_err(|err| format!("Error: {}", err))?;
+            Err(format!(
+                "Error: {} has bits set right of the prefix length, \
+                 did you mean {}?",
+                s, prefix
+            ))
+        }
+        Err(err) => Err(format!("Error: {}: {}", s, err)),
+    }
+}
+
+//------------ Args ----------------------------------------------------------
+
+/// The arguments and options after the name of a command.
+#[derive(Default)]
+struct Args<'a> {
+    words: Vec<&'a str>,
+    less: bool,
+    more: bool,
+    limit: Option<usize>,
+    match_type: Option<MatchType>,
+    file: Option<&'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(
+        mut words: impl Iterator<Item = &'a str>,
+    ) -> Result<Self, String> {
+        let mut args = Args::default();
+        while let Some(word) = words.next() {
+            match word {
+                "-l" | "--less" => args.less = true,
+                "-m" | "--more" => args.more = true,
+                "--limit" => {
+                    let limit = words.next().unwrap_or_default();
+                    args.limit = Some(limit.parse().map_err(|_| {
+                        format!("Error: invalid limit {:?}", limit)
+                    })?);
+                }
+                "--type" => {
+                    args.match_type = Some(match words.next() {
+                        Some("exact") => MatchType::ExactMatch,
+                        Some("longest") => MatchType::LongestMatch,
+                        Some("empty") => MatchType::EmptyMatch,
+                        _ => {
+                            return Err("Error: --type takes exact, \
+                                        longest or empty"
+                                .into())
+                        }
+                    });
+                }
+                ">" => {
+                    args.file = Some(words.next().ok_or_else(|| {
+                        "Error: missing file name after >".to_string()
+                    })?);
+                }
+                _ if word.starts_with('>') => {
+                    args.file = word.strip_prefix('>')
+                }
+                _ if word.starts_with('-') => {
+                    return Err(format!("Error: unknown option {}", word))
+                }
+                _ => args.words.push(word),
+            }
+        }
+        Ok(args)
+    }
+
+    fn has_options(&self) -> bool {
+        self.less
+            || self.more
+            || self.limit.is_some()
+            || self.match_type.is_some()
+    }
+
+    fn no_options(&self, name: &str) -> Result<(), String> {
+        if self.has_options() || self.file.is_some() {
+            return Err(format!("Error: {} doesn't take options", name));
+        }
+        Ok(())
+    }
+
+    fn only_limit(&self, name: &str) -> Result<(), String> {
+        if self.less || self.more || self.match_type.is_some() {
+            return Err(format!("Error: {} only takes --limit", name));
+        }
+        Ok(())
+    }
+
+    fn single(&self, name: &str) -> Result<&'a str, String> {
+        match self.words[..] {
+            [word] => Ok(word),
+            _ => Err(usage(name)),
+        }
+    }
+
+    fn af(&self, name: &str) -> Result<AfSelector, String> {
+        match self.words[..] {
+            [] => Ok(AfSelector::Both),
+            ["v4"] => Ok(AfSelector::Ipv4),
+            ["v6"] => Ok(AfSelector::Ipv6),
+            _ => Err(usage(name)),
+        }
+    }
+
+    fn match_options(&self, match_type: MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: self.less,
+            include_more_specifics: self.more,
+        }
+    }
+}
+
+//------------ Format --------------------------------------------------------
+
+/// The format of the output of the commands.
+///
+/// With JSON, every command writes one JSON value on a line of its own.
+/// With CSV, the lookups write a row for every prefix in their result,
+/// with the prefix looked for, its relation to the result (`match`,
+/// `less`, `more` or `none`), the prefix and its origin AS.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!(
+                "Error: unknown format {}, use text, json or csv",
+                s
+            )),
+        }
+    }
+}
+
+//------------ Executing -----------------------------------------------------
+
+/// Executes a command on the store, writing its output to `out`.
+///
+/// `Command::Quit` is left to the caller and does nothing here.
+pub fn execute(
+    store: &mut Store,
+    command: &Command,
+    format: Format,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let guard = &epoch::pin();
+    match command {
+        Command::Match {
+            prefix,
+            options,
+            limit,
+        } => {
+            let res = store.match_prefix(prefix, options, guard);
+            write_result(out, format, prefix, res, *limit)?;
+        }
+        Command::MoreSpecifics { prefix, limit } => {
+            let res = store.more_specifics_from(prefix, guard);
+            write_result(out, format, prefix, res, *limit)?;
+        }
+        Command::LessSpecifics { prefix, limit } => {
+            let res = store.less_specifics_from(prefix, guard);
+            write_result(out, format, prefix, res, *limit)?;
+        }
+        Command::Insert { prefix, asn } => {
+            store.insert(prefix, PrefixAs(*asn))?;
+            match format {
+                Format::Json => write_json(
+                    out,
+                    &json!({
+                        "command": "insert",
+                        "prefix": prefix.to_string(),
+                        "asn": asn,
+                    }),
+                )?,
+                _ => writeln!(out, "inserted {} AS{}", prefix, asn)?,
+            }
+        }
+        Command::Remove(prefix) => {
+            let removed = store.remove(prefix)?;
+            match (format, removed) {
+                (Format::Json, removed) => write_json(
+                    out,
+                    &json!({
+                        "command": "remove",
+                        "prefix": prefix.to_string(),
+                        "removed": removed.map(|meta| meta.0),
+                    }),
+                )?,
+                (_, Some(meta)) => {
+                    writeln!(out, "removed {} {}", prefix, meta)?
+                }
+                (_, None) => writeln!(out, "{} not found", prefix)?,
+            }
+        }
+        Command::Count(af) => {
+            let mut counts = vec![];
+            if *af != AfSelector::Ipv6 {
+                counts.push(("ipv4", store.prefixes_v4_len()));
+            }
+            if *af != AfSelector::Ipv4 {
+                counts.push(("ipv6", store.prefixes_v6_len()));
+            }
+            if *af == AfSelector::Both {
+                counts.push((
+                    "total",
+                    store.prefixes_v4_len() + store.prefixes_v6_len(),
+                ));
+            }
+            write_counts(out, format, "prefixes", &counts)?;
+        }
+        Command::Nodes => {
+            let (v4, v6) = (store.nodes_v4_len(), store.nodes_v6_len());
+            let counts = [("ipv4", v4), ("ipv6", v6), ("total", v4 + v6)];
+            write_counts(out, format, "nodes", &counts)?;
+        }
+        Command::Stats => match format {
+            Format::Json => write_json(out, &store.stats())?,
+            _ => write!(out, "{}", store.stats())?,
+        },
+        Command::Strides => {
+            let strides = store.strides();
+            let (v4, v6) = (strides.v4, strides.v6);
+            match format {
+                Format::Json => {
+                    write_json(out, &json!({ "ipv4": v4, "ipv6": v6 }))?
+                }
+                _ => {
+                    writeln!(out, "ipv4 strides :\t{:?}", v4)?;
+                    writeln!(out, "ipv6 strides :\t{:?}", v6)?;
+                }
+            }
+        }
+        // A dump to a file is always CSV, so it can be loaded again.
+        Command::Dump { af, file } => match file {
+            Some(file) => {
+                let mut writer = BufWriter::new(File::create(file)?);
+                let count = dump(store, *af, Format::Csv, &mut writer)?;
+                writer.flush()?;
+                match format {
+                    Format::Json => write_json(
+                        out,
+                        &json!({
+                            "command": "dump",
+                            "file": file,
+                            "count": count,
+                        }),
+                    )?,
+                    _ => {
+                        writeln!(out, "wrote {} prefixes to {}", count, file)?
+                    }
+                }
+            }
+            None => {
+                dump(store, *af, format, out)?;
+            }
+        },
+        Command::Help(command) => write_help(out, command.as_deref())?,
+        Command::Quit => {}
+    }
+    Ok(())
+}
+
+fn write_result(
+    out: &mut dyn Write,
+    format: Format,
+    query: &Prefix,
+    mut res: QueryResult<PrefixAs>,
+    limit: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => {
+            writeln!(out, "match type: {}", res.match_type)?;
+            match (res.prefix, res.prefix_meta) {
+                (Some(prefix), Some(meta)) => {
+                    writeln!(out, "prefix: {} {}", prefix, meta)?
+                }
+                (Some(prefix), None) => writeln!(out, "prefix: {}", prefix)?,
+                (None, _) => writeln!(out, "prefix: none")?,
+            }
+            if let Some(records) = &res.less_specifics {
+                write_records(out, "less-specifics", records, limit)?;
+            }
+            if let Some(records) = &res.more_specifics {
+                write_records(out, "more-specifics", records, limit)?;
+            }
+        }
+        Format::Json => {
+            if let Some(limit) = limit {
+                for records in res
+                    .less_specifics
+                    .iter_mut()
+                    .chain(res.more_specifics.iter_mut())
+                {
+                    records.v4.truncate(limit);
+                    records.v6.truncate(limit - records.v4.len());
+                }
+            }
+            write_json(out, &res)?;
+        }
+        Format::Csv => {
+            match res.prefix {
+                Some(prefix) => write_row(
+                    out,
+                    query,
+                    "match",
+                    &prefix,
+                    res.prefix_meta,
+                )?,
+                None => writeln!(out, "{},none,,", query)?,
+            }
+            for (relation, records) in [
+                ("less", &res.less_specifics),
+                ("more", &res.more_specifics),
+            ] {
+                let records = match records {
+                    Some(records) => records,
+                    None => continue,
+                };
+                let limit = limit.unwrap_or(usize::MAX);
+                for record in
+                    records.v4.iter().chain(records.v6.iter()).take(limit)
+                {
+                    write_row(
+                        out,
+                        query,
+                        relation,
+                        &record.prefix,
+                        Some(&*record.meta),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_records(
+    out: &mut dyn Write,
+    title: &str,
+    records: &RecordSet<PrefixAs>,
+    limit: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let total = records.v4.len() + records.v6.len();
+    let shown = limit.map_or(total, |limit| limit.min(total));
+    if shown < total {
+        writeln!(out, "{} ({}, showing {}):", title, total, shown)?;
+    } else {
+        writeln!(out, "{} ({}):", title, total)?;
+    }
+    for record in records.v4.iter().chain(records.v6.iter()).take(shown) {
+        writeln!(out, "  {} {}", record.prefix, record.meta)?;
+    }
+    Ok(())
+}
+
+fn write_row(
+    out: &mut dyn Write,
+    query: &Prefix,
+    relation: &str,
+    prefix: &Prefix,
+    meta: Option<&PrefixAs>,
+) -> Result<(), Box<dyn Error>> {
+    match meta {
+        Some(meta) => {
+            writeln!(out, "{},{},{},{}", query, relation, prefix, meta.0)?
+        }
+        None => writeln!(out, "{},{},{},", query, relation, prefix)?,
+    }
+    Ok(())
+}
+
+fn write_counts(
+    out: &mut dyn Write,
+    format: Format,
+    kind: &str,
+    counts: &[(&str, usize)],
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => {
+            for (name, count) in counts {
+                writeln!(out, "{} {} :\t{}", name, kind, count)?;
+            }
+        }
+        Format::Json => {
+            let counts: serde_json::Map<_, _> = counts
+                .iter()
+                .map(|(name, count)| (name.to_string(), json!(count)))
+                .collect();
+            write_json(out, &counts)?;
+        }
+        Format::Csv => {
+            for (name, count) in counts {
+                writeln!(out, "{},{}", name, count)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_json(
+    out: &mut dyn Write,
+    value: &impl Serialize,
+) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer(&mut *out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+// Writes the prefixes, as JSON Lines or as CSV that the loader reads
+// back, and returns the number of prefixes written.
+fn dump(
+    store: &Store,
+    af: AfSelector,
+    format: Format,
+    out: &mut dyn Write,
+) -> Result<usize, Box<dyn Error>> {
+    let guard = &epoch::pin();
+    let mut count = 0;
+    if format != Format::Json {
+        writeln!(out, "prefix,asn")?;
+    }
+    for record in store.prefixes_iter_af(af, guard) {
+        if format == Format::Json {
+            write_json(
+                out,
+                &json!({
+                    "prefix": record.prefix.to_string(),
+                    "meta": record.meta.0,
+                }),
+            )?;
+        } else {
+            writeln!(out, "{},{}", record.prefix, record.meta.0)?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,58 @@
+//! Tab completion for the prompt.
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::commands::{COMMANDS, MATCH_TYPES, OPTIONS};
+
+//------------ CliHelper -----------------------------------------------------
+
+/// Completes the command names, the options and their values.
+pub struct CliHelper;
+
+impl CliHelper {
+    fn candidates(words: &[&str], word: &str) -> Vec<String> {
+        let choices: Vec<&str> = match words {
+            [] | ["help"] => {
+                COMMANDS.iter().map(|(name, _, _)| *name).collect()
+            }
+            ["count"] | ["dump"] => vec!["v4", "v6"],
+            [.., "--type"] => MATCH_TYPES.to_vec(),
+            _ if word.starts_with('-') => OPTIONS.to_vec(),
+            _ => vec![],
+        };
+        choices
+            .into_iter()
+            .filter(|choice| choice.starts_with(word))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Completer for CliHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let words: Vec<&str> = line[..start].split_whitespace().collect();
+        Ok((start, Self::candidates(&words, &line[start..])))
+    }
+}
+
+impl Hinter for CliHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CliHelper {}
+
+impl Validator for CliHelper {}
+
+impl Helper for CliHelper {}
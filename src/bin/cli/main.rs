@@ -0,0 +1,375 @@
+#![cfg(feature = "cli")]
+
+mod commands;
+mod completion;
+mod store;
+
+use ansi_term::Colour;
+use rotonda_store::loader::Loader;
+use rotonda_store::mrt;
+use rotonda_store::prelude::*;
+use rotonda_store::{MultiThreadedStore, PrefixAs, SingleThreadedStore};
+
+use std::env;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[cfg(feature = "cli")]
+use rustyline::error::ReadlineError;
+#[cfg(feature = "cli")]
+use rustyline::Editor;
+
+use commands::{execute, Command, Format};
+use completion::CliHelper;
+use store::Store;
+
+const USAGE: &str = "\
+Usage: cli [<command>] [options] [<file>]
+
+Loads the prefixes in <file> into a store and starts a prompt, or runs
+the commands in a query file and exits.
+
+Commands:
+  prompt                   start a prompt, the default without --query
+  query                    run the commands in the query file, or from
+                           stdin without one, and exit
+
+Options:
+  -l, --load <file>        the file to load, same as <file>
+  -q, --query <file>       run the commands in the file, - for stdin
+  -f, --format <format>    the output format: text, json or csv
+      --v4-strides <list>  the strides of the IPv4 tree, e.g. 4,4,8,8,8
+      --v6-strides <list>  the strides of the IPv6 tree
+      --history <file>     the history file of the prompt
+  -h, --help               show this help
+
+Files ending in .mrt are loaded as MRT RIB dumps, files ending in .jsonl
+as JSON Lines, anything else as CSV. CSV files with a `prefix` column are
+read as written by the dump command, others as `ip,len,asn` with a
+header line that is skipped. The strides make the cli use the
+single-threaded store, that has no fixed strides. Type `help` at the
+prompt, or put it in a query file, for the commands.
+
+In a query file, empty lines and lines starting with # are skipped.
+The exit status is 1 if a command failed and 2 if the prefixes couldn't
+be loaded. For example:
+
+  cli query --load table.csv --query queries.txt --format json";
+
+const HISTORY_FILE: &str = "/tmp/rotonda-store-history.txt";
+
+//------------ Config --------------------------------------------------------
+
+struct Config {
+    load: Option<PathBuf>,
+    query: Option<PathBuf>,
+    format: Format,
+    v4_strides: Option<Vec<u8>>,
+    v6_strides: Option<Vec<u8>>,
+    history: PathBuf,
+}
+
+impl Config {
+    fn from_args(
+        args: impl Iterator<Item = OsString>,
+    ) -> Result<Self, String> {
+        let mut config = Config {
+            load: None,
+            query: None,
+            format: Format::Text,
+            v4_strides: None,
+            v6_strides: None,
+            history: PathBuf::from(HISTORY_FILE),
+        };
+        // The command, if there is one, comes before the options.
+        let mut args = args.peekable();
+        let command = match args.peek().and_then(|arg| arg.to_str()) {
+            Some(command @ ("prompt" | "query")) => Some(command.to_string()),
+            _ => None,
+        };
+        if command.is_some() {
+            args.next();
+        }
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next().ok_or_else(|| {
+                    format!("Error: missing value for {:?}", arg)
+                })
+            };
+            match arg.to_str() {
+                Some("-h") | Some("--help") => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                Some("-l") | Some("--load") => {
+                    config.load = Some(value()?.into())
+                }
+                Some("-q") | Some("--query") => {
+                    config.query = Some(value()?.into())
+                }
+                Some("-f") | Some("--format") => {
+                    config.format = value()?.to_string_lossy().parse()?
+                }
+                Some("--v4-strides") => {
+                    config.v4_strides = Some(parse_strides(&value()?, 32)?)
+                }
+                Some("--v6-strides") => {
+                    config.v6_strides = Some(parse_strides(&value()?, 128)?)
+                }
+                Some("--history") => config.history = value()?.into(),
+                Some(flag) if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("Error: unknown option {}", flag))
+                }
+                _ if config.load.is_none() => {
+                    config.load = Some(PathBuf::from(&arg))
+                }
+                _ => return Err("Error: more than one file to load".into()),
+            }
+        }
+        match command.as_deref() {
+            Some("prompt") if config.query.is_some() => {
+                return Err("Error: the prompt takes no query file".into())
+            }
+            Some("query") if config.query.is_none() => {
+                config.query = Some(PathBuf::from("-"))
+            }
+            _ => {}
+        }
+        Ok(config)
+    }
+
+    fn store(&self) -> Result<Store, Box<dyn Error>> {
+        match (&self.v4_strides, &self.v6_strides) {
+            (None, None) => Ok(Store::Multi(MultiThreadedStore::new()?)),
+            (v4, v6) => Ok(Store::Single(SingleThreadedStore::new(
+                v4.clone().unwrap_or_else(|| vec![4]),
+                v6.clone().unwrap_or_else(|| vec![4]),
+            ))),
+        }
+    }
+}
+
+// Parses a list of strides, checking them the way the trees would, so the
+// store doesn't panic on them.
+fn parse_strides(list: &OsStr, bits: u8) -> Result<Vec<u8>, String> {
+    let list = list.to_string_lossy();
+    let strides = list
+        .split(',')
+        .map(|stride| match stride.trim().parse::<u8>() {
+            Ok(stride) if (3..=8).contains(&stride) => Ok(stride),
+            _ => Err(format!("Error: invalid stride {:?}", stride)),
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    // The strides repeat until they cover all the bits of an address,
+    // and mustn't go over.
+    let mut sum = 0;
+    for stride in strides.iter().cycle() {
+        sum += stride;
+        if sum >= bits - 1 {
+            break;
+        }
+    }
+    if sum != bits {
+        return Err(format!(
+            "Error: strides {} don't add up to {} bits",
+            list, bits
+        ));
+    }
+    Ok(strides)
+}
+
+//------------ Loading -------------------------------------------------------
+
+fn load(store: &mut Store, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let loader = match path.extension().and_then(OsStr::to_str) {
+        // MRT RIB dumps are loaded with the origin AS of every route as
+        // its meta-data. They only go into a multi-threaded store, so for
+        // the other one they go through a temporary one.
+        Some("mrt") => {
+            return match store {
+                Store::Multi(store) => {
+                    Ok(mrt::load_file(store, path, origin_as)?)
+                }
+                Store::Single(store) => {
+                    let tmp = MultiThreadedStore::new()?;
+                    let count = mrt::load_file(&tmp, path, origin_as)?;
+                    for record in tmp.prefixes_iter(&epoch::pin()) {
+                        let meta = record.meta.into_owned();
+                        store.insert(&record.prefix, meta)?;
+                    }
+                    Ok(count)
+                }
+            };
+        }
+        Some("jsonl") => Loader::json_lines(),
+        _ => {
+            let mut header = String::new();
+            BufReader::new(File::open(path)?).read_line(&mut header)?;
+            if header.split(',').any(|column| column.trim() == "prefix") {
+                Loader::csv()
+            } else {
+                // The files in data/ have a header line that isn't a
+                // useful one.
+                Loader::csv()
+                    .columns(&["ip", "len", "asn"])
+                    .prefix_column("ip")
+                    .len_column("len")
+            }
+        }
+    };
+    let source = BufReader::new(File::open(path)?);
+    let count = match store {
+        Store::Multi(store) => loader.load_into(source, store)?,
+        Store::Single(store) => loader.load_into(source, store)?,
+    };
+    Ok(count)
+}
+
+fn origin_as(entry: &mrt::MrtRibEntry) -> Option<PrefixAs> {
+    entry.origin_as().map(PrefixAs)
+}
+
+//------------ Running -------------------------------------------------------
+
+// Runs the commands from `source`, reporting the failed ones on stderr.
+// Returns whether all of them succeeded.
+fn run_batch(
+    store: &mut Store,
+    source: impl BufRead,
+    format: Format,
+) -> Result<bool, Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut ok = true;
+    for (idx, line) in source.lines().enumerate() {
+        let line = line?;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let res = match Command::parse(&line) {
+            Ok(None) => continue,
+            Ok(Some(Command::Quit)) => break,
+            Ok(Some(command)) => execute(store, &command, format, &mut out)
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            eprintln!("line {}: {}", idx + 1, err);
+            ok = false;
+        }
+    }
+    Ok(ok)
+}
+
+fn run_prompt(
+    store: &mut Store,
+    format: Format,
+    history: &Path,
+) -> Result<(), Box<dyn Error>> {
+    store.print_funky_stats();
+    println!("Type help for the list of commands.");
+
+    let mut rl = Editor::<CliHelper>::new();
+    rl.set_helper(Some(CliHelper));
+    if rl.load_history(history).is_err() {
+        println!("No previous history.");
+    }
+    loop {
+        match rl.readline("(rotonda-store)> ") {
+            Ok(line) => {
+                let command = match Command::parse(&line) {
+                    Ok(Some(command)) => command,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        println!("{}", Colour::Red.paint(err));
+                        continue;
+                    }
+                };
+                rl.add_history_entry(line.as_str());
+                if let Command::Quit = command {
+                    break;
+                }
+                let mut out = io::stdout();
+                if let Err(err) = execute(store, &command, format, &mut out) {
+                    println!("{}", Colour::Red.paint(err.to_string()));
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(_err) => {
+                println!("Error: Can't parse the command");
+                continue;
+            }
+        }
+    }
+    rl.save_history(history)?;
+    Ok(())
+}
+
+fn main() {
+    #[cfg(feature = "cli")]
+    env_logger::init();
+
+    let config = match Config::from_args(env::args_os().skip(1)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut store = match config.store() {
+        Ok(store) => store,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+    if let Some(path) = &config.load {
+        let start = std::time::Instant::now();
+        match load(&mut store, path) {
+            Ok(count) => eprintln!(
+                "finished loading {} prefixes into the tree in {} msecs...",
+                count,
+                start.elapsed().as_millis()
+            ),
+            Err(err) => {
+                eprintln!("Error: can't load {}: {}", path.display(), err);
+                process::exit(2);
+            }
+        }
+    }
+
+    let res = match &config.query {
+        Some(path) if path.as_os_str() == "-" => {
+            let stdin = io::stdin();
+            run_batch(&mut store, stdin.lock(), config.format)
+        }
+        Some(path) => File::open(path)
+            .map_err(Into::into)
+            .and_then(|file| {
+                run_batch(&mut store, BufReader::new(file), config.format)
+            }),
+        None => run_prompt(&mut store, config.format, &config.history)
+            .map(|_| true),
+    };
+    match res {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! The store the cli works on.
+use std::error::Error;
+
+use rotonda_store::prelude::*;
+use rotonda_store::{
+    MultiThreadedStore, PrefixAs, SingleThreadedStore, Strides,
+};
+
+//------------ Store ---------------------------------------------------------
+
+/// Either store, with the methods the commands need.
+///
+/// The multi-threaded store has its strides compiled in, so the
+/// single-threaded store is used when the strides are given on the
+/// command line.
+pub enum Store {
+    Multi(MultiThreadedStore<PrefixAs>),
+    Single(SingleThreadedStore<PrefixAs>),
+}
+
+impl Store {
+    pub fn match_prefix<'a>(
+        &'a self,
+        prefix: &Prefix,
+        options: &MatchOptions,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, PrefixAs> {
+        match self {
+            Store::Multi(store) => store.match_prefix(prefix, options, guard),
+            Store::Single(store) => store.match_prefix(prefix, options),
+        }
+    }
+
+    pub fn more_specifics_from<'a>(
+        &'a self,
+        prefix: &Prefix,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, PrefixAs> {
+        match self {
+            Store::Multi(store) => store.more_specifics_from(prefix, guard),
+            Store::Single(store) => store.more_specifics_from(prefix),
+        }
+    }
+
+    pub fn less_specifics_from<'a>(
+        &'a self,
+        prefix: &Prefix,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, PrefixAs> {
+        match self {
+            Store::Multi(store) => store.less_specifics_from(prefix, guard),
+            Store::Single(store) => store.less_specifics_from(prefix),
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        prefix: &Prefix,
+        meta: PrefixAs,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Store::Multi(store) => store.insert(prefix, meta),
+            Store::Single(store) => store.insert(prefix, meta),
+        }
+    }
+
+    pub fn remove(
+        &mut self,
+        prefix: &Prefix,
+    ) -> Result<Option<PrefixAs>, Box<dyn Error>> {
+        match self {
+            Store::Multi(store) => PrefixStoreMut::remove(store, prefix),
+            Store::Single(store) => Ok(store.remove(prefix)),
+        }
+    }
+
+    pub fn prefixes_iter_af<'a>(
+        &'a self,
+        af: AfSelector,
+        guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, PrefixAs>> + 'a> {
+        match self {
+            Store::Multi(store) => {
+                Box::new(store.prefixes_iter_af(af, guard))
+            }
+            Store::Single(store) => Box::new(store.prefixes_iter_af(af)),
+        }
+    }
+
+    pub fn prefixes_v4_len(&self) -> usize {
+        match self {
+            Store::Multi(store) => store.prefixes_v4_len(),
+            Store::Single(store) => store.prefixes_v4_len(),
+        }
+    }
+
+    pub fn prefixes_v6_len(&self) -> usize {
+        match self {
+            Store::Multi(store) => store.prefixes_v6_len(),
+            Store::Single(store) => store.prefixes_v6_len(),
+        }
+    }
+
+    pub fn nodes_v4_len(&self) -> usize {
+        match self {
+            Store::Multi(store) => store.nodes_v4_len(),
+            Store::Single(store) => store.nodes_v4_len(),
+        }
+    }
+
+    pub fn nodes_v6_len(&self) -> usize {
+        match self {
+            Store::Multi(store) => store.nodes_v6_len(),
+            Store::Single(store) => store.nodes_v6_len(),
+        }
+    }
+
+    pub fn stats(&self) -> Stats<'_> {
+        match self {
+            Store::Multi(store) => store.stats(),
+            Store::Single(store) => store.stats(),
+        }
+    }
+
+    /// The strides of the IPv4 and the IPv6 tree, from the root down.
+    pub fn strides(&self) -> Strides<'_> {
+        match self {
+            Store::Multi(store) => store.strides(),
+            Store::Single(store) => store.strides(),
+        }
+    }
+
+    pub fn print_funky_stats(&self) {
+        match self {
+            Store::Multi(store) => store.print_funky_stats(),
+            Store::Single(store) => store.print_funky_stats(),
+        }
+    }
+}
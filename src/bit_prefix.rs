@@ -0,0 +1,157 @@
+use std::fmt;
+
+use routecore::record::Meta;
+
+use crate::af::{AddressFamily, IpAddressFamily};
+use crate::prefix_record::InternalPrefixRecord;
+use crate::MatchType;
+
+//------------ BitPrefix ----------------------------------------------------
+
+/// A prefix of a fixed length bit string.
+///
+/// This is the counterpart of [`routecore::addr::Prefix`] for keys of any
+/// [`AddressFamily`], e.g. AS numbers or MPLS labels in an `u32`, or MAC
+/// addresses in an `u64`: the first `len` bits of `net`, with all the bits
+/// after that set to zero.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BitPrefix<AF: AddressFamily> {
+    net: AF,
+    len: u8,
+}
+
+impl<AF: AddressFamily> BitPrefix<AF> {
+    /// Creates a new prefix of the first `len` bits of `net`.
+    ///
+    /// Returns an error if `len` is longer than the address family, or if
+    /// `net` has any bits set after the first `len` bits.
+    pub fn new(net: AF, len: u8) -> Result<Self, BitPrefixError> {
+        if len > AF::BITS {
+            return Err(BitPrefixError::LenTooLong);
+        }
+        if net.truncate_to_len(len) != net {
+            return Err(BitPrefixError::HostBitsSet);
+        }
+        Ok(Self { net, len })
+    }
+
+    /// Creates a new prefix of the first `len` bits of `net`, setting any
+    /// bits of `net` after that to zero.
+    ///
+    /// Returns an error if `len` is longer than the address family.
+    pub fn new_relaxed(net: AF, len: u8) -> Result<Self, BitPrefixError> {
+        if len > AF::BITS {
+            return Err(BitPrefixError::LenTooLong);
+        }
+        Ok(Self {
+            net: net.truncate_to_len(len),
+            len,
+        })
+    }
+
+    pub fn net(&self) -> AF {
+        self.net
+    }
+
+    pub fn len(&self) -> u8 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether `other` is equal to, or a more-specific of, this
+    /// prefix.
+    pub fn covers(&self, other: &Self) -> bool {
+        other.len >= self.len
+            && other.net.truncate_to_len(self.len) == self.net
+    }
+
+    pub(crate) fn from_record<M: Meta>(
+        record: &InternalPrefixRecord<AF, M>,
+    ) -> Self {
+        Self {
+            net: record.net,
+            len: record.len,
+        }
+    }
+}
+
+impl<AF: AddressFamily> fmt::Display for BitPrefix<AF> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", AF::fmt_net(self.net), self.len)
+    }
+}
+
+impl<AF: IpAddressFamily> From<BitPrefix<AF>> for routecore::addr::Prefix {
+    fn from(prefix: BitPrefix<AF>) -> Self {
+        prefix.net.into_prefix(prefix.len)
+    }
+}
+
+//------------ BitPrefixError -----------------------------------------------
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BitPrefixError {
+    /// The length is longer than the address family.
+    LenTooLong,
+    /// There are bits set in the address after the length of the prefix.
+    HostBitsSet,
+}
+
+impl std::error::Error for BitPrefixError {}
+
+impl fmt::Display for BitPrefixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitPrefixError::LenTooLong => {
+                write!(f, "Error: prefix length too long.")
+            }
+            BitPrefixError::HostBitsSet => {
+                write!(f, "Error: bits set after the prefix length.")
+            }
+        }
+    }
+}
+
+//------------ BitPrefixRecord ----------------------------------------------
+
+/// A [`BitPrefix`] with a reference to its meta-data in a store.
+#[derive(Clone, Debug)]
+pub struct BitPrefixRecord<'a, AF: AddressFamily, M: Meta> {
+    pub prefix: BitPrefix<AF>,
+    pub meta: &'a M,
+}
+
+impl<'a, AF: AddressFamily, M: Meta> From<&'a InternalPrefixRecord<AF, M>>
+    for BitPrefixRecord<'a, AF, M>
+{
+    fn from(record: &'a InternalPrefixRecord<AF, M>) -> Self {
+        Self {
+            prefix: BitPrefix::from_record(record),
+            meta: &record.meta,
+        }
+    }
+}
+
+impl<'a, AF: AddressFamily, M: Meta> fmt::Display
+    for BitPrefixRecord<'a, AF, M>
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.prefix, self.meta)
+    }
+}
+
+//------------ BitQueryResult -----------------------------------------------
+
+/// The result of a query on a [`crate::BitPrefixStore`], the counterpart of
+/// [`crate::QueryResult`] for keys of any [`AddressFamily`].
+#[derive(Clone, Debug)]
+pub struct BitQueryResult<'a, AF: AddressFamily, M: Meta> {
+    pub match_type: MatchType,
+    pub prefix: Option<BitPrefix<AF>>,
+    pub prefix_meta: Option<&'a M>,
+    pub less_specifics: Option<Vec<BitPrefixRecord<'a, AF, M>>>,
+    pub more_specifics: Option<Vec<BitPrefixRecord<'a, AF, M>>>,
+}
@@ -1,10 +1,12 @@
 use std::fmt;
 
+/// The errors the Rotonda Stores return, boxed, from their methods.
 #[derive(Debug)]
 pub enum PrefixStoreError {
     NodeCreationMaxRetryError,
     NodeNotFound,
-    PrefixAlreadyExist,
+    PrefixNotFound,
+    RemoveNotSupported,
 }
 
 impl std::error::Error for PrefixStoreError {}
@@ -19,8 +21,11 @@ impl fmt::Display for PrefixStoreError {
             PrefixStoreError::NodeNotFound => {
                 write!(f, "Error: Node not found.")
             },
-            PrefixStoreError::PrefixAlreadyExist => {
-                write!(f, "Error: Prefix already exists.")
+            PrefixStoreError::PrefixNotFound => {
+                write!(f, "Error: Prefix not found.")
+            },
+            PrefixStoreError::RemoveNotSupported => {
+                write!(f, "Error: This store does not support removal.")
             }
         }
     }
@@ -0,0 +1,394 @@
+//! A [`Generator`] makes routing tables that look like real ones, so tests
+//! and benchmarks don't need data files.
+//!
+//! The lengths of the prefixes follow a distribution, by default one like
+//! that of the DFZ. Part of the prefixes are more-specifics of other
+//! prefixes in the table, nested up to a maximum depth, and part of those
+//! have the same origin AS as the prefix that covers them. The origin ASes
+//! are skewed, a few of them originate many prefixes. The same seed always
+//! gives the same table.
+//!
+//! ```ignore
+//! let records = Generator::new(1)
+//!     .v4_prefixes(10_000)
+//!     .v6_prefixes(2_000)
+//!     .generate();
+//! ```
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use routecore::addr::Prefix;
+use routecore::bgp::PrefixRecord;
+use routecore::record::Meta;
+
+use crate::PrefixAs;
+
+//------------ Distributions ------------------------------------------------
+
+/// Roughly the share of every IPv4 prefix length in the DFZ, as pairs of
+/// length and weight.
+pub const DFZ_V4_LENGTHS: &[(u8, u32)] = &[
+    (8, 2),
+    (12, 3),
+    (13, 6),
+    (14, 12),
+    (15, 22),
+    (16, 140),
+    (17, 90),
+    (18, 150),
+    (19, 270),
+    (20, 440),
+    (21, 560),
+    (22, 1150),
+    (23, 1050),
+    (24, 6105),
+];
+
+/// Roughly the share of every IPv6 prefix length in the DFZ, as pairs of
+/// length and weight.
+pub const DFZ_V6_LENGTHS: &[(u8, u32)] = &[
+    (29, 300),
+    (32, 1700),
+    (36, 400),
+    (40, 650),
+    (44, 1200),
+    (46, 250),
+    (47, 200),
+    (48, 5300),
+];
+
+//------------ Generator ----------------------------------------------------
+
+/// Generates a table of unique prefixes with their origin AS.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    seed: u64,
+    v4_prefixes: usize,
+    v6_prefixes: usize,
+    v4_lengths: Vec<(u8, u32)>,
+    v6_lengths: Vec<(u8, u32)>,
+    nesting: u8,
+    max_depth: u8,
+    origins: u32,
+    same_origin: u8,
+}
+
+impl Generator {
+    /// Creates a generator for 10,000 IPv4 and 2,000 IPv6 prefixes, with
+    /// the DFZ distributions of prefix lengths. 40% of the prefixes are
+    /// more-specifics, at most three deep, and half of those have the
+    /// origin AS of the prefix covering them. There are 10,000 origin
+    /// ASes, numbered from 1.
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            seed,
+            v4_prefixes: 10_000,
+            v6_prefixes: 2_000,
+            v4_lengths: DFZ_V4_LENGTHS.to_vec(),
+            v6_lengths: DFZ_V6_LENGTHS.to_vec(),
+            nesting: 40,
+            max_depth: 3,
+            origins: 10_000,
+            same_origin: 50,
+        }
+    }
+
+    /// Sets the number of IPv4 prefixes.
+    pub fn v4_prefixes(mut self, count: usize) -> Self {
+        self.v4_prefixes = count;
+        self
+    }
+
+    /// Sets the number of IPv6 prefixes.
+    pub fn v6_prefixes(mut self, count: usize) -> Self {
+        self.v6_prefixes = count;
+        self
+    }
+
+    /// Sets the distribution of the IPv4 prefix lengths, as pairs of
+    /// length and weight.
+    ///
+    /// # Panics
+    ///
+    /// If a length is over 32, or if all the weights are zero.
+    pub fn v4_lengths(mut self, lengths: &[(u8, u32)]) -> Self {
+        check_lengths(lengths, 32);
+        self.v4_lengths = lengths.to_vec();
+        self
+    }
+
+    /// Sets the distribution of the IPv6 prefix lengths, as pairs of
+    /// length and weight.
+    ///
+    /// # Panics
+    ///
+    /// If a length is over 128, or if all the weights are zero.
+    pub fn v6_lengths(mut self, lengths: &[(u8, u32)]) -> Self {
+        check_lengths(lengths, 128);
+        self.v6_lengths = lengths.to_vec();
+        self
+    }
+
+    /// Sets the percentage of prefixes that are more-specifics of another
+    /// prefix in the table.
+    ///
+    /// More-specifics only get the lengths from the distribution that are
+    /// longer than the prefix covering them, so with few of those the
+    /// percentage can end up lower.
+    pub fn nesting(mut self, percent: u8) -> Self {
+        self.nesting = percent.min(100);
+        self
+    }
+
+    /// Sets how deep more-specifics can be nested, zero for none at all.
+    pub fn max_depth(mut self, depth: u8) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the number of origin ASes, which are numbered from 1.
+    pub fn origins(mut self, count: u32) -> Self {
+        self.origins = count.max(1);
+        self
+    }
+
+    /// Sets the percentage of more-specifics with the origin AS of the
+    /// prefix covering them.
+    pub fn same_origin(mut self, percent: u8) -> Self {
+        self.same_origin = percent.min(100);
+        self
+    }
+
+    /// Generates the table, with the origin AS of every prefix as its
+    /// meta-data.
+    pub fn generate(&self) -> Vec<PrefixRecord<'static, PrefixAs>> {
+        self.generate_with(|_, origin| PrefixAs(origin))
+    }
+
+    /// Generates the table, making the meta-data of every prefix from the
+    /// prefix and its origin AS.
+    ///
+    /// The records are in random order, the IPv4 and IPv6 ones mixed.
+    /// There are fewer of them than asked for if the distributions can't
+    /// make enough unique prefixes.
+    pub fn generate_with<M: Meta>(
+        &self,
+        mut meta: impl FnMut(&Prefix, u32) -> M,
+    ) -> Vec<PrefixRecord<'static, M>> {
+        let mut rng = Rng(self.seed);
+        let mut entries =
+            self.family(&mut rng, &V4, self.v4_prefixes, &self.v4_lengths);
+        entries.extend(self.family(
+            &mut rng,
+            &V6,
+            self.v6_prefixes,
+            &self.v6_lengths,
+        ));
+
+        // Fisher-Yates, so the inserts don't go in order of nesting.
+        for i in (1..entries.len()).rev() {
+            entries.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+
+        entries
+            .into_iter()
+            .map(|(prefix, origin)| {
+                let meta = meta(&prefix, origin);
+                PrefixRecord::new_with_local_meta(prefix, meta)
+            })
+            .collect()
+    }
+
+    fn family(
+        &self,
+        rng: &mut Rng,
+        family: &Family,
+        count: usize,
+        lengths: &[(u8, u32)],
+    ) -> Vec<(Prefix, u32)> {
+        let mut table: Vec<Entry> = Vec::with_capacity(count);
+        let mut seen = HashSet::with_capacity(count);
+
+        // Duplicates and more-specifics that don't fit are tried again,
+        // but not forever, for the distributions that can't make enough
+        // unique prefixes.
+        let mut attempts = count.saturating_mul(10);
+        while table.len() < count && attempts > 0 {
+            attempts -= 1;
+            let nested = !table.is_empty()
+                && self.max_depth > 0
+                && rng.percent(self.nesting);
+            let entry = if nested {
+                let parent = table[rng.below(table.len() as u64) as usize];
+                if parent.depth >= self.max_depth {
+                    continue;
+                }
+                let len = match rng.length(lengths, Some(parent.len)) {
+                    Some(len) => len,
+                    None => continue,
+                };
+                let origin = if rng.percent(self.same_origin) {
+                    parent.origin
+                } else {
+                    self.origin(rng)
+                };
+                let host = family.mask(len) & !family.mask(parent.len);
+                Entry {
+                    addr: parent.addr | (rng.addr(family) & host),
+                    len,
+                    depth: parent.depth + 1,
+                    origin,
+                }
+            } else {
+                let len = rng
+                    .length(lengths, None)
+                    .expect("the weights can't all be zero");
+                Entry {
+                    addr: rng.addr(family) & family.mask(len),
+                    len,
+                    depth: 0,
+                    origin: self.origin(rng),
+                }
+            };
+            if seen.insert((entry.addr, entry.len)) {
+                table.push(entry);
+            }
+        }
+
+        table
+            .into_iter()
+            .map(|entry| (family.prefix(entry.addr, entry.len), entry.origin))
+            .collect()
+    }
+
+    // A few ASes originate most of the prefixes.
+    fn origin(&self, rng: &mut Rng) -> u32 {
+        let share = rng.below(1 << 20) as f64 / (1 << 20) as f64;
+        1 + (f64::from(self.origins) * share.powi(3)) as u32
+    }
+}
+
+fn check_lengths(lengths: &[(u8, u32)], max_len: u8) {
+    assert!(
+        lengths.iter().all(|(len, _)| *len <= max_len),
+        "prefix lengths can't be over {}",
+        max_len
+    );
+    assert!(
+        lengths.iter().any(|(_, weight)| *weight > 0),
+        "the weights of the prefix lengths can't all be zero"
+    );
+}
+
+//------------ Entry --------------------------------------------------------
+
+// A prefix in the making, its address in the low bits.
+#[derive(Clone, Copy)]
+struct Entry {
+    addr: u128,
+    len: u8,
+    depth: u8,
+    origin: u32,
+}
+
+//------------ Family -------------------------------------------------------
+
+// The unicast part of the address space of an address family.
+struct Family {
+    bits: u8,
+    // The prefix all the addresses are in.
+    space: u128,
+    space_len: u8,
+}
+
+// All of the IPv4 address space.
+const V4: Family = Family {
+    bits: 32,
+    space: 0,
+    space_len: 0,
+};
+
+// 2000::/3.
+const V6: Family = Family {
+    bits: 128,
+    space: 0x2000 << 112,
+    space_len: 3,
+};
+
+impl Family {
+    // The network mask for a length, in the low bits.
+    fn mask(&self, len: u8) -> u128 {
+        let all = u128::MAX >> (128 - u32::from(self.bits));
+        match self.bits - len {
+            0 => all,
+            host => all & !(u128::MAX >> (128 - u32::from(host))),
+        }
+    }
+
+    fn prefix(&self, addr: u128, len: u8) -> Prefix {
+        let addr = if self.bits == 32 {
+            IpAddr::V4(Ipv4Addr::from(addr as u32))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(addr))
+        };
+        Prefix::new(addr, len).expect("host bits are cleared")
+    }
+}
+
+//------------ Rng ----------------------------------------------------------
+
+// SplitMix64, which is good enough here and needs nothing from outside.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn percent(&mut self, percent: u8) -> bool {
+        self.below(100) < u64::from(percent)
+    }
+
+    // A random address in the unicast space of the family.
+    fn addr(&mut self, family: &Family) -> u128 {
+        let addr = (u128::from(self.next()) << 64) | u128::from(self.next());
+        let addr = addr >> (128 - u32::from(family.bits));
+        let space = family.mask(family.space_len);
+        (addr & !space) | family.space
+    }
+
+    // Picks a length by its weight, only from the lengths longer than
+    // `above` if given. Returns `None` if there are no such lengths.
+    fn length(
+        &mut self,
+        lengths: &[(u8, u32)],
+        above: Option<u8>,
+    ) -> Option<u8> {
+        let candidates = || {
+            lengths.iter().filter(move |(len, weight)| {
+                *weight > 0 && above.map_or(true, |above| *len > above)
+            })
+        };
+        let total: u64 =
+            candidates().map(|(_, weight)| u64::from(*weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = self.below(total);
+        for (len, weight) in candidates() {
+            if pick < u64::from(*weight) {
+                return Some(*len);
+            }
+            pick -= u64::from(*weight);
+        }
+        None
+    }
+}
@@ -0,0 +1,20 @@
+//! Helpers for the stores and formats that read from files.
+use std::io::{self, Read};
+
+/// Like `read_exact`, but returns how many bytes could be read instead of
+/// failing at the end of the file.
+pub(crate) fn read_full(
+    source: &mut impl Read,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match source.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
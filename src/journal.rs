@@ -0,0 +1,450 @@
+//! A write-ahead journal for the [`MultiThreadedStore`].
+//!
+//! A [`JournaledStore`] keeps its state in a directory with two files:
+//!
+//! ```text
+//! snapshot    "RTSTSNP1" | generation (u64) | entry*
+//! journal     "RTSTWAL1" | generation (u64) | entry*
+//! ```
+//!
+//! The snapshot holds all the prefixes of the store at the time it was
+//! taken, the journal all the mutations since then. Every entry in both
+//! files is framed as
+//!
+//! ```text
+//! payload length (u32) | CRC-32 of the payload (u32) | payload
+//! ```
+//!
+//! with a payload of the operation (u8, 1 for an insert), the address
+//! family (u8, 4 or 6), the prefix length (u8), the address (4 or 16
+//! bytes), and the meta-data encoded with [`MetaCodec::encode`]. All
+//! integers are big-endian.
+//!
+//! Taking a snapshot bumps the generation. A journal with an older
+//! generation than the snapshot was left behind by a crash right after
+//! the snapshot was written, and all of its entries are in the snapshot
+//! already, so it is discarded.
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{
+    self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write,
+};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crossbeam_epoch as epoch;
+use routecore::addr::Prefix;
+use routecore::record::MergeUpdate;
+
+use crate::io_util::read_full;
+use crate::meta_codec::MetaCodec;
+use crate::MultiThreadedStore;
+
+const SNAPSHOT: &str = "snapshot";
+const SNAPSHOT_TMP: &str = "snapshot.tmp";
+const JOURNAL: &str = "journal";
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"RTSTSNP1";
+const JOURNAL_MAGIC: &[u8; 8] = b"RTSTWAL1";
+const HEADER_LEN: u64 = 16;
+
+const OP_INSERT: u8 = 1;
+
+//------------ Durability ---------------------------------------------------
+
+/// When an entry in the journal counts as written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Durability {
+    /// Entries are handed to the operating system before a mutation
+    /// returns. They survive a crash of the process, but not of the
+    /// machine.
+    Written,
+    /// Entries are synced to disk before a mutation returns. This
+    /// survives a crash of the machine, but costs an `fsync` per entry.
+    Synced,
+}
+
+//------------ JournaledStore -----------------------------------------------
+
+/// A [`MultiThreadedStore`] that logs every mutation to a journal on disk,
+/// so that it can be recovered after a crash.
+///
+/// Opening a journaled store loads the last snapshot and replays the
+/// journal on top of it. A torn entry at the end of the journal, left by
+/// a crash in the middle of a write, is cut off. Call
+/// [`JournaledStore::snapshot`] every now and then to keep the journal,
+/// and with it the time needed to recover, short.
+///
+/// Mutations go through the journaled store, which serializes them on
+/// the journal. Queries go straight to the store returned by
+/// [`JournaledStore::store`].
+pub struct JournaledStore<M: MetaCodec + MergeUpdate> {
+    store: MultiThreadedStore<M>,
+    journal: Mutex<Journal>,
+    dir: PathBuf,
+    durability: Durability,
+}
+
+impl<M: MetaCodec + MergeUpdate> JournaledStore<M> {
+    /// Opens the journaled store in `dir`, creating the directory if it
+    /// doesn't exist yet.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        durability: Durability,
+    ) -> Result<Self, JournalError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let store = MultiThreadedStore::new().map_err(JournalError::Store)?;
+
+        let generation = match File::open(dir.join(SNAPSHOT)) {
+            Ok(file) => load_snapshot(&store, file)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err.into()),
+        };
+        let journal = Journal::replay(
+            &store,
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open(dir.join(JOURNAL))?,
+            generation,
+        )?;
+
+        Ok(JournaledStore {
+            store,
+            journal: Mutex::new(journal),
+            dir,
+            durability,
+        })
+    }
+
+    /// The store, for queries.
+    pub fn store(&self) -> &MultiThreadedStore<M> {
+        &self.store
+    }
+
+    /// Appends the insert to the journal, and then inserts the prefix
+    /// into the store, merging the meta-data if the prefix already
+    /// exists.
+    pub fn insert(
+        &self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<(), JournalError> {
+        let mut entry = vec![];
+        write_entry(&mut entry, OP_INSERT, prefix, &meta);
+
+        // The lock is held until the store has the mutation, so that the
+        // journal has the mutations in the order they were applied.
+        let mut journal = self.lock();
+        let start = journal.len;
+        journal.append(&entry, self.durability)?;
+        if let Err(err) = self.store.insert(prefix, meta) {
+            journal.truncate(start)?;
+            return Err(JournalError::Store(err));
+        }
+        Ok(())
+    }
+
+    /// Writes all the prefixes in the store to a new snapshot, and empties
+    /// the journal.
+    ///
+    /// Mutations wait for the snapshot to finish.
+    pub fn snapshot(&self) -> Result<(), JournalError> {
+        let mut journal = self.lock();
+        let generation = journal.generation + 1;
+
+        let tmp = self.dir.join(SNAPSHOT_TMP);
+        let mut target = BufWriter::new(File::create(&tmp)?);
+        target.write_all(SNAPSHOT_MAGIC)?;
+        target.write_all(&generation.to_be_bytes())?;
+        let guard = &epoch::pin();
+        let mut entry = vec![];
+        for record in self.store.prefixes_iter(guard) {
+            entry.clear();
+            write_entry(&mut entry, OP_INSERT, &record.prefix, &*record.meta);
+            target.write_all(&entry)?;
+        }
+        target.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+
+        fs::rename(&tmp, self.dir.join(SNAPSHOT))?;
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        journal.reset(generation)
+    }
+
+    fn lock(&self) -> MutexGuard<Journal> {
+        // A panic while holding the lock leaves the journal in a state
+        // that replay can deal with, so the poison is ignored.
+        self.journal.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+//------------ Journal ------------------------------------------------------
+
+struct Journal {
+    file: File,
+    generation: u64,
+    // The length of the valid part of the file.
+    len: u64,
+}
+
+impl Journal {
+    // Replays the journal in `file` into the store, and cuts off anything
+    // after the last valid entry.
+    fn replay<M: MetaCodec + MergeUpdate>(
+        store: &MultiThreadedStore<M>,
+        file: File,
+        generation: u64,
+    ) -> Result<Self, JournalError> {
+        let mut source = BufReader::new(&file);
+        let mut header = [0; HEADER_LEN as usize];
+        let read = read_full(&mut source, &mut header)?;
+
+        // An empty or half-written header is left by a crash while the
+        // journal was created or reset.
+        let mut len = 0;
+        if read == header.len() {
+            if &header[..8] != JOURNAL_MAGIC {
+                return Err(JournalError::Corrupt("not a journal file"));
+            }
+            let journal_gen =
+                u64::from_be_bytes(header[8..].try_into().unwrap());
+            if journal_gen > generation {
+                return Err(JournalError::Corrupt(
+                    "journal is newer than the snapshot",
+                ));
+            }
+            if journal_gen == generation {
+                len = HEADER_LEN;
+                loop {
+                    let payload = match read_frame(&mut source) {
+                        Ok(Some(payload)) => payload,
+                        Ok(None) => break,
+                        // A crash can only tear the last entry that was
+                        // written. A damaged entry with more behind it
+                        // means the journal itself is broken, and cutting
+                        // it off would lose entries that were synced.
+                        Err(JournalError::Corrupt(msg)) => {
+                            if source.fill_buf()?.is_empty() {
+                                break;
+                            }
+                            return Err(JournalError::Corrupt(msg));
+                        }
+                        Err(err) => return Err(err),
+                    };
+                    let (prefix, meta) = decode_payload::<M>(&payload)?;
+                    store.insert(&prefix, meta).map_err(JournalError::Store)?;
+                    len += 8 + payload.len() as u64;
+                }
+            }
+        }
+        drop(source);
+
+        let mut journal = Journal {
+            file,
+            generation,
+            len,
+        };
+        if len == 0 {
+            journal.reset(generation)?;
+        } else {
+            journal.truncate(len)?;
+            journal.file.sync_data()?;
+        }
+        Ok(journal)
+    }
+
+    fn append(
+        &mut self,
+        entry: &[u8],
+        durability: Durability,
+    ) -> Result<(), JournalError> {
+        let res = self.file.write_all(entry).and_then(|_| match durability {
+            Durability::Synced => self.file.sync_data(),
+            Durability::Written => Ok(()),
+        });
+        if let Err(err) = res {
+            // Cut off whatever part of the entry made it to the file, so
+            // that later entries don't end up behind a torn one.
+            let _ = self.truncate(self.len);
+            return Err(err.into());
+        }
+        self.len += entry.len() as u64;
+        Ok(())
+    }
+
+    fn truncate(&mut self, len: u64) -> Result<(), JournalError> {
+        self.file.set_len(len)?;
+        self.file.seek(SeekFrom::Start(len))?;
+        self.len = len;
+        Ok(())
+    }
+
+    // Empties the journal and starts it over for `generation`.
+    fn reset(&mut self, generation: u64) -> Result<(), JournalError> {
+        self.truncate(0)?;
+        self.file.write_all(JOURNAL_MAGIC)?;
+        self.file.write_all(&generation.to_be_bytes())?;
+        self.file.sync_data()?;
+        self.generation = generation;
+        self.len = HEADER_LEN;
+        Ok(())
+    }
+}
+
+//------------ Snapshots ----------------------------------------------------
+
+// Loads the snapshot in `file` into the store and returns its generation.
+// Unlike the journal, a snapshot is written in full before it is used, so
+// any damage to it is an error.
+fn load_snapshot<M: MetaCodec + MergeUpdate>(
+    store: &MultiThreadedStore<M>,
+    file: File,
+) -> Result<u64, JournalError> {
+    let mut source = BufReader::new(file);
+    let mut header = [0; HEADER_LEN as usize];
+    if read_full(&mut source, &mut header)? != header.len()
+        || &header[..8] != SNAPSHOT_MAGIC
+    {
+        return Err(JournalError::Corrupt("not a snapshot file"));
+    }
+    while let Some(payload) = read_frame(&mut source)? {
+        let (prefix, meta) = decode_payload::<M>(&payload)?;
+        store.insert(&prefix, meta).map_err(JournalError::Store)?;
+    }
+    Ok(u64::from_be_bytes(header[8..].try_into().unwrap()))
+}
+
+//------------ Entries ------------------------------------------------------
+
+fn write_entry<M: MetaCodec>(
+    target: &mut Vec<u8>,
+    op: u8,
+    prefix: &Prefix,
+    meta: &M,
+) {
+    let mut payload = vec![op];
+    match prefix.addr() {
+        IpAddr::V4(addr) => {
+            payload.extend_from_slice(&[4, prefix.len()]);
+            payload.extend_from_slice(&addr.octets());
+        }
+        IpAddr::V6(addr) => {
+            payload.extend_from_slice(&[6, prefix.len()]);
+            payload.extend_from_slice(&addr.octets());
+        }
+    }
+    meta.encode(&mut payload);
+
+    target.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    target.extend_from_slice(&crc32(&payload).to_be_bytes());
+    target.extend_from_slice(&payload);
+}
+
+// Reads the payload of the next entry, or returns `None` at the end of
+// the file.
+fn read_frame(
+    source: &mut impl Read,
+) -> Result<Option<Vec<u8>>, JournalError> {
+    let mut header = [0; 8];
+    match read_full(source, &mut header)? {
+        0 => return Ok(None),
+        8 => {}
+        _ => return Err(JournalError::Corrupt("torn entry")),
+    }
+    let len = u32::from_be_bytes(header[..4].try_into().unwrap());
+    let crc = u32::from_be_bytes(header[4..].try_into().unwrap());
+
+    // Reading through `take` keeps a damaged length from making us
+    // allocate gigabytes up front.
+    let mut payload = vec![];
+    source.take(len as u64).read_to_end(&mut payload)?;
+    if payload.len() != len as usize {
+        return Err(JournalError::Corrupt("torn entry"));
+    }
+    if crc32(&payload) != crc {
+        return Err(JournalError::Corrupt("checksum mismatch"));
+    }
+    Ok(Some(payload))
+}
+
+// Decodes the payload of an entry that passed its checksum, so anything
+// wrong with it isn't caused by a torn write.
+fn decode_payload<M: MetaCodec>(
+    payload: &[u8],
+) -> Result<(Prefix, M), JournalError> {
+    let (addr, rest): (IpAddr, _) = match payload {
+        [OP_INSERT, 4, _, rest @ ..] if rest.len() >= 4 => {
+            let octets: [u8; 4] = rest[..4].try_into().unwrap();
+            (Ipv4Addr::from(octets).into(), &rest[4..])
+        }
+        [OP_INSERT, 6, _, rest @ ..] if rest.len() >= 16 => {
+            let octets: [u8; 16] = rest[..16].try_into().unwrap();
+            (Ipv6Addr::from(octets).into(), &rest[16..])
+        }
+        [OP_INSERT, ..] => {
+            return Err(JournalError::Corrupt("invalid entry"))
+        }
+        _ => return Err(JournalError::Corrupt("unknown operation")),
+    };
+    let prefix = Prefix::new(addr, payload[2])
+        .map_err(|_| JournalError::Corrupt("invalid prefix"))?;
+    let meta =
+        M::decode(rest).ok_or(JournalError::Corrupt("invalid meta-data"))?;
+    Ok((prefix, meta))
+}
+
+// The CRC-32 used by Ethernet and zlib. Entries are small, so a bitwise
+// implementation will do.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+//------------ JournalError -------------------------------------------------
+
+/// An error while reading or writing a [`JournaledStore`].
+#[derive(Debug)]
+pub enum JournalError {
+    /// Reading or writing one of the files failed.
+    Io(io::Error),
+    /// A snapshot or journal file is damaged beyond what a crash could
+    /// have done to it.
+    Corrupt(&'static str),
+    /// The store refused a mutation.
+    Store(Box<dyn std::error::Error>),
+}
+
+impl std::error::Error for JournalError {}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalError::Io(err) => write!(f, "Error: {}", err),
+            JournalError::Corrupt(reason) => {
+                write!(f, "Error: Corrupt journal: {}.", reason)
+            }
+            JournalError::Store(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for JournalError {
+    fn from(err: io::Error) -> Self {
+        JournalError::Io(err)
+    }
+}
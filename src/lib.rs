@@ -11,23 +11,44 @@
 
 //! Read more about the data-structure in this [blog post](https://blog.nlnetlabs.nl/donkeys-mules-horses/).
 mod af;
+mod bit_prefix;
+mod errors;
+#[cfg(any(feature = "journal", feature = "mrt"))]
+mod io_util;
+#[cfg(feature = "journal")]
+mod journal;
 mod local_array;
 mod local_vec;
+#[cfg(feature = "mmap")]
+mod mapped_store;
+mod meta_codec;
 mod node_id;
 mod prefix_record;
 mod stride;
 mod synth_int;
+mod sync;
 
 #[macro_use]
 mod macros;
 
 mod rotonda_store;
+#[cfg(feature = "serde")]
+mod ser;
 
 // Public Interfaces
 
 pub mod prelude;
 /// Statistics for the two trees (IPv4 and IPv6).
 pub mod stats;
+/// Loading prefixes from CSV and JSON Lines files.
+#[cfg(feature = "csv")]
+pub mod loader;
+/// Reading and writing MRT TABLE_DUMP_V2 files.
+#[cfg(feature = "mrt")]
+pub mod mrt;
+/// Generating routing tables for tests and benchmarks.
+#[cfg(feature = "generator")]
+pub mod generator;
 pub use crate::rotonda_store::*;
 
 // re-exports
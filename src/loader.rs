@@ -0,0 +1,403 @@
+//! A [`Loader`] reads rows with a prefix and its meta-data, either from
+//! CSV with a header line, or from JSON Lines with one flat object per
+//! line. The prefix is taken from one column, as `addr/len`, or from two
+//! columns with the address and the length, and can be IPv4 or IPv6. The
+//! meta-data is made from the row with [`FromRow`].
+//!
+//! Reading JSON Lines needs the `serde_json` feature as well.
+//!
+//! ```ignore
+//! // The files in data/ have a meaningless header line.
+//! let records = Loader::csv()
+//!     .columns(&["ip", "len", "asn"])
+//!     .prefix_column("ip")
+//!     .len_column("len")
+//!     .load_file::<PrefixAs>("data/test_1.csv")?;
+//! ```
+use std::fmt;
+use std::fs::File;
+#[cfg(feature = "serde_json")]
+use std::io::BufRead;
+use std::io::{self, BufReader, Read};
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use routecore::addr::Prefix;
+use routecore::bgp::PrefixRecord;
+use routecore::record::{MergeUpdate, Meta};
+
+use crate::{PrefixAs, PrefixStoreMut};
+
+//------------ FromRow ------------------------------------------------------
+
+/// Meta-data that can be made from the columns of a row.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, LoadError>;
+}
+
+/// Takes the AS number from the `asn` column.
+impl FromRow for PrefixAs {
+    fn from_row(row: &Row) -> Result<Self, LoadError> {
+        row.parse("asn").map(PrefixAs)
+    }
+}
+
+//------------ Row ----------------------------------------------------------
+
+/// A row from a CSV or JSON Lines file, with its values by column name.
+pub struct Row<'a> {
+    line: u64,
+    fields: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Row<'a> {
+    /// The line the row started on, counting from one.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// Returns the value in `column`, or `None` if the row doesn't have
+    /// the column.
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        self.fields
+            .iter()
+            .find(|(name, _)| *name == column)
+            .map(|(_, value)| *value)
+    }
+
+    /// Parses the value in `column`.
+    pub fn parse<T: FromStr>(&self, column: &str) -> Result<T, LoadError> {
+        let value = self.get(column).ok_or_else(|| {
+            LoadError::MissingColumn {
+                line: self.line,
+                column: column.to_string(),
+            }
+        })?;
+        value.trim().parse().map_err(|_| self.invalid(column, value))
+    }
+
+    fn invalid(&self, column: &str, value: &str) -> LoadError {
+        LoadError::InvalidValue {
+            line: self.line,
+            column: column.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+//------------ Loader -------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Csv,
+    #[cfg(feature = "serde_json")]
+    JsonLines,
+}
+
+/// Reads prefixes with their meta-data from CSV or JSON Lines.
+///
+/// By default the prefix is read from the `prefix` column, as
+/// `addr/len`.
+#[derive(Clone, Debug)]
+pub struct Loader {
+    format: Format,
+    has_headers: bool,
+    columns: Option<Vec<String>>,
+    prefix_column: String,
+    len_column: Option<String>,
+}
+
+impl Loader {
+    /// A loader for CSV, with the column names in the first line.
+    pub fn csv() -> Self {
+        Loader::new(Format::Csv)
+    }
+
+    /// A loader for JSON Lines, with a flat object on every line. The
+    /// values of the object can be strings, numbers or booleans, null
+    /// values count as missing.
+    #[cfg(feature = "serde_json")]
+    pub fn json_lines() -> Self {
+        Loader::new(Format::JsonLines)
+    }
+
+    fn new(format: Format) -> Self {
+        Loader {
+            format,
+            has_headers: true,
+            columns: None,
+            prefix_column: "prefix".to_string(),
+            len_column: None,
+        }
+    }
+
+    /// Sets whether the first line of a CSV file holds the column names.
+    /// Without one, the names have to be set with [`Loader::columns`].
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// Names the columns of a CSV file, in order. These replace the names
+    /// in the first line of the file, if there is one.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Sets the column with the prefix, or with just its address if
+    /// there is a [`Loader::len_column`].
+    pub fn prefix_column(mut self, column: &str) -> Self {
+        self.prefix_column = column.to_string();
+        self
+    }
+
+    /// Sets the column with the length of the prefix.
+    pub fn len_column(mut self, column: &str) -> Self {
+        self.len_column = Some(column.to_string());
+        self
+    }
+
+    /// Reads all the records in the file at `path`.
+    pub fn load_file<M>(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<PrefixRecord<'static, M>>, LoadError>
+    where
+        M: Meta + FromRow,
+    {
+        self.load(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads all the records from `source`.
+    pub fn load<M>(
+        &self,
+        source: impl Read,
+    ) -> Result<Vec<PrefixRecord<'static, M>>, LoadError>
+    where
+        M: Meta + FromRow,
+    {
+        let mut records = vec![];
+        self.for_each_row(source, |row| {
+            let prefix = self.prefix(row)?;
+            records.push(PrefixRecord::new_with_local_meta(
+                prefix,
+                M::from_row(row)?,
+            ));
+            Ok(())
+        })?;
+        Ok(records)
+    }
+
+    /// Reads all the records from `source` and inserts them into `store`,
+    /// as they are read. Returns the number of records.
+    pub fn load_into<M, S>(
+        &self,
+        source: impl Read,
+        store: &mut S,
+    ) -> Result<usize, LoadError>
+    where
+        M: Meta + MergeUpdate + FromRow,
+        S: PrefixStoreMut<M>,
+    {
+        let mut count = 0;
+        self.for_each_row(source, |row| {
+            let prefix = self.prefix(row)?;
+            store.insert(&prefix, M::from_row(row)?).map_err(|error| {
+                LoadError::Store {
+                    line: row.line(),
+                    error,
+                }
+            })?;
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    }
+
+    fn prefix(&self, row: &Row) -> Result<Prefix, LoadError> {
+        let (addr, len) = match &self.len_column {
+            Some(len_column) => (
+                row.parse::<IpAddr>(&self.prefix_column)?,
+                row.parse(len_column)?,
+            ),
+            None => {
+                let value = row.parse::<String>(&self.prefix_column)?;
+                let invalid = || row.invalid(&self.prefix_column, &value);
+                let (addr, len) = value.split_once('/').ok_or_else(invalid)?;
+                (
+                    addr.parse().map_err(|_| invalid())?,
+                    len.parse().map_err(|_| invalid())?,
+                )
+            }
+        };
+        Prefix::new(addr, len).map_err(|_| {
+            row.invalid(&self.prefix_column, &format!("{}/{}", addr, len))
+        })
+    }
+
+    fn for_each_row(
+        &self,
+        source: impl Read,
+        mut op: impl FnMut(&Row) -> Result<(), LoadError>,
+    ) -> Result<(), LoadError> {
+        match self.format {
+            Format::Csv => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .has_headers(self.has_headers)
+                    .from_reader(source);
+                let columns = match &self.columns {
+                    Some(columns) => columns.clone(),
+                    None if self.has_headers => reader
+                        .headers()
+                        .map_err(csv_error)?
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect(),
+                    None => return Err(LoadError::NoColumns),
+                };
+                let mut record = csv::StringRecord::new();
+                while reader.read_record(&mut record).map_err(csv_error)? {
+                    op(&Row {
+                        line: record.position().map_or(0, |p| p.line()),
+                        fields: columns
+                            .iter()
+                            .map(|c| c.as_str())
+                            .zip(record.iter())
+                            .collect(),
+                    })?;
+                }
+            }
+            #[cfg(feature = "serde_json")]
+            Format::JsonLines => {
+                for (i, line) in BufReader::new(source).lines().enumerate() {
+                    let line_no = i as u64 + 1;
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let fields = json_fields(&line).map_err(|reason| {
+                        LoadError::Syntax {
+                            line: line_no,
+                            reason,
+                        }
+                    })?;
+                    op(&Row {
+                        line: line_no,
+                        fields: fields
+                            .iter()
+                            .map(|(name, value)| {
+                                (name.as_str(), value.as_str())
+                            })
+                            .collect(),
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn csv_error(err: csv::Error) -> LoadError {
+    let line = err.position().map_or(0, |p| p.line());
+    let reason = err.to_string();
+    match err.into_kind() {
+        csv::ErrorKind::Io(err) => LoadError::Io(err),
+        _ => LoadError::Syntax { line, reason },
+    }
+}
+
+//------------ JSON ---------------------------------------------------------
+
+// The keys and values of a flat JSON object. Numbers and booleans are
+// turned into strings, and fields with a null value are left out.
+#[cfg(feature = "serde_json")]
+fn json_fields(line: &str) -> Result<Vec<(String, String)>, String> {
+    use serde_json::{Map, Value};
+
+    let object: Map<String, Value> =
+        serde_json::from_str(line).map_err(|err| err.to_string())?;
+    object
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::Null => return None,
+                Value::String(value) => value,
+                Value::Number(value) => value.to_string(),
+                Value::Bool(value) => value.to_string(),
+                Value::Array(_) | Value::Object(_) => {
+                    return Some(Err(
+                        "nested values are not supported".to_string()
+                    ))
+                }
+            };
+            Some(Ok((key, value)))
+        })
+        .collect()
+}
+
+//------------ LoadError ----------------------------------------------------
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// A line isn't valid CSV or JSON.
+    Syntax { line: u64, reason: String },
+    /// The CSV file has no header line, and no column names were set.
+    NoColumns,
+    /// A row doesn't have a column that is needed.
+    MissingColumn { line: u64, column: String },
+    /// The value in a column can't be parsed.
+    InvalidValue {
+        line: u64,
+        column: String,
+        value: String,
+    },
+    /// The store refused a record.
+    Store {
+        line: u64,
+        error: Box<dyn std::error::Error>,
+    },
+}
+
+impl std::error::Error for LoadError {}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "Error: {}", err),
+            LoadError::Syntax { line, reason } => {
+                write!(f, "Error: line {}: {}.", line, reason)
+            }
+            LoadError::NoColumns => {
+                write!(f, "Error: no column names.")
+            }
+            LoadError::MissingColumn { line, column } => {
+                write!(
+                    f,
+                    "Error: line {}: missing column '{}'.",
+                    line, column
+                )
+            }
+            LoadError::InvalidValue {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "Error: line {}: invalid value '{}' in column '{}'.",
+                line, value, column
+            ),
+            LoadError::Store { line, error } => {
+                write!(f, "Error: line {}: {}", line, error)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
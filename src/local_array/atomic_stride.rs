@@ -1,11 +1,10 @@
 use std::fmt::{Binary, Debug};
 use log::trace;
-use std::sync::atomic::{
-    AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering,
-};
+use std::sync::atomic::Ordering;
 
 use crate::af::Zero;
 use crate::synth_int::AtomicU128;
+use crate::sync::{AtomicU16, AtomicU32, AtomicU64, AtomicU8};
 use crate::{impl_primitive_atomic_stride, AddressFamily};
 
 pub type Stride3 = u16;
@@ -55,7 +54,7 @@ impl AtomicBitmap for AtomicStride2 {
         AtomicStride2(AtomicU8::new(0))
     }
     fn inner(self) -> Self::InnerType {
-        self.0.into_inner()
+        self.0.load(Ordering::Relaxed)
     }
     fn is_set(&self, bit: usize) -> bool {
         self.load() & (1 << bit) != 0
@@ -102,7 +101,7 @@ impl AtomicBitmap for AtomicStride3 {
         AtomicStride3(AtomicU16::new(0))
     }
     fn inner(self) -> Self::InnerType {
-        self.0.into_inner()
+        self.0.load(Ordering::Relaxed)
     }
     fn is_set(&self, bit: usize) -> bool {
         self.load() & (1 << bit) != 0
@@ -150,7 +149,7 @@ impl AtomicBitmap for AtomicStride4 {
         AtomicStride4(AtomicU32::new(0))
     }
     fn inner(self) -> Self::InnerType {
-        self.0.into_inner()
+        self.0.load(Ordering::Relaxed)
     }
     fn is_set(&self, bit: usize) -> bool {
         self.load() & (1 << bit) != 0
@@ -197,7 +196,7 @@ impl AtomicBitmap for AtomicStride5 {
         AtomicStride5(AtomicU64::new(0))
     }
     fn inner(self) -> Self::InnerType {
-        self.0.into_inner()
+        self.0.load(Ordering::Relaxed)
     }
     fn is_set(&self, bit: usize) -> bool {
         self.load() & (1 << bit) != 0
@@ -244,8 +243,8 @@ impl AtomicBitmap for AtomicStride6 {
         AtomicStride6(AtomicU128::new(0))
     }
     fn inner(self) -> Self::InnerType {
-        let hi = self.0 .0.into_inner().to_be_bytes();
-        let lo = self.0 .1.into_inner().to_be_bytes();
+        let hi = self.0 .0.load(Ordering::Relaxed).to_be_bytes();
+        let lo = self.0 .1.load(Ordering::Relaxed).to_be_bytes();
 
         u128::from_be_bytes([
             hi[0], hi[1], hi[2], hi[3], hi[4], hi[5], hi[6], hi[7], lo[0],
@@ -10,7 +10,9 @@ macro_rules! insert_match {
         $nibble_len: expr;
         $nibble: expr; // nibble is a variable-length bitarray (1,2,4,8,etc)
         $is_last_stride: expr;
-        $pfx: ident; // the whole search prefix
+        $pfx: ident; // the id of the whole search prefix
+        $update: ident; // the update for the prefix, see upsert_prefix_with
+        $report_old: ident; // what to report of replaced meta-data, idem
         $truncate_len: ident; // the start of the length of this stride
         $stride_len: ident; // the length of this stride
         $cur_i: expr; // the id of the current node in this stride
@@ -48,7 +50,7 @@ macro_rules! insert_match {
                                 $nibble_len,
                                 // All the bits of the search prefix, but with a length set to
                                 // the start of the current stride.
-                                StrideNodeId::dangerously_new_with_id_as_is($pfx.net, $truncate_len),
+                                StrideNodeId::dangerously_new_with_id_as_is($pfx.get_net(), $truncate_len),
                                 // the length of THIS stride
                                 $stride_len,
                                 // the length of the next stride
@@ -60,7 +62,7 @@ macro_rules! insert_match {
                                     // $self.stats[$stats_level].inc($level);
 
                                     // get a new identifier for the node we're going to create.
-                                    let new_id = $self.store.acquire_new_node_id(($pfx.net, $truncate_len + $nibble_len));
+                                    let new_id = $self.store.acquire_new_node_id(($pfx.get_net(), $truncate_len + $nibble_len));
 
                                     // store the new node in the global store
                                     // let i: StrideNodeId<Store::AF>;
@@ -97,12 +99,12 @@ macro_rules! insert_match {
                                     break Ok(i)
                                 },
                                 NewNodeOrIndex::NewPrefix => {
-                                    return $self.store.upsert_prefix($pfx, $guard)
+                                    return $self.store.upsert_prefix_with($pfx, &mut $update, &$report_old, $guard)
                                     // Log
                                     // $self.stats[$stats_level].inc_prefix_count($level);
                                 }
                                 NewNodeOrIndex::ExistingPrefix => {
-                                    return $self.store.upsert_prefix($pfx, $guard)
+                                    return $self.store.upsert_prefix_with($pfx, &mut $update, &$report_old, $guard)
                                 }
                             }   // end of eval_node_or_prefix_at
                         }
@@ -122,7 +124,7 @@ macro_rules! insert_match {
                     trace!("STOP LOOPING {}", $cur_i);
                     return Err(
                         Box::new(
-                            crate::local_array::store::errors::PrefixStoreError::NodeCreationMaxRetryError
+                            crate::errors::PrefixStoreError::NodeCreationMaxRetryError
                         )
                     );
                 } 
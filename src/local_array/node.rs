@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8};
+use crate::sync::{AtomicU16, AtomicU32, AtomicU64, AtomicU8};
 use std::{
     fmt::Debug,
     marker::PhantomData,
@@ -1,9 +1,8 @@
 use crossbeam_epoch::{self as epoch};
 use epoch::Guard;
 use log::{debug, trace, warn};
-use routecore::bgp::RecordSet;
 
-use crate::af::AddressFamily;
+use crate::af::{AddressFamily, IpAddressFamily};
 use crate::local_array::store::atomic_types::{NodeBuckets, PrefixBuckets};
 use crate::prefix_record::InternalPrefixRecord;
 use routecore::addr::Prefix;
@@ -17,15 +16,162 @@ use crate::{MatchOptions, MatchType};
 
 use super::node::{PrefixId, SizedStrideRef, StrideNodeId};
 use super::store::atomic_types::StoredPrefix;
+use crate::local_vec::query::QueryRecords;
 
 //------------ Prefix Matching ----------------------------------------------
 
+// The queries that return the records they find, for the stores to turn
+// into their own result type. These work for every address family.
 impl<'a, AF, M, NB, PB> TreeBitMap<AF, M, NB, PB>
 where
     AF: AddressFamily,
     M: Meta + MergeUpdate,
     NB: NodeBuckets<AF>,
     PB: PrefixBuckets<AF, M>,
+{
+    pub(crate) fn match_prefix_records_by_store_direct(
+        &'a self,
+        search_pfx: PrefixId<AF>,
+        options: &MatchOptions,
+        guard: &'a Guard,
+    ) -> QueryRecords<'a, AF, M> {
+        // `non_recursive_retrieve_prefix_with_guard` return an exact match
+        // only, so no longest matching prefix!
+        let mut stored_prefix = self
+            .store
+            .non_recursive_retrieve_prefix_with_guard(search_pfx, guard)
+            .0
+            .and_then(|pfx| pfx.get_record(guard));
+
+        // Check if we have an actual exact match, if not then fetch the
+        // first lesser-specific with the greatest length, that's the Longest
+        // matching prefix, but only if the user requested a longest match or
+        // empty match.
+        let mut include_more_specifics = false;
+        let mut include_less_specifics = false;
+        let match_type = match (&options.match_type, &stored_prefix) {
+            // we found an exact match, we don't need to do anything.
+            (_, Some(_pfx)) => {
+                include_more_specifics = options.include_more_specifics;
+                include_less_specifics = options.include_less_specifics;
+                MatchType::ExactMatch
+            }
+            // we didn't find an exact match, but the user requested it
+            // so we need to find the longest matching prefix.
+            (MatchType::LongestMatch | MatchType::EmptyMatch, None) => {
+                warn!("less specific iter");
+                stored_prefix = self
+                    .store
+                    .less_specific_prefix_iter(search_pfx, guard)
+                    .max_by(|p0, p1| p0.len.cmp(&p1.len));
+                include_more_specifics = options.include_more_specifics;
+                include_less_specifics = options.include_less_specifics;
+                trace!("LMP prefix {:?}", stored_prefix);
+                if stored_prefix.is_some() {
+                    MatchType::LongestMatch
+                } else {
+                    MatchType::EmptyMatch
+                }
+            }
+            // We got an empty match, but the user requested an exact match
+            (MatchType::ExactMatch, None) => MatchType::EmptyMatch,
+        };
+
+        QueryRecords {
+            prefix: stored_prefix,
+            less_specifics: if include_less_specifics {
+                Some(
+                    self.store
+                        .less_specific_prefix_iter(
+                            if let Some(pfx) = stored_prefix {
+                                pfx.get_prefix_id()
+                            } else {
+                                search_pfx
+                            },
+                            guard,
+                        )
+                        .collect(),
+                )
+            } else if options.include_less_specifics {
+                Some(vec![])
+            } else {
+                None
+            },
+            more_specifics: if include_more_specifics {
+                Some(
+                    self.store
+                        .more_specific_prefix_iter_from(
+                            if let Some(pfx) = stored_prefix {
+                                pfx.get_prefix_id()
+                            } else {
+                                search_pfx
+                            },
+                            guard,
+                        )
+                        .collect(),
+                )
+                // The user requested more specifics, but there aren't any,
+                // so we need to return an empty vec, not a None.
+            } else if options.include_more_specifics {
+                Some(vec![])
+            } else {
+                None
+            },
+            match_type,
+        }
+    }
+
+    pub(crate) fn more_specifics_records_from(
+        &'a self,
+        prefix_id: PrefixId<AF>,
+        guard: &'a Guard,
+    ) -> QueryRecords<'a, AF, M> {
+        QueryRecords {
+            prefix: self
+                .store
+                .non_recursive_retrieve_prefix_with_guard(prefix_id, guard)
+                .0
+                .and_then(|pfx| pfx.get_record(guard)),
+            match_type: MatchType::EmptyMatch,
+            less_specifics: None,
+            more_specifics: Some(
+                self.store
+                    .more_specific_prefix_iter_from(prefix_id, guard)
+                    .collect(),
+            ),
+        }
+    }
+
+    pub(crate) fn less_specifics_records_from(
+        &'a self,
+        prefix_id: PrefixId<AF>,
+        guard: &'a Guard,
+    ) -> QueryRecords<'a, AF, M> {
+        QueryRecords {
+            prefix: self
+                .store
+                .non_recursive_retrieve_prefix_with_guard(prefix_id, guard)
+                .0
+                .and_then(|pfx| pfx.get_record(guard)),
+            match_type: MatchType::EmptyMatch,
+            less_specifics: Some(
+                self.store
+                    .less_specific_prefix_iter(prefix_id, guard)
+                    .collect(),
+            ),
+            more_specifics: None,
+        }
+    }
+}
+
+// The queries return `Prefix`es, so they're only available for the IP
+// address families.
+impl<'a, AF, M, NB, PB> TreeBitMap<AF, M, NB, PB>
+where
+    AF: IpAddressFamily,
+    M: Meta + MergeUpdate,
+    NB: NodeBuckets<AF>,
+    PB: PrefixBuckets<AF, M>,
 {
     pub fn more_specifics_from(
         &'a self,
@@ -112,98 +258,8 @@ where
         options: &MatchOptions,
         guard: &'a Guard,
     ) -> QueryResult<'a, M> {
-        // `non_recursive_retrieve_prefix_with_guard` return an exact match
-        // only, so no longest matching prefix!
-        let mut stored_prefix = self
-            .store
-            .non_recursive_retrieve_prefix_with_guard(search_pfx, guard)
-            .0
-            .and_then(|pfx| pfx.get_record(guard));
-
-        // Check if we have an actual exact match, if not then fetch the
-        // first lesser-specific with the greatest length, that's the Longest
-        // matching prefix, but only if the user requested a longest match or
-        // empty match.
-        let mut include_more_specifics = false;
-        let mut include_less_specifics = false;
-        let match_type = match (&options.match_type, &stored_prefix) {
-            // we found an exact match, we don't need to do anything.
-            (_, Some(_pfx)) => {
-                include_more_specifics = options.include_more_specifics;
-                include_less_specifics = options.include_less_specifics;
-                MatchType::ExactMatch
-            }
-            // we didn't find an exact match, but the user requested it
-            // so we need to find the longest matching prefix.
-            (MatchType::LongestMatch | MatchType::EmptyMatch, None) => {
-                warn!("less specific iter");
-                stored_prefix = self
-                    .store
-                    .less_specific_prefix_iter(search_pfx, guard)
-                    .max_by(|p0, p1| p0.len.cmp(&p1.len));
-                include_more_specifics = options.include_more_specifics;
-                include_less_specifics = options.include_less_specifics;
-                trace!("LMP prefix {:?}", stored_prefix);
-                if stored_prefix.is_some() {
-                    MatchType::LongestMatch
-                } else {
-                    MatchType::EmptyMatch
-                }
-            }
-            // We got an empty match, but the user requested an exact match
-            (MatchType::ExactMatch, None) => MatchType::EmptyMatch,
-        };
-
-        QueryResult {
-            prefix: stored_prefix.map(|p| p.prefix_into_pub()),
-            prefix_meta: stored_prefix.map(|pfx| &pfx.meta),
-            less_specifics: if include_less_specifics {
-                Some(
-                    self.store
-                        .less_specific_prefix_iter(
-                            if let Some(pfx) = stored_prefix {
-                                pfx.get_prefix_id()
-                            } else {
-                                search_pfx
-                            },
-                            guard,
-                        )
-                        .collect(),
-                )
-            } else if options.include_less_specifics {
-                Some(RecordSet {
-                    v4: vec![],
-                    v6: vec![],
-                })
-            } else {
-                None
-            },
-            more_specifics: if include_more_specifics {
-                Some(
-                    self.store
-                        .more_specific_prefix_iter_from(
-                            if let Some(pfx) = stored_prefix {
-                                pfx.get_prefix_id()
-                            } else {
-                                search_pfx
-                            },
-                            guard,
-                        )
-                        // .map(|p| (p.prefix_into_pub(), p))
-                        .collect(),
-                )
-                // The user requested more specifics, but there aren't any, so we
-                // need to return an empty vec, not a None.
-            } else if options.include_more_specifics {
-                Some(RecordSet {
-                    v4: vec![],
-                    v6: vec![],
-                })
-            } else {
-                None
-            },
-            match_type,
-        }
+        self.match_prefix_records_by_store_direct(search_pfx, options, guard)
+            .into_query_result()
     }
 
     // In a LMP search we have to go over all the nibble lengths in the
@@ -325,6 +381,20 @@ where
             None
         };
 
+        // The default route doesn't live in the tree (see above), so the
+        // stride processing below will never find it. It is a less-specific
+        // of every prefix, though, so if it's there we start out with it as
+        // the longest matching prefix and as the first less-specific.
+        if self.store.load_default_route_prefix_serial() > 0 {
+            let default_route_id = PrefixId::new(AF::zero(), 0);
+            if matches!(options.match_type, MatchType::LongestMatch) {
+                match_prefix_idx = Some(default_route_id);
+            }
+            if let Some(ls_vec) = less_specifics_vec.as_mut() {
+                ls_vec.push(default_route_id);
+            }
+        }
+
         //---- Stride Processing --------------------------------------------
 
         // We're going to iterate over all the strides in the treebitmap (so
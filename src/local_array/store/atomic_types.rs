@@ -123,6 +123,21 @@ impl<AF: AddressFamily, M: routecore::record::Meta> StoredPrefix<AF, M> {
         }
     }
 
+    // Creates a stored prefix in an overflow set, see
+    // `PrefixSet::index_of`. Its own overflow set is only created once
+    // another prefix collides with it.
+    pub fn new_overflow(record: InternalPrefixRecord<AF, M>) -> Self {
+        StoredPrefix {
+            serial: 1,
+            prefix: record.get_prefix_id(),
+            super_agg_record: AtomicSuperAggRecord::<AF, M>::new(
+                record.get_prefix_id(),
+                record.meta,
+            ),
+            next_bucket: PrefixSet::empty(),
+        }
+    }
+
     pub(crate) fn get_record<'a>(
         &'a self,
         guard: &'a Guard,
@@ -350,13 +365,64 @@ pub struct PrefixSet<AF: AddressFamily, M: routecore::record::Meta>(
 
 impl<AF: AddressFamily, M: routecore::record::Meta> PrefixSet<AF, M> {
     pub fn init(size: usize) -> Self {
+        PrefixSet(Self::empty_slots(size).into())
+    }
+
+    pub(crate) fn empty_slots(
+        size: usize,
+    ) -> Owned<[MaybeUninit<AtomicStoredPrefix<AF, M>>]> {
         let mut l =
             Owned::<[MaybeUninit<AtomicStoredPrefix<AF, M>>]>::init(size);
         debug!("creating space for {} prefixes in prefix_set", &size);
         for i in 0..size {
             l[i] = MaybeUninit::new(AtomicStoredPrefix::empty());
         }
-        PrefixSet(l.into())
+        l
+    }
+
+    // The number of slots in this set, zero if it isn't there.
+    pub(crate) fn slots(&self, guard: &Guard) -> usize {
+        let slots = self.0.load(Ordering::SeqCst, guard);
+        match slots.is_null() {
+            true => 0,
+            false => unsafe { slots.deref() }.len(),
+        }
+    }
+
+    // Whether this is an overflow set. The levels of the sets for a
+    // prefix length hash the bits of the prefixes, but two prefixes can
+    // still end up in the same slot at the last level. The one that comes
+    // second goes into an overflow set with a single slot hanging off the
+    // first, and so on.
+    pub(crate) fn is_overflow(&self, level: u8, guard: &Guard) -> bool {
+        level > 0 && self.slots(guard) == 1
+    }
+
+    // The index of the slot for `id` in this set, at `level` of the sets
+    // for its length.
+    pub(crate) fn index_of<PB: PrefixBuckets<AF, M>>(
+        &self,
+        id: PrefixId<AF>,
+        level: u8,
+        guard: &Guard,
+    ) -> usize {
+        if self.is_overflow(level, guard) {
+            return 0;
+        }
+        let last_level = if level > 0 {
+            PB::get_bits_for_len(id.get_len(), level - 1)
+        } else {
+            0
+        };
+        let this_level = PB::get_bits_for_len(id.get_len(), level);
+        trace!(
+            "calculated index from {} bits {}..{}",
+            id.get_net(),
+            last_level,
+            this_level
+        );
+        // HASHING FUNCTION
+        id.get_net().get_index(last_level, this_level - last_level)
     }
 
     pub fn get_len_recursive(&self) -> usize {
@@ -0,0 +1,106 @@
+use crossbeam_epoch::Guard;
+use routecore::record::{MergeUpdate, Meta};
+
+use crate::af::AddressFamily;
+use crate::bit_prefix::{BitPrefix, BitQueryResult};
+use crate::local_array::tree::{PrefixId, TreeBitMap};
+use crate::prefix_record::InternalPrefixRecord;
+use crate::MatchOptions;
+
+use super::buckets::{GenericNodeBuckets, GenericPrefixBuckets};
+
+//------------ BitPrefixStore -----------------------------------------------
+
+/// A multi-threaded store for prefixes of keys of any [`AddressFamily`].
+///
+/// This is the counterpart of the single-threaded
+/// [`crate::BitPrefixStore`]: it holds a single tree of [`BitPrefix`]es,
+/// and answers the same queries with [`BitQueryResult`]s. Like the
+/// [`crate::MultiThreadedStore`], it takes inserts from many threads at
+/// once, and it can't remove prefixes.
+///
+/// The tree has strides of four bits, so the number of bits of the address
+/// family has to be a multiple of four, up to 192.
+pub struct BitPrefixStore<AF: AddressFamily, M: Meta + MergeUpdate> {
+    tree: TreeBitMap<
+        AF,
+        M,
+        GenericNodeBuckets<AF>,
+        GenericPrefixBuckets<AF, M>,
+    >,
+}
+
+impl<'a, AF: AddressFamily, M: Meta + MergeUpdate> BitPrefixStore<AF, M> {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(BitPrefixStore {
+            tree: TreeBitMap::new()?,
+        })
+    }
+
+    /// Inserts a prefix with its meta-data into the store. If the prefix
+    /// already exists, the meta-data is merged into the existing meta-data.
+    pub fn insert(
+        &self,
+        prefix: BitPrefix<AF>,
+        meta: M,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.tree.insert(InternalPrefixRecord::new_with_meta(
+            prefix.net(),
+            prefix.len(),
+            meta,
+        ))
+    }
+
+    pub fn match_prefix(
+        &'a self,
+        search_pfx: BitPrefix<AF>,
+        options: &MatchOptions,
+        guard: &'a Guard,
+    ) -> BitQueryResult<'a, AF, M> {
+        self.tree
+            .match_prefix_records_by_store_direct(
+                PrefixId::new(search_pfx.net(), search_pfx.len()),
+                options,
+                guard,
+            )
+            .into_bit_query_result()
+    }
+
+    pub fn more_specifics_from(
+        &'a self,
+        search_pfx: BitPrefix<AF>,
+        guard: &'a Guard,
+    ) -> BitQueryResult<'a, AF, M> {
+        self.tree
+            .more_specifics_records_from(
+                PrefixId::new(search_pfx.net(), search_pfx.len()),
+                guard,
+            )
+            .into_bit_query_result()
+    }
+
+    pub fn less_specifics_from(
+        &'a self,
+        search_pfx: BitPrefix<AF>,
+        guard: &'a Guard,
+    ) -> BitQueryResult<'a, AF, M> {
+        self.tree
+            .less_specifics_records_from(
+                PrefixId::new(search_pfx.net(), search_pfx.len()),
+                guard,
+            )
+            .into_bit_query_result()
+    }
+
+    pub fn prefixes_len(&self) -> usize {
+        self.tree.store.get_prefixes_len()
+    }
+
+    pub fn nodes_len(&self) -> usize {
+        self.tree.store.get_nodes_len()
+    }
+
+    pub fn strides(&self) -> &[u8] {
+        self.tree.store.get_stride_sizes()
+    }
+}
@@ -0,0 +1,110 @@
+use routecore::record::Meta;
+
+use crate::af::AddressFamily;
+use crate::local_array::tree::{
+    PrefixId, Stride3, Stride4, Stride5, StrideNodeId,
+};
+
+use super::atomic_types::{NodeBuckets, NodeSet, PrefixBuckets, PrefixSet};
+
+//------------ Buckets ------------------------------------------------------
+
+// The `create_store` macro only generates buckets for IPv4 and IPv6, so the
+// buckets for the trees of the other address families are written out
+// here, once for all of them. All nodes have a stride of four bits, which
+// divides the length of every family up to the 192 bits of VPN-IPv6.
+const STRIDE: u8 = 4;
+const STRIDES: [u8; 48] = [STRIDE; 48];
+
+// Nodes and prefixes are both hashed on eight more of their bits on every
+// level, until all the bits up to their length are used. So a VPN-IPv6
+// prefix goes down at most 24 levels, within the 26 levels the prefix
+// iterators keep track of, and two nodes or prefixes of the same length
+// never share a slot at the last level.
+fn bits_for_len(len: u8, level: u8) -> u8 {
+    let bits = (u16::from(level) + 1) * 8;
+    if level > 0 && bits - 8 >= u16::from(len) {
+        0
+    } else {
+        bits.min(u16::from(len)) as u8
+    }
+}
+
+pub(crate) struct GenericNodeBuckets<AF: AddressFamily>(
+    Vec<NodeSet<AF, Stride4>>,
+);
+
+impl<AF: AddressFamily> NodeBuckets<AF> for GenericNodeBuckets<AF> {
+    fn init() -> Self {
+        GenericNodeBuckets(
+            (0..Self::get_strides_len())
+                .map(|i| {
+                    NodeSet::init(1 << Self::len_to_store_bits(i * STRIDE, 0))
+                })
+                .collect(),
+        )
+    }
+
+    fn len_to_store_bits(len: u8, level: u8) -> u8 {
+        bits_for_len(len, level)
+    }
+
+    fn get_stride_sizes(&self) -> &[u8] {
+        &STRIDES[..Self::get_strides_len() as usize]
+    }
+
+    fn get_stride_for_id(&self, _id: StrideNodeId<AF>) -> u8 {
+        STRIDE
+    }
+
+    fn get_store3(&self, _id: StrideNodeId<AF>) -> &NodeSet<AF, Stride3> {
+        unreachable!()
+    }
+
+    fn get_store4(&self, id: StrideNodeId<AF>) -> &NodeSet<AF, Stride4> {
+        &self.0[(id.get_len() / STRIDE) as usize]
+    }
+
+    fn get_store5(&self, _id: StrideNodeId<AF>) -> &NodeSet<AF, Stride5> {
+        unreachable!()
+    }
+
+    fn get_strides_len() -> u8 {
+        AF::BITS / STRIDE
+    }
+
+    fn get_first_stride_size() -> u8 {
+        STRIDE
+    }
+}
+
+pub(crate) struct GenericPrefixBuckets<AF: AddressFamily, M: Meta>(
+    Vec<PrefixSet<AF, M>>,
+);
+
+impl<AF: AddressFamily, M: Meta> PrefixBuckets<AF, M>
+    for GenericPrefixBuckets<AF, M>
+{
+    fn init() -> Self {
+        GenericPrefixBuckets(
+            (0..=AF::BITS)
+                .map(|len| {
+                    PrefixSet::init(1 << Self::get_bits_for_len(len, 0))
+                })
+                .collect(),
+        )
+    }
+
+    // The multi-threaded trees don't remove prefixes.
+    fn remove(&mut self, _id: PrefixId<AF>) -> Option<M> {
+        None
+    }
+
+    fn get_root_prefix_set(&self, len: u8) -> &'_ PrefixSet<AF, M> {
+        &self.0[len as usize]
+    }
+
+    fn get_bits_for_len(len: u8, level: u8) -> u8 {
+        bits_for_len(len, level)
+    }
+}
@@ -91,10 +91,7 @@
 //                                              │ metadata (current)      │
 //                                              └─────────────────────────┘
 //
-use std::{
-    fmt::Debug,
-    sync::atomic::{AtomicUsize, Ordering},
-};
+use std::{fmt::Debug, sync::atomic::Ordering};
 
 use crossbeam_epoch::{self as epoch, Atomic};
 
@@ -104,12 +101,12 @@ use log::{debug, log_enabled, trace, warn};
 use epoch::{Guard, Owned, Shared};
 use std::marker::PhantomData;
 
+use crate::errors::PrefixStoreError;
+use crate::local_array::bit_span::BitSpan;
 use crate::local_array::tree::*;
-use crate::local_array::{
-    bit_span::BitSpan, store::errors::PrefixStoreError,
-};
 
 use crate::prefix_record::InternalPrefixRecord;
+use crate::sync::{fence, AtomicUsize};
 use crate::{
     impl_search_level, retrieve_node_mut_with_guard_closure,
     store_node_closure,
@@ -118,6 +115,40 @@ use crate::{
 use super::atomic_types::*;
 use crate::AddressFamily;
 
+// ----------- UpsertReport -------------------------------------------------
+
+/// What an upsert did to the meta-data of a prefix.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Upserted<M> {
+    /// The prefix wasn't in the store, and now is.
+    Created,
+    /// The prefix was in the store, with this meta-data before.
+    Updated(M),
+    /// The prefix was left as it was, or absent.
+    ///
+    /// Only an update that decides not to change anything, like one of
+    /// [`compare_and_set`](crate::MultiThreadedStore::compare_and_set)
+    /// that doesn't match, reports this. A merging insert always reports
+    /// [`Updated`](Self::Updated) for a prefix that exists, even if the
+    /// merge left its meta-data the same, because telling those apart
+    /// would need the meta-data to be `PartialEq`.
+    Unchanged,
+}
+
+/// The report of an upsert of a prefix into the store.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpsertReport<M> {
+    /// What the upsert did to the meta-data of the prefix.
+    pub upserted: Upserted<M>,
+    /// The number of times the compare-and-swap of the prefix record
+    /// failed, because another thread changed it first. High numbers
+    /// indicate contention on the prefix.
+    ///
+    /// This only counts the prefix record itself. Retries while creating
+    /// the nodes of the tree on the way to the prefix are not included.
+    pub cas_count: usize,
+}
+
 // ----------- CustomAllocStorage -------------------------------------------
 //
 // CustomAllocStorage is a storage backend that uses a custom allocator, that
@@ -362,8 +393,7 @@ impl<
         self.default_route_prefix_serial.load(Ordering::SeqCst)
     }
 
-    #[allow(dead_code)]
-    fn increment_default_route_prefix_serial(&self) -> usize {
+    pub(crate) fn increment_default_route_prefix_serial(&self) -> usize {
         self.default_route_prefix_serial
             .fetch_add(1, Ordering::SeqCst)
     }
@@ -383,38 +413,65 @@ impl<
     // 1. LOAD
     //    Load the current prefix and meta-data from the store if any.
     // 2. INSERT
-    //    If there is no current meta-data, create it.
+    //    If there is no current meta-data, create it with `update`.
     // 3. UPDATE
-    //    If there is a prefix, meta-data combo, then load it and merge
-    //    the existing meta-dat with our meta-data using the `MergeUpdate`
-    //    trait (a so-called 'Read-Copy-Update').
+    //    If there is a prefix, meta-data combo, then load it and make new
+    //    meta-data out of the existing meta-data with `update`. For an
+    //    insert, that merges in our meta-data using the `MergeUpdate` trait
+    //    (a so-called 'Read-Copy-Update').
     // 4. SUCCESS
     //    See if we can successfully store the updated meta-data in the store.
     // 5. DONE
-    //    If Step 4 succeeded we're done!
+    //    If Step 4 succeeded we're done! If `update` didn't come up with
+    //    meta-data in step 2 or 3, we're done as well, without changing
+    //    anything.
     // 6. FAILURE - REPEAT
-    //    If Step 4 failed we're going to do the whole thing again.
+    //    If Step 4 failed we're going to do the whole thing again, so
+    //    `update` may be called more than once.
+    //
+    // The meta-data that was replaced in step 4 goes into the report
+    // through `report_old`, so that only callers that want it pay for a
+    // clone.
 
-    pub(crate) fn upsert_prefix(
+    pub(crate) fn upsert_prefix_with<O>(
         &self,
-        record: InternalPrefixRecord<AF, Meta>,
+        id: PrefixId<AF>,
+        mut update: impl FnMut(
+            Option<&Meta>,
+        )
+            -> Result<Option<Meta>, Box<dyn std::error::Error>>,
+        report_old: impl Fn(&Meta) -> O,
         guard: &Guard,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
         let backoff = Backoff::new();
+        let mut cas_count = 0;
 
-        let (atomic_stored_prefix, level) = self
-            .non_recursive_retrieve_prefix_mut_with_guard(
-                PrefixId::new(record.net, record.len),
-                guard,
-            )?;
-        let inner_stored_prefix =
-            atomic_stored_prefix.0.load(Ordering::SeqCst, guard);
-
-        match inner_stored_prefix.is_null() {
-            true => {
+        loop {
+            let (atomic_stored_prefix, level, overflow) =
+                self.non_recursive_retrieve_prefix_mut_with_guard(id, guard)?;
+            let inner_stored_prefix =
+                atomic_stored_prefix.0.load(Ordering::SeqCst, guard);
+
+            if inner_stored_prefix.is_null() {
+                let meta = match update(None)? {
+                    Some(meta) => meta,
+                    None => {
+                        return Ok(UpsertReport {
+                            upserted: Upserted::Unchanged,
+                            cas_count,
+                        })
+                    }
+                };
                 debug!("create new super-aggregated prefix record");
-                let new_stored_prefix =
-                    StoredPrefix::new::<PB>(record, level);
+                let record = InternalPrefixRecord::new_with_meta(
+                    id.get_net(),
+                    id.get_len(),
+                    meta,
+                );
+                let new_stored_prefix = match overflow {
+                    true => StoredPrefix::new_overflow(record),
+                    false => StoredPrefix::new::<PB>(record, level),
+                };
 
                 match atomic_stored_prefix.0.compare_exchange(
                     Shared::null(),
@@ -425,134 +482,215 @@ impl<
                 ) {
                     Ok(spfx) => {
                         debug!("inserted new prefix record {:?}", &spfx);
-                        Ok(())
+                        return Ok(UpsertReport {
+                            upserted: Upserted::Created,
+                            cas_count,
+                        });
                     }
+                    // Another thread got to the empty slot first, either
+                    // with this prefix or with another one that hashes to
+                    // the same slot. Start over to find out which, so the
+                    // update is made to the one for this prefix instead of
+                    // getting lost.
                     Err(stored_prefix) => {
                         debug!(
                             "prefix can't be inserted as new {:?}",
                             stored_prefix.current
                         );
-                        Err(Box::new(PrefixStoreError::PrefixAlreadyExist))
+                        cas_count += 1;
+                        backoff.spin();
+                        continue;
                     }
                 }
             }
-            false => {
-                trace!(
-                    "existing super-aggregated prefix record for {}/{}",
-                    record.net,
-                    record.len
-                );
-                let super_agg_record =
-                    &unsafe { inner_stored_prefix.deref() }
-                        .super_agg_record
-                        .0;
-                let mut inner_agg_record =
-                    super_agg_record.load(Ordering::Acquire, guard);
-
-                loop {
-                    let prefix_record =
-                        unsafe { inner_agg_record.as_ref() }.unwrap();
-                    let new_record = Owned::new(InternalPrefixRecord::<
-                        AF,
-                        Meta,
-                    >::new_with_meta(
-                        record.net,
-                        record.len,
-                        prefix_record
-                            .meta
-                            .clone_merge_update(&record.meta)
-                            .unwrap(),
-                    ))
-                    .into_shared(guard);
-
-                    // CAS the nested Atomic InternalPrefixRecord.
-                    match super_agg_record.compare_exchange(
-                        inner_agg_record,
-                        new_record,
-                        Ordering::AcqRel,
-                        Ordering::Acquire,
-                        guard,
-                    ) {
-                        Ok(_rec) => {
-                            if !inner_agg_record.is_null() {
-                                unsafe {
-                                    guard.defer_unchecked(move || {
-                                        std::sync::atomic::fence(
-                                            Ordering::Acquire,
-                                        );
-
-                                        std::mem::drop(
-                                            inner_agg_record.into_owned(),
-                                        )
-                                    });
-                                }
-                            };
-                            return Ok(());
-                        }
-                        Err(next_agg) => {
-                            // Do it again
-                            // warn!("contention {:?}", next_agg.current);
-                            inner_agg_record = next_agg.current;
-                            backoff.spin();
-                            continue;
-                        }
+
+            // The slot was empty when we looked it up, but another thread
+            // may have put a different prefix in it since. Start over, to
+            // go on into the overflow set of that one.
+            let stored_prefix = unsafe { inner_stored_prefix.deref() };
+            if stored_prefix.prefix != id {
+                debug!("slot taken by {:?}", stored_prefix.prefix);
+                backoff.spin();
+                continue;
+            }
+
+            trace!("existing super-aggregated prefix record for {:?}", id);
+            return self.update_stored_prefix_with(
+                stored_prefix,
+                update,
+                report_old,
+                cas_count,
+                guard,
+            );
+        }
+    }
+
+    // Updates the meta-data of a prefix that is in the store already with
+    // `update`, going straight to its record in the prefix buckets without
+    // walking the tree. Fails with `PrefixNotFound` if the prefix isn't in
+    // the store, without calling `update`.
+    pub(crate) fn update_prefix_with<O>(
+        &self,
+        id: PrefixId<AF>,
+        update: impl FnMut(
+            Option<&Meta>,
+        )
+            -> Result<Option<Meta>, Box<dyn std::error::Error>>,
+        report_old: impl Fn(&Meta) -> O,
+        guard: &Guard,
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
+        // Looking the prefix up read-only doesn't create overflow sets for
+        // a prefix that isn't there.
+        match self.non_recursive_retrieve_prefix_with_guard(id, guard).0 {
+            Some(stored_prefix) => self.update_stored_prefix_with(
+                stored_prefix,
+                update,
+                report_old,
+                0,
+                guard,
+            ),
+            None => Err(Box::new(PrefixStoreError::PrefixNotFound)),
+        }
+    }
+
+    // The UPDATE, SUCCESS and FAILURE - REPEAT steps of the critical
+    // section above, for a prefix that exists.
+    fn update_stored_prefix_with<O>(
+        &self,
+        stored_prefix: &StoredPrefix<AF, Meta>,
+        mut update: impl FnMut(
+            Option<&Meta>,
+        )
+            -> Result<Option<Meta>, Box<dyn std::error::Error>>,
+        report_old: impl Fn(&Meta) -> O,
+        mut cas_count: usize,
+        guard: &Guard,
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
+        let backoff = Backoff::new();
+        let id = stored_prefix.prefix;
+        let super_agg_record = &stored_prefix.super_agg_record.0;
+        let mut inner_agg_record =
+            super_agg_record.load(Ordering::Acquire, guard);
+
+        loop {
+            let prefix_record = unsafe { inner_agg_record.as_ref() }.unwrap();
+            let meta = match update(Some(&prefix_record.meta))? {
+                Some(meta) => meta,
+                None => {
+                    return Ok(UpsertReport {
+                        upserted: Upserted::Unchanged,
+                        cas_count,
+                    })
+                }
+            };
+            let new_record =
+                Owned::new(InternalPrefixRecord::<AF, Meta>::new_with_meta(
+                    id.get_net(),
+                    id.get_len(),
+                    meta,
+                ))
+                .into_shared(guard);
+
+            // CAS the nested Atomic InternalPrefixRecord.
+            match super_agg_record.compare_exchange(
+                inner_agg_record,
+                new_record,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_rec) => {
+                    let old = report_old(&prefix_record.meta);
+                    unsafe {
+                        guard.defer_unchecked(move || {
+                            fence(Ordering::Acquire);
+
+                            std::mem::drop(inner_agg_record.into_owned())
+                        });
                     }
+                    return Ok(UpsertReport {
+                        upserted: Upserted::Updated(old),
+                        cas_count,
+                    });
+                }
+                Err(next_agg) => {
+                    // Do it again, with the record that beat us. Ours was
+                    // never seen by anyone, so it can go right away.
+                    // warn!("contention {:?}", next_agg.current);
+                    inner_agg_record = next_agg.current;
+                    std::mem::drop(unsafe { next_agg.new.into_owned() });
+                    cas_count += 1;
+                    backoff.spin();
+                    continue;
                 }
             }
         }
     }
 
+    // Finds the slot for `search_prefix_id`, which either holds the prefix
+    // already, or is empty so that the caller can create it there. At the
+    // last level a slot may hold another prefix that hashed to the same
+    // place, then this goes on into the overflow set of that prefix,
+    // creating it if it isn't there yet. Returns the slot, its level, and
+    // whether it is in an overflow set.
     #[allow(clippy::type_complexity)]
     fn non_recursive_retrieve_prefix_mut_with_guard(
         &'a self,
         search_prefix_id: PrefixId<AF>,
         guard: &'a Guard,
-    ) -> Result<(&'a AtomicStoredPrefix<AF, Meta>, u8), PrefixStoreError>
+    ) -> Result<(&'a AtomicStoredPrefix<AF, Meta>, u8, bool), PrefixStoreError>
     {
         let mut prefix_set = self
             .prefixes
             .get_root_prefix_set(search_prefix_id.get_len());
         let mut level: u8 = 0;
-        let mut stored_prefix = None;
 
         loop {
-            // HASHING FUNCTION
-            let index = Self::hash_prefix_id(search_prefix_id, level);
-
             trace!("retrieve prefix with guard");
 
             let prefixes = prefix_set.0.load(Ordering::SeqCst, guard);
             debug!("prefixes at level {}? {:?}", level, !prefixes.is_null());
 
-            let prefix_ref = if !prefixes.is_null() {
-                debug!("prefix found.");
-                unsafe { &prefixes.deref()[index] }
-            } else {
-                debug!("no prefix set.");
-                return Ok((stored_prefix.unwrap(), level));
-            };
+            if prefixes.is_null() {
+                if level == 0 {
+                    return Err(PrefixStoreError::PrefixNotFound);
+                }
+                // We came here from a prefix at the last level that isn't
+                // ours. Whoever creates its overflow set first wins, the
+                // others use that one.
+                debug!("create overflow set at level {}", level);
+                let _ = prefix_set.0.compare_exchange(
+                    Shared::null(),
+                    PrefixSet::empty_slots(1),
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                    guard,
+                );
+                continue;
+            }
 
-            stored_prefix = Some(unsafe { prefix_ref.assume_init_ref() });
-
-            if let Some(StoredPrefix {
-                prefix,
-                next_bucket,
-                ..
-            }) = stored_prefix.unwrap().get_stored_prefix_mut(guard)
-            {
-                if search_prefix_id == *prefix {
-                    debug!("found requested prefix {:?}", search_prefix_id);
-                    return Ok((stored_prefix.unwrap(), level));
-                } else {
+            let overflow = prefix_set.is_overflow(level, guard);
+            let index =
+                prefix_set.index_of::<PB>(search_prefix_id, level, guard);
+            let stored_prefix =
+                unsafe { prefixes.deref()[index].assume_init_ref() };
+
+            match stored_prefix.get_stored_prefix_mut(guard) {
+                Some(StoredPrefix {
+                    prefix,
+                    next_bucket,
+                    ..
+                }) if search_prefix_id != *prefix => {
                     level += 1;
                     prefix_set = next_bucket;
-                    continue;
+                }
+                // Either our prefix, or no record yet, so the caller can
+                // insert a new record here.
+                _ => {
+                    debug!("found slot for prefix {:?}", search_prefix_id);
+                    return Ok((stored_prefix, level, overflow));
                 }
             }
-
-            // No record at the deepest level, still we're returning a reference to it,
-            // so the caller can insert a new record here.
-            return Ok((stored_prefix.unwrap(), level));
         }
     }
 
@@ -581,12 +719,10 @@ impl<
             // level) is calculated by performing the hash function
             // over the prefix.
 
-            // HASHING FUNCTION
-            let index = Self::hash_prefix_id(id, level);
-
             let mut prefixes = prefix_set.0.load(Ordering::Acquire, guard);
 
             if !prefixes.is_null() {
+                let index = prefix_set.index_of::<PB>(id, level, guard);
                 let prefix_ref = unsafe { &mut prefixes.deref_mut()[index] };
                 if let Some(stored_prefix) =
                     unsafe { prefix_ref.assume_init_ref() }
@@ -640,10 +776,12 @@ impl<
                  prefix_set: &PrefixSet<AF, Meta>,
                  mut level: u8,
                  guard: &Guard| {
-                // HASHING FUNCTION
-                let index = Self::hash_prefix_id(prefix_id, level);
-
                 let prefixes = prefix_set.0.load(Ordering::SeqCst, guard);
+                if prefixes.is_null() {
+                    return None;
+                }
+                let index =
+                    prefix_set.index_of::<PB>(prefix_id, level, guard);
                 // trace!("nodes {:?}", unsafe { unwrapped_nodes.deref_mut().len() });
                 let prefix_ref = unsafe { &prefixes.deref()[index] };
                 if let Some(stored_prefix) =
@@ -663,7 +801,7 @@ impl<
                             ));
                         };
                         level += 1;
-                        (search_level.f)(
+                        return (search_level.f)(
                             search_level,
                             &stored_prefix.next_bucket,
                             level,
@@ -737,9 +875,10 @@ impl<
                     ),
                     // NOT THE HASHING FUNCTION!
                     BitSpan::new(
-                        ((prefix.get_net() << node_len)
-                            >> (AF::BITS - (prefix.get_len() - node_len)))
-                            .dangerously_truncate_to_u32(),
+                        prefix
+                            .get_net()
+                            .get_index(node_len, prefix.get_len() - node_len)
+                            as u32,
                         prefix.get_len() - node_len,
                     ),
                 );
@@ -796,7 +935,9 @@ impl<
     // cause a collision: they'll all point to the same array element. These
     // collisions are resolved by creating a linked list from each array
     // element, where each element in the list has an array of its own that
-    // uses the hash function with the level incremented.
+    // uses the hash function with the level incremented. Prefixes that
+    // still end up in the same slot at the last level go into overflow
+    // sets of a single slot, see `PrefixSet::index_of`.
 
     pub(crate) fn hash_node_id(id: StrideNodeId<AF>, level: u8) -> usize {
         // Aaaaand, this is all of our hashing function.
@@ -809,36 +950,12 @@ impl<
         let this_level = <NB>::len_to_store_bits(id.get_id().1, level);
         trace!("bits division {}", this_level);
         trace!(
-            "calculated index ({} << {}) >> {}",
+            "calculated index from {} bits {}..{}",
             id.get_id().0,
             last_level,
-            ((<AF>::BITS - (this_level - last_level)) % <AF>::BITS) as usize
-        );
-        // HASHING FUNCTION
-        ((id.get_id().0 << last_level)
-            >> ((<AF>::BITS - (this_level - last_level)) % <AF>::BITS))
-            .dangerously_truncate_to_u32() as usize
-    }
-
-    pub(crate) fn hash_prefix_id(id: PrefixId<AF>, level: u8) -> usize {
-        // Aaaaand, this is all of our hashing function.
-        // I'll explain later.
-        let last_level = if level > 0 {
-            <PB>::get_bits_for_len(id.get_len(), level - 1)
-        } else {
-            0
-        };
-        let this_level = <PB>::get_bits_for_len(id.get_len(), level);
-        trace!("bits division {}", this_level);
-        trace!(
-            "calculated index ({} << {}) >> {}",
-            id.get_net(),
-            last_level,
-            ((<AF>::BITS - (this_level - last_level)) % <AF>::BITS) as usize
+            this_level
         );
         // HASHING FUNCTION
-        ((id.get_net() << last_level)
-            >> ((<AF>::BITS - (this_level - last_level)) % <AF>::BITS))
-            .dangerously_truncate_to_u32() as usize
+        id.get_id().0.get_index(last_level, this_level - last_level)
     }
 }
@@ -1,5 +1,11 @@
 use std::fmt;
 use crate::prelude::*;
+use crate::{
+    AfIter, AfSelector, ConcurrentPrefixStore, FrozenStore, PrefixStore,
+    PrefixStoreMut, Strides,
+};
+use super::custom_alloc::{UpsertReport, Upserted};
+use crate::errors::PrefixStoreError;
 
 // The default stride sizes for IPv4, IPv6, resp.
 #[create_store((
@@ -38,3 +44,288 @@ impl<
         )
     }
 }
+
+//------------ Address family selection -------------------------------------
+
+impl<'a, M: Meta + MergeUpdate> DefaultStore<M> {
+    /// Iterates over the prefixes of the address families selected with
+    /// `af`. Unlike `prefixes_iter`, the tree for a family that is not
+    /// selected is never visited.
+    pub fn prefixes_iter_af(
+        &'a self,
+        af: AfSelector,
+        guard: &'a Guard,
+    ) -> impl Iterator<Item = PrefixRecord<'a, M>> + 'a {
+        AfIter::new(
+            af,
+            || self.prefixes_iter_v4(guard),
+            || self.prefixes_iter_v6(guard),
+        )
+    }
+}
+
+//------------ Reporting inserts --------------------------------------------
+
+impl<M: Meta + MergeUpdate> DefaultStore<M> {
+    /// Inserts a prefix with its meta-data into the store, like
+    /// [`insert`](Self::insert), and reports what the insert did.
+    ///
+    /// The report tells whether the prefix was created or updated, with
+    /// the meta-data from before the update, and how many times the insert
+    /// had to try again because another thread changed the prefix at the
+    /// same time. An insert into an existing prefix is always reported as
+    /// an update, also if merging left the meta-data as it was. See
+    /// [`UpsertReport`] for the details.
+    pub fn upsert(
+        &self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<UpsertReport<M>, Box<dyn std::error::Error>> {
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.upsert(
+                crate::prefix_record::InternalPrefixRecord::new_with_meta(
+                    addr.into(),
+                    prefix.len(),
+                    meta,
+                ),
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.upsert(
+                crate::prefix_record::InternalPrefixRecord::new_with_meta(
+                    addr.into(),
+                    prefix.len(),
+                    meta,
+                ),
+            ),
+        }
+    }
+}
+
+//------------ Meta-data updates --------------------------------------------
+
+impl<M: Meta + MergeUpdate> DefaultStore<M> {
+    /// Merges `meta` into the meta-data of a prefix that is in the store
+    /// already, like [`insert`](Self::insert) does, and reports what it
+    /// did.
+    ///
+    /// Unlike an insert, this goes straight to the prefix without walking
+    /// the tree, which makes it the faster choice when only the
+    /// meta-data of a known prefix changes. If the prefix isn't in the
+    /// store, it returns a "prefix not found" error and leaves the store
+    /// as it is.
+    pub fn update_meta(
+        &self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<UpsertReport<M>, Box<dyn std::error::Error>> {
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.update_meta(
+                PrefixId::<IPv4>::new(addr.into(), prefix.len()),
+                meta,
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.update_meta(
+                PrefixId::<IPv6>::new(addr.into(), prefix.len()),
+                meta,
+            ),
+        }
+    }
+}
+
+//------------ Conditional updates ------------------------------------------
+
+impl<M: Meta + MergeUpdate> DefaultStore<M> {
+    /// Updates the meta-data of `prefix` with `update`, instead of merging
+    /// new meta-data into it like [`insert`](Self::insert) does.
+    ///
+    /// `update` gets the current meta-data, or `None` if the prefix isn't
+    /// in the store, and returns the new meta-data, or `None` to leave the
+    /// prefix as it is. If another thread updates the prefix at the same
+    /// time, `update` is called again with its meta-data, so it may be
+    /// called more than once.
+    ///
+    /// Returns the meta-data from before the update, or `None` if the
+    /// prefix was created or left alone.
+    pub fn upsert_with(
+        &self,
+        prefix: &Prefix,
+        update: impl FnMut(Option<&M>) -> Option<M>,
+    ) -> Result<Option<M>, Box<dyn std::error::Error>> {
+        let res = match prefix.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.upsert_with(
+                PrefixId::<IPv4>::new(addr.into(), prefix.len()),
+                update,
+                M::clone,
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.upsert_with(
+                PrefixId::<IPv6>::new(addr.into(), prefix.len()),
+                update,
+                M::clone,
+            ),
+        }?;
+        match res.upserted {
+            Upserted::Updated(old_meta) => Ok(Some(old_meta)),
+            Upserted::Created | Upserted::Unchanged => Ok(None),
+        }
+    }
+
+    /// Sets the meta-data of `prefix` to `new`, but only if its current
+    /// meta-data equals `current`, with `None` for a prefix that isn't in
+    /// the store. Returns whether the meta-data was set.
+    ///
+    /// With a `current` of `None` this inserts the prefix only if it is
+    /// absent.
+    pub fn compare_and_set(
+        &self,
+        prefix: &Prefix,
+        current: Option<&M>,
+        new: M,
+    ) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        M: PartialEq,
+    {
+        let mut set = false;
+        let update = |meta: Option<&M>| {
+            set = meta == current;
+            if set {
+                Some(new.clone())
+            } else {
+                None
+            }
+        };
+        // The meta-data that was replaced isn't needed, so don't clone it.
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.upsert_with(
+                PrefixId::<IPv4>::new(addr.into(), prefix.len()),
+                update,
+                |_| (),
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.upsert_with(
+                PrefixId::<IPv6>::new(addr.into(), prefix.len()),
+                update,
+                |_| (),
+            ),
+        }?;
+        Ok(set)
+    }
+}
+
+//------------ Freezing -----------------------------------------------------
+
+impl<M: Meta + MergeUpdate> DefaultStore<M> {
+    /// Converts the store into a [`crate::FrozenStore`], an immutable copy
+    /// of the store that is faster to query.
+    pub fn freeze(
+        self,
+    ) -> Result<FrozenStore<M>, Box<dyn std::error::Error>> {
+        let guard = &epoch::pin();
+        FrozenStore::from_records(self.prefixes_iter(guard))
+    }
+}
+
+//------------ Strides ------------------------------------------------------
+
+impl<'a, M: Meta + MergeUpdate> DefaultStore<M> {
+    /// Returns the stride sizes of the IPv4 and the IPv6 tree, from the
+    /// root down.
+    pub fn strides(&'a self) -> Strides<'a> {
+        // The stride sizes are stored in an array that is padded with
+        // zeros.
+        let sizes = |sizes: &'a [u8]| {
+            &sizes[..sizes.iter().take_while(|s| **s > 0).count()]
+        };
+        Strides {
+            v4: sizes(self.v4.store.get_stride_sizes()),
+            v6: sizes(self.v6.store.get_stride_sizes()),
+        }
+    }
+}
+
+//------------ PrefixStore impl ---------------------------------------------
+
+impl<M: Meta + MergeUpdate> PrefixStore<M> for DefaultStore<M> {
+    fn match_prefix<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M> {
+        DefaultStore::match_prefix(self, search_pfx, options, guard)
+    }
+
+    fn more_specifics_from<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M> {
+        DefaultStore::more_specifics_from(self, search_pfx, guard)
+    }
+
+    fn less_specifics_from<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M> {
+        DefaultStore::less_specifics_from(self, search_pfx, guard)
+    }
+
+    fn prefixes_iter<'a>(
+        &'a self,
+        guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, M>> + 'a>
+    where
+        M: 'a,
+    {
+        Box::new(DefaultStore::prefixes_iter(self, guard))
+    }
+
+    fn prefixes_iter_af<'a>(
+        &'a self,
+        af: AfSelector,
+        guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, M>> + 'a>
+    where
+        M: 'a,
+    {
+        Box::new(DefaultStore::prefixes_iter_af(self, af, guard))
+    }
+
+    fn prefixes_len(&self) -> usize {
+        DefaultStore::prefixes_len(self)
+    }
+
+    fn nodes_len(&self) -> usize {
+        DefaultStore::nodes_len(self)
+    }
+
+    fn stats(&self) -> Stats<'_> {
+        DefaultStore::stats(self)
+    }
+}
+
+impl<M: Meta + MergeUpdate> PrefixStoreMut<M> for DefaultStore<M> {
+    fn insert(
+        &mut self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        DefaultStore::insert(self, prefix, meta)
+    }
+
+    fn remove(
+        &mut self,
+        _prefix: &Prefix,
+    ) -> Result<Option<M>, Box<dyn std::error::Error>> {
+        Err(Box::new(PrefixStoreError::RemoveNotSupported))
+    }
+}
+
+impl<M: Meta + MergeUpdate + Send + Sync> ConcurrentPrefixStore<M>
+    for DefaultStore<M>
+{
+    fn insert(
+        &self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        DefaultStore::insert(self, prefix, meta)
+    }
+}
@@ -12,7 +12,7 @@ use std::{marker::PhantomData, sync::atomic::Ordering};
 use super::atomic_types::{NodeBuckets, PrefixBuckets, PrefixSet};
 use super::custom_alloc::CustomAllocStorage;
 use crate::{
-    af::AddressFamily,
+    af::{AddressFamily, IpAddressFamily},
     local_array::{
         bit_span::BitSpan,
         node::{
@@ -57,7 +57,7 @@ pub(crate) struct PrefixIter<
     _meta: PhantomData<M>,
 }
 
-impl<'a, AF: AddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
+impl<'a, AF: IpAddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
     Iterator for PrefixIter<'a, AF, M, PB>
 {
     type Item = (routecore::addr::Prefix, &'a M);
@@ -75,7 +75,9 @@ impl<'a, AF: AddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
                 return None;
             }
 
-            if PB::get_bits_for_len(self.cur_len, self.cur_level) == 0 {
+            if self.cur_level == 0
+                && PB::get_bits_for_len(self.cur_len, self.cur_level) == 0
+            {
                 // END OF THE LENGTH
                 // This length is done too, go to the next length
                 trace!("next length {}", self.cur_len + 1);
@@ -93,16 +95,8 @@ impl<'a, AF: AddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
                     self.prefixes.get_root_prefix_set(self.cur_len);
                 continue;
             }
-            let bucket_size = 1_usize
-                << (if self.cur_level > 0 {
-                    PB::get_bits_for_len(self.cur_len, self.cur_level)
-                        - PB::get_bits_for_len(
-                            self.cur_len,
-                            self.cur_level - 1,
-                        )
-                } else {
-                    PB::get_bits_for_len(self.cur_len, self.cur_level)
-                });
+            // Overflow sets have a single slot, whatever the level.
+            let bucket_size = self.cur_bucket.slots(self.guard);
 
             if self.cursor >= bucket_size {
                 if self.cur_level == 0 {
@@ -448,6 +442,7 @@ pub(crate) struct LessSpecificPrefixIter<
     cur_bucket: &'a PrefixSet<AF, M>,
     cur_level: u8,
     cur_prefix_id: PrefixId<AF>,
+    default_route_done: bool,
     guard: &'a Guard,
     _af: PhantomData<AF>,
     _meta: PhantomData<M>,
@@ -467,9 +462,32 @@ impl<'a, AF: AddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
 
         loop {
             if self.cur_len == 0 {
-                // This is the end, my friend
-                trace!("reached min length {}, returning None", self.cur_len);
-                return None;
+                // The default route is a less-specific of every prefix, so
+                // we always end with checking for it. There's only one
+                // prefix with length zero, so there's no need to go over
+                // the levels: it lives in the first (and only) slot of the
+                // root prefix set for length zero.
+                if self.default_route_done {
+                    // This is the end, my friend
+                    trace!(
+                        "reached min length {}, returning None",
+                        self.cur_len
+                    );
+                    return None;
+                }
+                self.default_route_done = true;
+
+                let root_set = self.prefixes.get_root_prefix_set(0);
+                if root_set.0.load(Ordering::SeqCst, self.guard).is_null() {
+                    return None;
+                }
+                trace!("check for the default route");
+                return root_set
+                    .get_by_index(0, self.guard)
+                    .get_stored_prefix(self.guard)
+                    .and_then(|stored_prefix| {
+                        stored_prefix.get_record(self.guard)
+                    });
             }
 
             // shave a bit of the current prefix.
@@ -483,22 +501,12 @@ impl<'a, AF: AddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
                 self.cur_len,
             );
 
-            let last_level = if self.cur_level > 0 {
-                PB::get_bits_for_len(self.cur_len, self.cur_level - 1)
-            } else {
-                0
-            };
-
-            let this_level =
-                PB::get_bits_for_len(self.cur_len, self.cur_level);
+            let overflow =
+                self.cur_bucket.is_overflow(self.cur_level, self.guard);
 
-            // NOT THE HASHING FUNCTION
-            let index = ((self.cur_prefix_id.get_net() << last_level)
-                >> ((AF::BITS - (this_level - last_level)) % AF::BITS))
-                .dangerously_truncate_to_u32()
-                as usize;
-
-            if this_level == 0 {
+            if !overflow
+                && PB::get_bits_for_len(self.cur_len, self.cur_level) == 0
+            {
                 // END OF THE LENGTH
                 // This length is done too, go to the next length
                 trace!("next length {}", self.cur_len + 1);
@@ -516,9 +524,15 @@ impl<'a, AF: AddressFamily + 'a, M: Meta + 'a, PB: PrefixBuckets<AF, M>>
                 continue;
             }
 
+            // NOT THE HASHING FUNCTION
+            let index = self.cur_bucket.index_of::<PB>(
+                self.cur_prefix_id,
+                self.cur_level,
+                self.guard,
+            );
+
             // LEVEL DEPTH ITERATION
-            let s_pfx =
-                self.cur_bucket.get_by_index(index as usize, self.guard);
+            let s_pfx = self.cur_bucket.get_by_index(index, self.guard);
             // trace!("s_pfx {:?}", s_pfx);
 
             if let Some(stored_prefix) = s_pfx.get_stored_prefix(self.guard) {
@@ -733,10 +747,12 @@ impl<
         trace!("less specifics for {:?}", start_prefix_id);
         trace!("level {}, len {}", 0, start_prefix_id.get_len());
 
-        // We could just let the /0 prefix search the tree and have it return
-        // an empty iterator, but to avoid having to read out the root node
-        // for this prefix, we'll just return an empty iterator. None can be
-        // turned into an Iterator!
+        // The default route (/0) doesn't have any less-specifics. We could
+        // just let it search the tree and have it return an empty iterator,
+        // but to avoid having to read out the root node for this prefix,
+        // we'll just return an empty iterator. None can be turned into an
+        // Iterator! All other prefixes will end their search with the
+        // default route, if it's there.
         if start_prefix_id.get_len() < 1 {
             None
         } else {
@@ -749,6 +765,7 @@ impl<
                 cur_bucket,
                 cur_level: 0,
                 cur_prefix_id: start_prefix_id,
+                default_route_done: false,
                 guard,
                 _af: PhantomData,
                 _meta: PhantomData,
@@ -758,7 +775,16 @@ impl<
         .into_iter()
         .flatten()
     }
+}
 
+impl<
+        'a,
+        AF: IpAddressFamily,
+        M: routecore::record::Meta,
+        NB: NodeBuckets<AF>,
+        PB: PrefixBuckets<AF, M>,
+    > CustomAllocStorage<AF, M, NB, PB>
+{
     // Iterator over all the prefixes in the storage.
     pub fn prefixes_iter(
         &'a self,
@@ -780,7 +806,7 @@ impl<
 
 // ----------- InternalPrefixRecord -> RecordSet (public) -------------------
 
-impl<'a, AF: AddressFamily, Meta: routecore::record::Meta>
+impl<'a, AF: IpAddressFamily, Meta: routecore::record::Meta>
     std::iter::FromIterator<InternalPrefixRecord<AF, Meta>>
     for routecore::bgp::RecordSet<'a, Meta>
 {
@@ -335,7 +335,7 @@ macro_rules! store_node_closure {
                             }
                         }
                         true => {
-                            return Err(Box::new(super::errors::PrefixStoreError::NodeNotFound));
+                            return Err(Box::new(crate::errors::PrefixStoreError::NodeNotFound));
                         }
                     };
                 }
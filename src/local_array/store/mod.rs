@@ -1,10 +1,14 @@
 pub mod custom_alloc;
 pub mod iterators;
-pub mod errors;
 
 pub(crate) mod default_store;
 pub(crate) mod atomic_types;
+pub(crate) mod bit_store;
+pub(crate) mod buckets;
+pub(crate) mod vpn_store;
 
+pub use bit_store::BitPrefixStore;
 pub use default_store::DefaultStore;
+pub use vpn_store::VpnStore;
 #[macro_use]
 mod macros;
\ No newline at end of file
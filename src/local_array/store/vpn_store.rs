@@ -0,0 +1,107 @@
+use crossbeam_epoch::Guard;
+use routecore::addr::Prefix;
+use routecore::record::{MergeUpdate, Meta};
+
+use crate::af::{IpAddressFamily, RouteDistinguisher, VpnIPv4, VpnIPv6};
+use crate::local_array::tree::{PrefixId, TreeBitMap};
+use crate::prefix_record::InternalPrefixRecord;
+use crate::{MatchOptions, QueryResult};
+
+use super::buckets::{GenericNodeBuckets, GenericPrefixBuckets};
+
+type VpnTree<AF, M> =
+    TreeBitMap<AF, M, GenericNodeBuckets<AF>, GenericPrefixBuckets<AF, M>>;
+
+//------------ VpnStore -----------------------------------------------------
+
+/// The VPN-IPv4 and VPN-IPv6 prefixes, for use in multi-threaded contexts.
+///
+/// This is the counterpart of the VPN prefixes in the
+/// [`crate::SingleThreadedStore`]: a prefix goes into the VPN identified
+/// by a [`RouteDistinguisher`], and queries only see the prefixes in the
+/// VPN they ask for. Like the [`crate::MultiThreadedStore`], it takes
+/// inserts from many threads at once, and it can't remove prefixes.
+pub struct VpnStore<M: Meta + MergeUpdate> {
+    v4: VpnTree<VpnIPv4, M>,
+    v6: VpnTree<VpnIPv6, M>,
+}
+
+impl<M: Meta + MergeUpdate> VpnStore<M> {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(VpnStore {
+            v4: TreeBitMap::new()?,
+            v6: TreeBitMap::new()?,
+        })
+    }
+
+    /// Inserts a VPN prefix, i.e. a prefix in the VPN identified by `rd`,
+    /// with its meta-data into the store.
+    pub fn insert_vpn(
+        &self,
+        rd: RouteDistinguisher,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => {
+                self.v4.insert(InternalPrefixRecord::new_with_meta(
+                    VpnIPv4::new(rd, addr),
+                    prefix.len() + VpnIPv4::IP_OFFSET,
+                    meta,
+                ))
+            }
+            std::net::IpAddr::V6(addr) => {
+                self.v6.insert(InternalPrefixRecord::new_with_meta(
+                    VpnIPv6::new(rd, addr),
+                    prefix.len() + VpnIPv6::IP_OFFSET,
+                    meta,
+                ))
+            }
+        }
+    }
+
+    /// Searches for the VPN prefix `search_pfx` in the VPN identified by
+    /// `rd`. Only prefixes within that same VPN can turn up as the result,
+    /// or as its less- or more-specifics, so the prefixes in the result
+    /// carry no RD.
+    pub fn match_vpn_prefix<'a>(
+        &'a self,
+        rd: RouteDistinguisher,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => {
+                self.v4.match_prefix_by_store_direct(
+                    PrefixId::new(
+                        VpnIPv4::new(rd, addr),
+                        search_pfx.len() + VpnIPv4::IP_OFFSET,
+                    ),
+                    options,
+                    guard,
+                )
+            }
+            std::net::IpAddr::V6(addr) => {
+                self.v6.match_prefix_by_store_direct(
+                    PrefixId::new(
+                        VpnIPv6::new(rd, addr),
+                        search_pfx.len() + VpnIPv6::IP_OFFSET,
+                    ),
+                    options,
+                    guard,
+                )
+            }
+        }
+    }
+
+    /// Returns the number of VPN prefixes in the store, over all VPNs.
+    pub fn vpn_prefixes_len(&self) -> usize {
+        self.v4.store.get_prefixes_len() + self.v6.store.get_prefixes_len()
+    }
+
+    /// Returns the number of nodes in the VPN trees.
+    pub fn nodes_len(&self) -> usize {
+        self.v4.store.get_nodes_len() + self.v6.store.get_nodes_len()
+    }
+}
@@ -56,3 +56,161 @@ fn test_af_2() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+//------------ Prefixes colliding at the last level -------------------------
+
+#[cfg(test)]
+mod last_level_collision {
+    use super::*;
+    use crate::local_array::store::atomic_types::{
+        NodeBuckets, NodeSet, PrefixBuckets, PrefixSet,
+    };
+    use crate::local_array::tree::{
+        PrefixId, Stride3, Stride4, Stride5, StrideNodeId, TreeBitMap,
+    };
+    use crate::prefix_record::InternalPrefixRecord;
+    use crate::{IPv4, PrefixAs};
+
+    // Node buckets for strides of four bits, hashing eight bits of a node
+    // per level.
+    struct Nodes(Vec<NodeSet<IPv4, Stride4>>);
+
+    impl NodeBuckets<IPv4> for Nodes {
+        fn init() -> Self {
+            Nodes(
+                (0..8)
+                    .map(|i| {
+                        NodeSet::init(1 << Self::len_to_store_bits(i * 4, 0))
+                    })
+                    .collect(),
+            )
+        }
+
+        fn len_to_store_bits(len: u8, level: u8) -> u8 {
+            let bits = (8 * (level + 1)).min(len);
+            if level > 0 && bits <= 8 * level {
+                0
+            } else {
+                bits
+            }
+        }
+
+        fn get_stride_sizes(&self) -> &[u8] {
+            &[4; 8]
+        }
+
+        fn get_stride_for_id(&self, _id: StrideNodeId<IPv4>) -> u8 {
+            4
+        }
+
+        fn get_store3(
+            &self,
+            _id: StrideNodeId<IPv4>,
+        ) -> &NodeSet<IPv4, Stride3> {
+            unreachable!()
+        }
+
+        fn get_store4(
+            &self,
+            id: StrideNodeId<IPv4>,
+        ) -> &NodeSet<IPv4, Stride4> {
+            &self.0[id.get_len() as usize / 4]
+        }
+
+        fn get_store5(
+            &self,
+            _id: StrideNodeId<IPv4>,
+        ) -> &NodeSet<IPv4, Stride5> {
+            unreachable!()
+        }
+
+        fn get_strides_len() -> u8 {
+            8
+        }
+
+        fn get_first_stride_size() -> u8 {
+            4
+        }
+    }
+
+    // Prefix buckets that only hash the first four bits of a prefix, so
+    // that all prefixes of a length that start with the same four bits end
+    // up in the same slot of the last, and only, level.
+    struct Prefixes(Vec<PrefixSet<IPv4, PrefixAs>>);
+
+    impl PrefixBuckets<IPv4, PrefixAs> for Prefixes {
+        fn init() -> Self {
+            Prefixes(
+                (0..=32)
+                    .map(|len| {
+                        PrefixSet::init(1 << Self::get_bits_for_len(len, 0))
+                    })
+                    .collect(),
+            )
+        }
+
+        fn remove(&mut self, _id: PrefixId<IPv4>) -> Option<PrefixAs> {
+            None
+        }
+
+        fn get_root_prefix_set(
+            &self,
+            len: u8,
+        ) -> &'_ PrefixSet<IPv4, PrefixAs> {
+            &self.0[len as usize]
+        }
+
+        fn get_bits_for_len(len: u8, level: u8) -> u8 {
+            if level == 0 {
+                len.min(4)
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn test_last_level_collision() -> Result<(), Box<dyn Error>> {
+        let tree = TreeBitMap::<IPv4, PrefixAs, Nodes, Prefixes>::new()?;
+
+        // Three /24s that all start with four zero bits.
+        let nets: [IPv4; 3] = [0x0a00_0000, 0x0b00_0000, 0x0c01_0200];
+        for (i, net) in nets.iter().enumerate() {
+            tree.insert(InternalPrefixRecord::new_with_meta(
+                *net,
+                24,
+                PrefixAs(i as u32),
+            ))?;
+        }
+        // Updates go to the record of their own prefix, not to the one
+        // that got to the slot first.
+        tree.insert(InternalPrefixRecord::new_with_meta(
+            nets[2],
+            24,
+            PrefixAs(42),
+        ))?;
+        tree.update_meta(PrefixId::new(nets[1], 24), PrefixAs(43))?;
+        assert!(tree
+            .update_meta(PrefixId::new(0x0d00_0000, 24), PrefixAs(44))
+            .is_err());
+
+        let guard = &crossbeam_epoch::pin();
+        let meta_of = |net: IPv4| {
+            tree.store
+                .non_recursive_retrieve_prefix_with_guard(
+                    PrefixId::new(net, 24),
+                    guard,
+                )
+                .0
+                .and_then(|stored_prefix| stored_prefix.get_record(guard))
+                .map(|record| record.meta.0)
+        };
+        assert_eq!(meta_of(nets[0]), Some(0));
+        assert_eq!(meta_of(nets[1]), Some(43));
+        assert_eq!(meta_of(nets[2]), Some(42));
+        assert_eq!(meta_of(0x0d00_0000), None);
+        assert_eq!(tree.store.prefixes_iter(guard).count(), 3);
+
+        Ok(())
+    }
+}
@@ -3,16 +3,17 @@ use log::{log_enabled, trace, warn};
 use routecore::record::{MergeUpdate, Meta};
 
 use std::hash::Hash;
-use std::sync::atomic::{
-    AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
-};
+use std::sync::atomic::Ordering;
 use std::{fmt::Debug, marker::PhantomData};
 
-use crate::af::AddressFamily;
-use crate::custom_alloc::CustomAllocStorage;
+use crate::af::{AddressFamily, IpAddressFamily};
+use crate::custom_alloc::{CustomAllocStorage, UpsertReport, Upserted};
 use crate::insert_match;
 use crate::local_array::store::atomic_types::{NodeBuckets, PrefixBuckets};
 use crate::prefix_record::InternalPrefixRecord;
+use crate::sync::{
+    fence, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize,
+};
 
 pub(crate) use super::atomic_stride::*;
 use crate::stats::{SizedStride, StrideStats};
@@ -104,16 +105,6 @@ impl<AF: AddressFamily> PrefixId<AF> {
         self.0.unwrap().1
     }
 
-    // This should never fail, since there shouldn't be a invalid prefix in
-    // this prefix id in the first place.
-    pub fn into_pub(&self) -> routecore::addr::Prefix {
-        routecore::addr::Prefix::new(
-            self.get_net().into_ipaddr(),
-            self.get_len(),
-        )
-        .unwrap_or_else(|p| panic!("can't convert {:?} into prefix.", p))
-    }
-
     // Increment the length of the prefix without changing the bits part.
     // This is used to iterate over more-specific prefixes for this prefix,
     // since the more specifics iterator includes the requested `base_prefix`
@@ -123,6 +114,14 @@ impl<AF: AddressFamily> PrefixId<AF> {
     }
 }
 
+impl<AF: IpAddressFamily> PrefixId<AF> {
+    // This should never fail, since there shouldn't be a invalid prefix in
+    // this prefix id in the first place.
+    pub fn into_pub(&self) -> routecore::addr::Prefix {
+        self.get_net().into_prefix(self.get_len())
+    }
+}
+
 impl<AF: AddressFamily> std::default::Default for PrefixId<AF> {
     fn default() -> Self {
         PrefixId(None)
@@ -253,7 +252,7 @@ impl<AF: AddressFamily> AtomicStrideNodeId<AF> {
     //    otherwise, rollback the work result & repeat from step 1.
     pub fn get_serial(&self) -> usize {
         let serial = self.serial.load(Ordering::SeqCst);
-        std::sync::atomic::fence(Ordering::SeqCst);
+        fence(Ordering::SeqCst);
         serial
     }
 
@@ -261,7 +260,7 @@ impl<AF: AddressFamily> AtomicStrideNodeId<AF> {
         &self,
         current_serial: usize,
     ) -> Result<usize, usize> {
-        std::sync::atomic::fence(Ordering::Release);
+        fence(Ordering::Release);
         self.serial.compare_exchange(
             current_serial,
             current_serial + 1,
@@ -296,19 +295,6 @@ impl<AF: AddressFamily> AtomicStrideNodeId<AF> {
             ),
         }
     }
-
-    pub fn from_stridenodeid(
-        stride_type: StrideType,
-        id: StrideNodeId<AF>,
-    ) -> Self {
-        let index: AF = id.0.map_or(AF::zero(), |i| i.0);
-        Self {
-            stride_type,
-            index: AtomicU32::new(index.dangerously_truncate_to_u32()),
-            serial: AtomicUsize::new(if index == AF::zero() { 0 } else { 1 }),
-            _af: PhantomData,
-        }
-    }
 }
 
 impl<AF: AddressFamily> std::convert::From<AtomicStrideNodeId<AF>> for usize {
@@ -366,6 +352,18 @@ pub struct TreeBitMap<
     pub store: CustomAllocStorage<AF, M, NB, PB>,
 }
 
+// The update of an insert: the meta-data of a new prefix, or the meta-data
+// merged into that of an existing prefix.
+fn merge_update_with<M: Meta + MergeUpdate>(
+    meta: M,
+) -> impl FnMut(Option<&M>) -> Result<Option<M>, Box<dyn std::error::Error>>
+{
+    move |current| match current {
+        None => Ok(Some(meta.clone())),
+        Some(current) => current.clone_merge_update(&meta).map(Some),
+    }
+}
+
 impl<
         'a,
         AF: AddressFamily,
@@ -469,13 +467,136 @@ impl<
         &self,
         pfx: InternalPrefixRecord<AF, M>,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        self.merge_prefix(pfx, |_| ()).map(|_| ())
+    }
+
+    // Inserts like `insert`, and reports whether the prefix was created or
+    // updated, and how much contention there was on it.
+    pub fn upsert(
+        &self,
+        pfx: InternalPrefixRecord<AF, M>,
+    ) -> Result<UpsertReport<M>, Box<dyn std::error::Error>> {
+        self.merge_prefix(pfx, M::clone)
+    }
+
+    // Merges the meta-data of `pfx` into the store, with `report_old`
+    // picking what goes into the report of meta-data that was replaced.
+    fn merge_prefix<O>(
+        &self,
+        pfx: InternalPrefixRecord<AF, M>,
+        report_old: impl Fn(&M) -> O,
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
         let guard = &epoch::pin();
 
         if pfx.len == 0 {
-            let _res = self.update_default_route_prefix_meta(pfx.meta, guard);
-            return Ok(());
+            return self.update_default_route_prefix_meta(
+                pfx.meta, report_old, guard,
+            );
         }
 
+        self.upsert_in_tree(
+            PrefixId::new(pfx.net, pfx.len),
+            merge_update_with(pfx.meta),
+            report_old,
+            guard,
+        )
+    }
+
+    // Merges `meta` into the meta-data of a prefix like `insert`, but only
+    // if the prefix is in the store already. That goes straight to the
+    // prefix in the store, without walking the tree. Fails with
+    // `PrefixNotFound` if the prefix isn't there.
+    pub fn update_meta(
+        &self,
+        pfx: PrefixId<AF>,
+        meta: M,
+    ) -> Result<UpsertReport<M>, Box<dyn std::error::Error>> {
+        let guard = &epoch::pin();
+        self.store.update_prefix_with(
+            pfx,
+            merge_update_with(meta),
+            M::clone,
+            guard,
+        )
+    }
+
+    // Upserts a prefix with `update`, which gets the current meta-data of
+    // the prefix, or `None` if it isn't there, and returns its new
+    // meta-data, or `None` to leave the prefix as it is.
+    pub(crate) fn upsert_with<O>(
+        &self,
+        pfx: PrefixId<AF>,
+        mut update: impl FnMut(Option<&M>) -> Option<M>,
+        report_old: impl Fn(&M) -> O,
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
+        let guard = &epoch::pin();
+
+        if pfx.get_len() == 0 {
+            let report = self.store.upsert_prefix_with(
+                pfx,
+                |meta| Ok(update(meta)),
+                report_old,
+                guard,
+            )?;
+            if !matches!(report.upserted, Upserted::Unchanged) {
+                self.store.increment_default_route_prefix_serial();
+            }
+            return Ok(report);
+        }
+
+        // Walking the tree sets the bit for the prefix in its node, so
+        // that only happens if there is a prefix to create. The bit of an
+        // existing prefix is set already.
+        let exists = self
+            .store
+            .non_recursive_retrieve_prefix_with_guard(pfx, guard)
+            .0
+            .and_then(|stored_prefix| stored_prefix.get_record(guard))
+            .is_some();
+        if exists {
+            return self.store.upsert_prefix_with(
+                pfx,
+                |meta| Ok(update(meta)),
+                report_old,
+                guard,
+            );
+        }
+
+        let new_meta = match update(None) {
+            Some(meta) => meta,
+            None => {
+                return Ok(UpsertReport {
+                    upserted: Upserted::Unchanged,
+                    cas_count: 0,
+                })
+            }
+        };
+        // Another thread may create the prefix in the mean time, the
+        // update is made to its meta-data then.
+        self.upsert_in_tree(
+            pfx,
+            |meta| match meta {
+                None => Ok(Some(new_meta.clone())),
+                Some(meta) => Ok(update(Some(meta))),
+            },
+            report_old,
+            guard,
+        )
+    }
+
+    // Walks the tree down to the node of the prefix, creating the nodes on
+    // the way that aren't there yet, and upserts the prefix with `update`.
+    fn upsert_in_tree<O>(
+        &self,
+        pfx: PrefixId<AF>,
+        mut update: impl FnMut(
+            Option<&M>,
+        )
+            -> Result<Option<M>, Box<dyn std::error::Error>>,
+        report_old: impl Fn(&M) -> O,
+        guard: &epoch::Guard,
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
+        let len = pfx.get_len();
         let mut stride_end: u8 = 0;
         let mut cur_i = self.store.get_root_node_id();
         let mut level: u8 = 0;
@@ -483,15 +604,18 @@ impl<
         loop {
             let stride = self.store.get_stride_sizes()[level as usize];
             stride_end += stride;
-            let nibble_len = if pfx.len < stride_end {
-                stride + pfx.len - stride_end
+            let nibble_len = if len < stride_end {
+                stride + len - stride_end
             } else {
                 stride
             };
 
-            let nibble =
-                AF::get_nibble(pfx.net, stride_end - stride, nibble_len);
-            let is_last_stride = pfx.len <= stride_end;
+            let nibble = AF::get_nibble(
+                pfx.get_net(),
+                stride_end - stride,
+                nibble_len,
+            );
+            let is_last_stride = len <= stride_end;
             let stride_start = stride_end - stride;
             // used for counting the number of reloads the
             // match_node_for_strides macro will tolerate.
@@ -508,6 +632,8 @@ impl<
                 nibble;
                 is_last_stride;
                 pfx;
+                update;
+                report_old;
                 stride_start; // the length at the start of the stride a.k.a. start_bit
                 stride;
                 cur_i;
@@ -563,17 +689,24 @@ impl<
     //   how to search for the default-route prefix and now how to set serial
     //  for that prefix and make sure we start searching/inserting with one
     //   of those specialized methods we're good to go.
-    fn update_default_route_prefix_meta(
+    fn update_default_route_prefix_meta<O>(
         &self,
         new_meta: M,
+        report_old: impl Fn(&M) -> O,
         guard: &epoch::Guard,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<UpsertReport<O>, Box<dyn std::error::Error>> {
         trace!("Updating the default route...");
         // let guard = unsafe { epoch::unprotected() };
-        self.store.upsert_prefix(
-            InternalPrefixRecord::new_with_meta(AF::zero(), 0, new_meta),
+        let report = self.store.upsert_prefix_with(
+            PrefixId::new(AF::zero(), 0),
+            merge_update_with(new_meta),
+            report_old,
             guard,
-        )
+        )?;
+        // The tree traversal uses this serial to decide whether there's a
+        // default route at all.
+        self.store.increment_default_route_prefix_serial();
+        Ok(report)
     }
 
     // This function assembles all entries in the `pfx_vec` of all child nodes of the
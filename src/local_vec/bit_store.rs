@@ -0,0 +1,132 @@
+use crate::af::AddressFamily;
+use crate::bit_prefix::{BitPrefix, BitPrefixRecord, BitQueryResult};
+use crate::local_vec::storage_backend::{InMemStorage, StorageBackend};
+use crate::local_vec::TreeBitMap;
+use crate::prefix_record::InternalPrefixRecord;
+use crate::MatchOptions;
+
+use routecore::record::MergeUpdate;
+
+use super::query::PrefixId;
+
+/// A single-threaded store for prefixes of keys of any [`AddressFamily`].
+///
+/// Where the [`crate::SingleThreadedStore`] stores IP prefixes, this store
+/// holds a single tree of [`BitPrefix`]es of any fixed length bit string,
+/// e.g. AS numbers or MPLS labels in an `u32`, or MAC addresses in an
+/// `u64`. It answers the same queries, but returns [`BitQueryResult`]s.
+///
+/// The nodes and prefixes of the tree are kept in the [`StorageBackend`]
+/// `B`, by default the [`InMemStorage`].
+///
+/// Its multi-threaded counterpart is the
+/// [`crate::MultiThreadedBitPrefixStore`].
+pub struct BitPrefixStore<AF, Meta, B = InMemStorage<AF, Meta>>
+where
+    AF: AddressFamily,
+    Meta: routecore::record::Meta + MergeUpdate,
+    B: StorageBackend<AF = AF, Meta = Meta>,
+{
+    tree: TreeBitMap<B>,
+}
+
+impl<'a, AF, Meta, B> BitPrefixStore<AF, Meta, B>
+where
+    AF: AddressFamily,
+    Meta: routecore::record::Meta + MergeUpdate,
+    B: StorageBackend<AF = AF, Meta = Meta>,
+{
+    /// Creates a new store with the given stride sizes, repeated over the
+    /// length of the address family.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the repeated strides don't add up to exactly the
+    /// number of bits of the address family.
+    pub fn new(strides: Vec<u8>) -> Self {
+        BitPrefixStore {
+            tree: TreeBitMap::new(strides),
+        }
+    }
+
+    /// Inserts a prefix with its meta-data into the store. If the prefix
+    /// already exists, the meta-data is merged into the existing meta-data.
+    pub fn insert(
+        &mut self,
+        prefix: BitPrefix<AF>,
+        meta: Meta,
+    ) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
+        self.tree.insert(InternalPrefixRecord::new_with_meta(
+            prefix.net(),
+            prefix.len(),
+            meta,
+        ))
+    }
+
+    /// Removes the prefix from the store and returns its meta-data, or
+    /// `None` if the prefix wasn't in the store.
+    pub fn remove(&mut self, prefix: BitPrefix<AF>) -> Option<Meta> {
+        self.tree.remove(PrefixId::new(prefix.net(), prefix.len()))
+    }
+
+    /// Rewrites the store without the removed prefixes, see
+    /// [`crate::SingleThreadedStore::compact`].
+    pub fn compact(&mut self) {
+        self.tree.compact();
+    }
+
+    pub fn match_prefix(
+        &'a self,
+        search_pfx: BitPrefix<AF>,
+        options: &MatchOptions,
+    ) -> BitQueryResult<'a, AF, Meta> {
+        self.tree
+            .match_prefix_records(
+                PrefixId::new(search_pfx.net(), search_pfx.len()),
+                options,
+            )
+            .into_bit_query_result()
+    }
+
+    pub fn more_specifics_from(
+        &'a self,
+        search_pfx: BitPrefix<AF>,
+    ) -> BitQueryResult<'a, AF, Meta> {
+        self.tree
+            .more_specifics_records_from(PrefixId::new(
+                search_pfx.net(),
+                search_pfx.len(),
+            ))
+            .into_bit_query_result()
+    }
+
+    pub fn less_specifics_from(
+        &'a self,
+        search_pfx: BitPrefix<AF>,
+    ) -> BitQueryResult<'a, AF, Meta> {
+        self.tree
+            .less_specifics_records_from(PrefixId::new(
+                search_pfx.net(),
+                search_pfx.len(),
+            ))
+            .into_bit_query_result()
+    }
+
+    pub fn prefixes_iter(
+        &'a self,
+    ) -> impl Iterator<Item = BitPrefixRecord<'a, AF, Meta>> {
+        self.tree.store.prefixes_iter().map(BitPrefixRecord::from)
+    }
+
+    pub fn prefixes_len(&self) -> usize {
+        self.tree.store.get_prefixes_len()
+    }
+
+    pub fn nodes_len(&self) -> usize {
+        self.tree.store.get_nodes_len()
+    }
+
+    pub fn strides(&self) -> &Vec<u8> {
+        &self.tree.strides
+    }
+}
@@ -0,0 +1,154 @@
+use crate::local_vec::storage_backend::{InMemStorage, StorageBackend};
+use crate::local_vec::TreeBitMap;
+use crate::prefix_record::InternalPrefixRecord;
+use crate::{MatchOptions, QueryResult, Stats, Strides};
+
+use crate::af::{IPv4, IPv6};
+use routecore::addr::Prefix;
+use routecore::bgp::PrefixRecord;
+use routecore::record::MergeUpdate;
+
+use super::query::PrefixId;
+
+/// An immutable Prefix Store, for read-heavy workloads on a table that
+/// doesn't change anymore.
+///
+/// A frozen store is created from a [`crate::MultiThreadedStore`] with
+/// [`crate::MultiThreadedStore::freeze`]. It keeps its nodes in one
+/// contiguous vec per address family, laid out depth-first, with the
+/// prefixes of every node in a sorted vec, just like the
+/// [`crate::SingleThreadedStore`]. As it can't be changed, it does without
+/// the atomics, the epochs and the hash buckets of the multi-threaded
+/// store, and lookups don't need a guard.
+pub struct FrozenStore<Meta: routecore::record::Meta>
+where
+    Meta: MergeUpdate,
+{
+    v4: TreeBitMap<InMemStorage<IPv4, Meta>>,
+    v6: TreeBitMap<InMemStorage<IPv6, Meta>>,
+}
+
+impl<Meta: routecore::record::Meta + MergeUpdate> FrozenStore<Meta> {
+    // Builds the trees from the records, and compacts them, which moves
+    // the nodes into depth-first order.
+    pub(crate) fn from_records<'a>(
+        records: impl Iterator<Item = PrefixRecord<'a, Meta>>,
+    ) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        Meta: 'a,
+    {
+        let mut v4 = TreeBitMap::new(vec![5, 5, 4, 4, 4, 4, 3, 3]);
+        let mut v6 = TreeBitMap::new(vec![4]);
+
+        for record in records {
+            let len = record.prefix.len();
+            let meta = record.meta.into_owned();
+            match record.prefix.addr() {
+                std::net::IpAddr::V4(addr) => {
+                    v4.insert(InternalPrefixRecord::new_with_meta(
+                        addr.into(),
+                        len,
+                        meta,
+                    ))?
+                }
+                std::net::IpAddr::V6(addr) => {
+                    v6.insert(InternalPrefixRecord::new_with_meta(
+                        addr.into(),
+                        len,
+                        meta,
+                    ))?
+                }
+            }
+        }
+        v4.compact();
+        v6.compact();
+
+        Ok(FrozenStore { v4, v6 })
+    }
+}
+
+impl<'a, Meta: routecore::record::Meta + MergeUpdate> FrozenStore<Meta> {
+    pub fn match_prefix(
+        &'a self,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+    ) -> QueryResult<'a, Meta> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.match_prefix(
+                PrefixId::<IPv4>::new(addr.into(), search_pfx.len()),
+                options,
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.match_prefix(
+                PrefixId::<IPv6>::new(addr.into(), search_pfx.len()),
+                options,
+            ),
+        }
+    }
+
+    pub fn more_specifics_from(
+        &'a self,
+        search_pfx: &Prefix,
+    ) -> QueryResult<'a, Meta> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.more_specifics_from(
+                PrefixId::<IPv4>::new(addr.into(), search_pfx.len()),
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.more_specifics_from(
+                PrefixId::<IPv6>::new(addr.into(), search_pfx.len()),
+            ),
+        }
+    }
+
+    pub fn less_specifics_from(
+        &'a self,
+        search_pfx: &Prefix,
+    ) -> QueryResult<'a, Meta> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.less_specifics_from(
+                PrefixId::<IPv4>::new(addr.into(), search_pfx.len()),
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.less_specifics_from(
+                PrefixId::<IPv6>::new(addr.into(), search_pfx.len()),
+            ),
+        }
+    }
+
+    pub fn prefixes_iter(&'a self) -> crate::PrefixRecordIter<'a, Meta> {
+        crate::PrefixRecordIter::<'a, Meta> {
+            v4: Some(self.v4.store.prefixes[..].iter()),
+            v6: self.v6.store.prefixes[..].iter(),
+            v4_removed: self.v4.store.removed[..].iter(),
+            v6_removed: self.v6.store.removed[..].iter(),
+        }
+    }
+
+    pub fn prefixes_len(&self) -> usize {
+        self.v4.store.get_prefixes_len() + self.v6.store.get_prefixes_len()
+    }
+
+    pub fn prefixes_v4_len(&self) -> usize {
+        self.v4.store.get_prefixes_len()
+    }
+
+    pub fn prefixes_v6_len(&self) -> usize {
+        self.v6.store.get_prefixes_len()
+    }
+
+    pub fn nodes_len(&self) -> usize {
+        self.v4.store.get_nodes_len() + self.v6.store.get_nodes_len()
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            v4: &self.v4.stats,
+            v6: &self.v6.stats,
+        }
+    }
+
+    pub fn strides(&'a self) -> Strides {
+        Strides {
+            v4: &self.v4.strides,
+            v6: &self.v6.strides,
+        }
+    }
+}
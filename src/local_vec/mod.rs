@@ -3,6 +3,8 @@ pub mod query;
 pub mod node;
 pub mod storage_backend;
 pub mod store;
+pub mod bit_store;
+pub mod frozen;
 
 pub(crate) use tree::TreeBitMap;
 
@@ -201,69 +201,65 @@ where
 
     //-------- Search nibble functions --------------------------------------
 
-    // This function looks for the longest marching prefix in the provided nibble,
-    // by iterating over all the bits in it and comparing that with the appriopriate
-    // bytes from the requested prefix.
-    // It mutates the `less_specifics_vec` that was passed in to hold all the prefixes
-    // found along the way.
-    pub(crate) fn search_stride_for_longest_match_at(
+    // This function collects all the prefixes in this node that cover the
+    // search prefix, i.e. its less-specifics and the search prefix itself,
+    // by iterating over all the bits in the nibble, like a longest match
+    // would. The less-specifics are pushed onto `less_specifics_vec` in
+    // order of increasing length.
+    //
+    // The default route (nibble length 0) can only live on the root node,
+    // so only there we start at nibble length 0.
+    //
+    // Returns the child node to continue the search in, if the search
+    // prefix reaches beyond this node, and the exactly matching prefix, if
+    // it lives in this node.
+    pub(crate) fn search_stride_for_covering_prefixes_at(
         &self,
         search_pfx: PrefixId<AF>,
-        mut nibble: u32,
+        nibble: u32,
         nibble_len: u8,
         start_bit: u8,
-        less_specifics_vec: &mut Option<Vec<NodeId>>,
+        less_specifics_vec: &mut Vec<NodeId>,
     ) -> (Option<NodeId>, Option<NodeId>) {
-        let mut bit_pos = S::get_bit_pos(nibble, nibble_len);
         let mut found_pfx = None;
+        let first_nibble_len = if start_bit == 0 { 0 } else { 1 };
 
-        for n_l in 1..(nibble_len + 1) {
+        for n_l in first_nibble_len..(nibble_len + 1) {
             // Move the bit in the right position.
-            nibble =
-                AddressFamily::get_nibble(search_pfx.get_net(), start_bit, n_l);
-            bit_pos = S::get_bit_pos(nibble, n_l);
-
-            // Check if the prefix has been set, if so select the prefix. This is not
-            // necessarily the final prefix that will be returned.
+            let ls_nibble = if n_l == 0 {
+                0
+            } else {
+                AddressFamily::get_nibble(search_pfx.get_net(), start_bit, n_l)
+            };
+            let bit_pos = S::get_bit_pos(ls_nibble, n_l);
 
-            // Check it there's a prefix matching in this bitmap for this nibble
             if self.pfxbitarr & bit_pos > S::zero() {
                 let f_pfx = self.pfx_vec
-                    [S::get_pfx_index(self.pfxbitarr, nibble, n_l)];
-
-                // Receiving a less_specifics_vec means that the user wants to have
-                // all the last-specific prefixes returned, so add the found prefix.
-                if let Some(ls_vec) = less_specifics_vec {
-                    if !(search_pfx.get_len() <= start_bit + nibble_len
-                        || (S::into_stride_size(self.ptrbitarr)
-                            & S::get_bit_pos(nibble, nibble_len))
-                            == S::zero())
-                    {
-                        ls_vec.push(f_pfx);
-                    }
+                    [S::get_pfx_index(self.pfxbitarr, ls_nibble, n_l)];
+                if start_bit + n_l == search_pfx.get_len() {
+                    found_pfx = Some(f_pfx);
+                } else {
+                    less_specifics_vec.push(f_pfx);
                 }
-
-                found_pfx = Some(f_pfx);
             }
         }
 
-        // Check if this the last stride, or if they're no more children to go to,
-        // if so return what we found up until now.
-        if search_pfx.get_len() <= start_bit + nibble_len
-            || (S::into_stride_size(self.ptrbitarr) & bit_pos) == S::zero()
-        // No children or at the end, return the definitive LMP we found.
-        {
-            return (
-                None,      /* no more children */
-                found_pfx, /* The definitive LMP if any */
-            );
+        // Is this the last nibble? Then we're done.
+        if search_pfx.get_len() <= start_bit + nibble_len {
+            return (None, found_pfx);
         }
 
-        // There's another child, return it together with the preliminary LMP we found.
-        (
-            Some(self.ptr_vec[S::get_ptr_index(self.ptrbitarr, nibble)]), /* The node that has children the next stride */
-            found_pfx,
-        )
+        // Otherwise continue with the child node at the complete nibble, if
+        // there is one.
+        let bit_pos = S::get_bit_pos(nibble, nibble_len);
+        if (S::into_stride_size(self.ptrbitarr) & bit_pos) > S::zero() {
+            (
+                Some(self.ptr_vec[S::get_ptr_index(self.ptrbitarr, nibble)]),
+                None,
+            )
+        } else {
+            (None, None)
+        }
     }
 
     // This function looks for the exactly matching prefix in the provided nibble.
@@ -277,7 +273,6 @@ where
         nibble: u32,
         nibble_len: u8,
         start_bit: u8,
-        _: &mut Option<Vec<NodeId>>,
     ) -> (Option<NodeId>, Option<NodeId>) {
         // This is an exact match, so we're only considering the position of the full nibble.
         let bit_pos = S::get_bit_pos(nibble, nibble_len);
@@ -319,81 +314,6 @@ where
         )
     }
 
-    // This function looks for the exactly matching prefix in the provided nibble,
-    // just like the one above, but this *does* iterate over all the bytes in the nibble to collect
-    // the less-specific prefixes of the the search prefix.
-    // This is of course slower, so it should only be used when the user explicitly requests less-specifics.
-    pub(crate) fn search_stride_for_exact_match_with_less_specifics_at(
-        &self,
-        search_pfx: PrefixId<AF>,
-        mut nibble: u32,
-        nibble_len: u8,
-        start_bit: u8,
-        less_specifics_vec: &mut Option<Vec<NodeId>>,
-    ) -> (Option<NodeId>, Option<NodeId>) {
-        let mut bit_pos = S::get_bit_pos(nibble, nibble_len);
-        let mut found_pfx = None;
-
-        let ls_vec = less_specifics_vec
-            .as_mut()
-            .expect("You shouldn't call this function without a `less_specifics_vec` buffer. Supply one when calling this function or use `search_stride_for_exact_match_at`");
-
-        for n_l in 1..(nibble_len + 1) {
-            // Move the bit in the right position.
-            nibble =
-                AddressFamily::get_nibble(search_pfx.get_net(), start_bit, n_l);
-            bit_pos = S::get_bit_pos(nibble, n_l);
-
-            // Check if the prefix has been set, if so select the prefix. This is not
-            // necessarily the final prefix that will be returned.
-
-            // Check it there's a prefix matching in this bitmap for this nibble,
-
-            if self.pfxbitarr & bit_pos > S::zero() {
-                // since we want an exact match only, we will fill the prefix field only
-                // if we're exactly at the last bit of the nibble
-                if n_l == nibble_len {
-                    found_pfx = Some(
-                        self.pfx_vec
-                            [S::get_pfx_index(self.pfxbitarr, nibble, n_l)],
-                    );
-                }
-
-                // Receiving a less_specifics_vec means that the user wants to have
-                // all the last-specific prefixes returned, so add the found prefix.
-                ls_vec.push(
-                    self.pfx_vec
-                        [S::get_pfx_index(self.pfxbitarr, nibble, n_l)],
-                );
-            }
-        }
-
-        if found_pfx.is_none() {
-            // no prefix here, clear out all of the prefixes we found along the way,
-            // since it doesn't make sense to return less-specifics if we don't have a exact match.
-            ls_vec.clear();
-        }
-
-        // Check if this the last stride, or if they're no more children to go to,
-        // if so return what we found up until now.
-        match search_pfx.get_len() <= start_bit + nibble_len
-            || (S::into_stride_size(self.ptrbitarr) & bit_pos)
-                == <S as std::ops::BitAnd>::Output::zero()
-        {
-            // No children or at the end, return the definitive LMP we found.
-            true => (
-                None,      /* no more children */
-                found_pfx, /* The definitive LMP if any */
-            ),
-            // There's another child, we won't return the found_pfx, since we're not
-            // at the last nibble and we want an exact match only.
-            false => (
-                Some(self.ptr_vec[S::get_ptr_index(self.ptrbitarr, nibble)]), /* The node that has children the next stride */
-                None,
-            ),
-        }
-    }
-
     // Search a stride for more-specific prefixes and child nodes containing
     // more specifics for `search_prefix`.
     pub fn add_more_specifics_at(
@@ -483,3 +403,85 @@ where
         )
     }
 }
+
+//-------- Removal ----------------------------------------------------------
+
+impl<AF, S, NodeId> TreeBitMapNode<AF, S, NodeId>
+where
+    AF: AddressFamily,
+    S: Stride
+        + std::ops::BitAnd<Output = S>
+        + std::ops::BitOr<Output = S>
+        + std::ops::BitXor<Output = S>
+        + Zero,
+    <S as Stride>::PtrSize: Debug
+        + Binary
+        + Copy
+        + std::ops::BitAnd<Output = S::PtrSize>
+        + PartialOrd
+        + Zero,
+    NodeId: SortableNodeId + Copy,
+{
+    // Removes the prefix at the position of the nibble from this node, by
+    // clearing its bit in the pfxbitarr and taking it out of the `pfx_vec`.
+    // Returns the id of the removed prefix, or None if there was no prefix
+    // at that position.
+    pub(crate) fn remove_prefix_at(
+        &mut self,
+        nibble: u32,
+        nibble_len: u8,
+    ) -> Option<NodeId> {
+        let bit_pos = S::get_bit_pos(nibble, nibble_len);
+
+        if self.pfxbitarr & bit_pos == S::zero() {
+            return None;
+        }
+
+        let index = S::get_pfx_index(self.pfxbitarr, nibble, nibble_len);
+        self.pfxbitarr = self.pfxbitarr ^ bit_pos;
+        Some(self.pfx_vec.remove(index))
+    }
+
+    // Re-indexes all the child nodes and prefixes of this node with the
+    // new ids returned by `map_ptr` and `map_pfx`. A child node for which
+    // `map_ptr` returns None is removed from this node, together with its
+    // bit in the ptrbitarr.
+    //
+    // The sort part of the ids must be left untouched by the mapping
+    // functions, so that the `ptr_vec` and the `pfx_vec` stay ordered in
+    // line with their bitmaps.
+    //
+    // Returns whether this node is empty afterwards, i.e. whether it has
+    // neither prefixes nor child nodes left.
+    pub(crate) fn reindex(
+        &mut self,
+        mut map_ptr: impl FnMut(NodeId) -> Option<NodeId>,
+        mut map_pfx: impl FnMut(NodeId) -> NodeId,
+    ) -> bool {
+        let ptrbitarr = self.ptrbitarr;
+        let mut new_ptr_vec: Vec<Option<NodeId>> =
+            vec![None; self.ptr_vec.len()];
+
+        // Child nodes only live at full length nibbles, so we only have to
+        // check those positions in the ptrbitarr.
+        for nibble in 0..(1_u32 << S::STRIDE_LEN) {
+            let bit_pos = S::get_bit_pos(nibble, S::STRIDE_LEN);
+            if (S::into_stride_size(ptrbitarr) & bit_pos) == S::zero() {
+                continue;
+            }
+
+            let index = S::get_ptr_index(ptrbitarr, nibble);
+            new_ptr_vec[index] = map_ptr(self.ptr_vec[index]);
+            if new_ptr_vec[index].is_none() {
+                self.ptrbitarr = S::into_ptrbitarr_size(
+                    S::into_stride_size(self.ptrbitarr) ^ bit_pos,
+                );
+            }
+        }
+
+        self.ptr_vec = new_ptr_vec.into_iter().flatten().collect();
+        self.pfx_vec = self.pfx_vec.iter().map(|p| map_pfx(*p)).collect();
+
+        self.ptr_vec.is_empty() && self.pfx_vec.is_empty()
+    }
+}
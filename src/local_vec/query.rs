@@ -1,12 +1,12 @@
-use crate::local_vec::node::TreeBitMapNode;
 use crate::local_vec::storage_backend::*;
 use crate::local_vec::tree::{SizedStrideNode, TreeBitMap};
 use crate::node_id::SortableNodeId;
-use crate::{MatchOptions, MatchType};
+use crate::prefix_record::InternalPrefixRecord;
+use crate::{MatchOptions, MatchType, QueryResult};
 
-use crate::af::AddressFamily;
-use routecore::addr::Prefix;
-use routecore::bgp::RecordSet;
+use crate::af::{AddressFamily, IpAddressFamily};
+use crate::bit_prefix::{BitPrefix, BitPrefixRecord, BitQueryResult};
+use routecore::record::Meta;
 
 #[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
 pub struct PrefixId<AF: AddressFamily>((AF, u8));
@@ -25,124 +25,46 @@ impl<AF: AddressFamily> PrefixId<AF> {
     }
 }
 
-//------------- QueryResult -------------------------------------------------
+//------------ Prefix Matching ----------------------------------------------
 
-#[derive(Clone, Debug)]
-pub struct QueryResult<'a, Meta: routecore::record::Meta> {
-    pub match_type: MatchType,
-    pub prefix: Option<Prefix>,
-    pub prefix_meta: Option<&'a Meta>,
-    pub less_specifics: Option<RecordSet<'a, Meta>>,
-    pub more_specifics: Option<RecordSet<'a, Meta>>,
-}
-
-impl<'a, Meta: routecore::record::Meta> std::fmt::Display
-    for QueryResult<'a, Meta>
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let pfx_str = match self.prefix {
-            Some(pfx) => format!("{}", pfx),
-            None => "".to_string(),
-        };
-        let pfx_meta_str = match &self.prefix_meta {
-            Some(pfx_meta) => format!("{}", pfx_meta),
-            None => "".to_string(),
-        };
-        write!(
-            f,
-            "match_type: {}\nprefix: {}\nmetadata: {}\nless_specifics: {}\nmore_specifics: {}",
-            self.match_type,
-            pfx_str,
-            pfx_meta_str,
-            if let Some(ls) = self.less_specifics.as_ref() {
-                format!("{}", ls)
-            } else {
-                "".to_string()
-            },
-            if let Some(ms) = self.more_specifics.as_ref() {
-                format!("{}", ms)
-            } else {
-                "".to_string()
-            },
-        )
-    }
-}
-
-//------------ Longest Matching Prefix  -------------------------------------
+// The queries in this tree have the same semantics as the queries on the
+// `local_array` tree (`match_prefix_by_store_direct` and friends), so that
+// both the stores return the same results for the same data.
 
 impl<'a, Store> TreeBitMap<Store>
 where
     Store: StorageBackend,
 {
-    // In a LMP search we have to go over all the nibble lengths in the stride up
-    // until the value of the actual nibble length were looking for (until we reach
-    // stride length for all strides that aren't the last) and see if the
-    // prefix bit in that posision is set.
-    // Note that this does not search for prefixes with length 0 (which would always
-    // match).
-    // So for matching a nibble 1010, we have to search for 1, 10, 101 and 1010 on
-    // resp. position 1, 5, 12 and 25:
+    // Walks down the tree along the path of `search_pfx` and returns the
+    // exactly matching prefix, if any, together with all the less-specifics
+    // of `search_pfx`, in order of increasing length.
+    //
+    // In a LMP search we have to go over all the nibble lengths in the
+    // stride up until the value of the actual nibble length were looking
+    // for (until we reach stride length for all strides that aren't the
+    // last) and see if the prefix bit in that posision is set.
+    // So for matching a nibble 1010, we have to search for 1, 10, 101 and
+    // 1010 on resp. position 1, 5, 12 and 25:
     //                       ↓          ↓                         ↓                                                              ↓
     // pfx bit arr (u32)   0 1 2  3  4  5  6   7   8   9  10  11  12  13  14   15   16   17   18   19   20   21   22   23   24   25   26   27   28   29   30   31
     // nibble              * 0 1 00 01 10 11 000 001 010 011 100 101 110 111 0000 0001 0010 0011 0100 0101 0110 0111 1000 1001 1010 1011 1100 1101 1110 1111    x
     // nibble len offset   0 1    2            3                                4
-
-    pub(crate) fn match_prefix(
-        &'a self,
+    //
+    // The position 0 ('*') holds the default route and is only used on the
+    // root node.
+    fn covering_prefixes(
+        &self,
         search_pfx: PrefixId<Store::AF>,
-        options: &MatchOptions,
-    ) -> QueryResult<'a, Store::Meta> {
+    ) -> (Option<Store::NodeType>, Vec<Store::NodeType>) {
+        let mut less_specifics_vec = vec![];
+        let mut exact_match = None;
         let mut stride_end = 0;
-
         let mut node = self.retrieve_node(self.get_root_node_id()).unwrap();
-        let mut nibble;
-        let mut nibble_len;
-
-        //---- result values ------------------------------------------------
-
-        // These result values are kept in mutable variables, and assembled
-        // at the end into a QueryResult struct. This proved to result in the
-        // most efficient code, where we don't have to match on 
-        // SizedStrideNode over and over. The `match_type` field in the 
-        // QueryResult is computed at the end.
-
-        // The final prefix
-        let mut match_prefix_idx: Option<
-            <<Store as StorageBackend>::NodeType as SortableNodeId>::Part,
-        > = None;
-
-        // The indexes of the less-specifics
-        let mut less_specifics_vec = if options.include_less_specifics {
-            Some(Vec::<Store::NodeType>::new())
-        } else {
-            None
-        };
-
-        // The indexes of the more-specifics.
-        let mut more_specifics_vec = if options.include_more_specifics {
-            Some(Vec::<Store::NodeType>::new())
-        } else {
-            None
-        };
-
-        //-------------- Stride Processing ----------------------------------
-
-        // We're going to iterate over all the strides in the treebitmap (so
-        // up to the last bit in the max prefix lentgth for that tree). When
-        // a final prefix is found or we get to the end of the strides,
-        // depending on the options.match_type (the type requested by the user).
-        // we ALWAYS break out of the loop. WE ALWAYS BREAK OUT OF THE LOOP
-        // Just before breaking some processing is done inside the loop before
-        // the break (looking up more-specifics mainly), which looks at bit
-        // repetitious, but again it's been done like that to avoid having to
-        // match over a SizedStrideNode again in the `post-processing`
-        // section.
 
         for stride in self.strides.iter() {
             stride_end += stride;
-            let last_stride = search_pfx.get_len() < stride_end;
 
-            nibble_len = if last_stride {
+            let nibble_len = if search_pfx.get_len() < stride_end {
                 stride + search_pfx.get_len() - stride_end
             } else {
                 *stride
@@ -150,611 +72,419 @@ where
 
             // Shift left and right to set the bits to zero that are not in
             // the nibble we're handling here.
-            nibble = AddressFamily::get_nibble(
+            let nibble = AddressFamily::get_nibble(
                 search_pfx.get_net(),
                 stride_end - stride,
                 nibble_len,
             );
 
-            match node {
-                SizedStrideNode::Stride3(current_node) => {
-                    let search_fn = match options.match_type {
-                        MatchType::ExactMatch => {
-                            if options.include_less_specifics {
-                                TreeBitMapNode::search_stride_for_exact_match_with_less_specifics_at
-                            } else {
-                                TreeBitMapNode::search_stride_for_exact_match_at
-                            }
-                        }
-                        MatchType::LongestMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                        MatchType::EmptyMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                    };
-
-                    // This whole match assumes that:
-                    // - if the first value in the return tuple of
-                    //   `search_fn` holds a value, then we need to continue
-                    //   searching by following the node contained in the
-                    //    value.
-                    // - The second value in the tuple holds the prefix that
-                    //   was found.
-                    // The less_specifics_vec is mutated by `search_fn` to
-                    // hold the prefixes found along the way, in the cases
-                    // where `include_less_specifics` was requested by the user.
-                    match search_fn(
-                        current_node,
+            let (next_node, found_pfx) = match node {
+                SizedStrideNode::Stride3(n) => n
+                    .search_stride_for_covering_prefixes_at(
                         search_pfx,
                         nibble,
                         nibble_len,
                         stride_end - stride,
                         &mut less_specifics_vec,
-                    ) {
-                        // This and the next match will handle all
-                        // intermediary nodes, but they might also handle
-                        // exit nodes.
-                        (Some(n), Some(pfx_idx)) => {
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (Some(n), None) => {
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        // This handles exact and longest matches: there are
-                        // no more children, but there is a prefix on this node.
-                        (None, Some(pfx_idx)) => {
-                            if options.include_more_specifics {
-                                more_specifics_vec = self
-                                    .get_all_more_specifics_from_nibble(
-                                        current_node,
-                                        nibble,
-                                        nibble_len,
-                                    );
-                            }
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            break;
-                        }
-                        // This handles cases where there's no prefix (and no
-                        // child) for exact match or longest match, the empty
-                        // match - which doesn't care about actually finding
-                        // a prefix - just continues in search of
-                        // more-specifics.
-                        (None, None) => {
-                            match options.match_type {
-                                MatchType::EmptyMatch => {
-                                    // To make sure we don't process this
-                                    // match arm more then once, we return
-                                    // early here.
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-
-                                    match_prefix_idx = None;
-                                    break;
-                                }
-                                MatchType::LongestMatch => {}
-                                MatchType::ExactMatch => {
-                                    match_prefix_idx = None;
-                                }
-                            }
-                            break;
-                        }
-                    }
-                }
-                SizedStrideNode::Stride4(current_node) => {
-                    let search_fn = match options.match_type {
-                        MatchType::ExactMatch => {
-                            if options.include_less_specifics {
-                                TreeBitMapNode::search_stride_for_exact_match_with_less_specifics_at
-                            } else {
-                                TreeBitMapNode::search_stride_for_exact_match_at
-                            }
-                        }
-                        MatchType::LongestMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                        MatchType::EmptyMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                    };
-                    match search_fn(
-                        current_node,
+                    ),
+                SizedStrideNode::Stride4(n) => n
+                    .search_stride_for_covering_prefixes_at(
                         search_pfx,
                         nibble,
                         nibble_len,
                         stride_end - stride,
                         &mut less_specifics_vec,
-                    ) {
-                        (Some(n), Some(pfx_idx)) => {
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (Some(n), None) => {
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (None, Some(pfx_idx)) => {
-                            if options.include_more_specifics {
-                                more_specifics_vec = self
-                                    .get_all_more_specifics_from_nibble(
-                                        current_node,
-                                        nibble,
-                                        nibble_len,
-                                    );
-                            }
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            break;
-                        }
-                        (None, None) => {
-                            match options.match_type {
-                                MatchType::EmptyMatch => {
-                                    // To make sure we don't process this match arm more then once, we
-                                    // return early here.
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-
-                                    match_prefix_idx = None;
-                                    break;
-                                }
-                                MatchType::LongestMatch => {}
-                                MatchType::ExactMatch => {
-                                    match_prefix_idx = None;
-                                }
-                            }
-                            break;
-                        }
-                    }
-                }
-                SizedStrideNode::Stride5(current_node) => {
-                    let search_fn = match options.match_type {
-                        MatchType::ExactMatch => {
-                            if options.include_less_specifics {
-                                TreeBitMapNode::search_stride_for_exact_match_with_less_specifics_at
-                            } else {
-                                TreeBitMapNode::search_stride_for_exact_match_at
-                            }
-                        }
-                        MatchType::LongestMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                        MatchType::EmptyMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                    };
-                    match search_fn(
-                        current_node,
+                    ),
+                SizedStrideNode::Stride5(n) => n
+                    .search_stride_for_covering_prefixes_at(
                         search_pfx,
                         nibble,
                         nibble_len,
                         stride_end - stride,
                         &mut less_specifics_vec,
-                    ) {
-                        (Some(n), Some(pfx_idx)) => {
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (Some(n), None) => {
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (None, Some(pfx_idx)) => {
-                            if options.include_more_specifics {
-                                more_specifics_vec = self
-                                    .get_all_more_specifics_from_nibble(
-                                        current_node,
-                                        nibble,
-                                        nibble_len,
-                                    );
-                            }
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            break;
-                        }
-                        (None, None) => {
-                            match options.match_type {
-                                MatchType::EmptyMatch => {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-
-                                    match_prefix_idx = None;
-                                    break;
-                                }
-                                MatchType::LongestMatch => {}
-                                MatchType::ExactMatch => {
-                                    match_prefix_idx = None;
-                                }
-                            }
-                            break;
-                        }
-                    }
-                }
-                SizedStrideNode::Stride6(current_node) => {
-                    let search_fn = match options.match_type {
-                        MatchType::ExactMatch => {
-                            if options.include_less_specifics {
-                                TreeBitMapNode::search_stride_for_exact_match_with_less_specifics_at
-                            } else {
-                                TreeBitMapNode::search_stride_for_exact_match_at
-                            }
-                        }
-                        MatchType::LongestMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                        MatchType::EmptyMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                    };
-                    match search_fn(
-                        current_node,
+                    ),
+                SizedStrideNode::Stride6(n) => n
+                    .search_stride_for_covering_prefixes_at(
                         search_pfx,
                         nibble,
                         nibble_len,
                         stride_end - stride,
                         &mut less_specifics_vec,
-                    ) {
-                        (Some(n), Some(pfx_idx)) => {
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (Some(n), None) => {
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (None, Some(pfx_idx)) => {
-                            if options.include_more_specifics {
-                                more_specifics_vec = self
-                                    .get_all_more_specifics_from_nibble(
-                                        current_node,
-                                        nibble,
-                                        nibble_len,
-                                    );
-                            }
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            break;
-                        }
-                        (None, None) => {
-                            match options.match_type {
-                                MatchType::EmptyMatch => {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-
-                                    match_prefix_idx = None;
-                                    break;
-                                }
-                                MatchType::LongestMatch => {}
-                                MatchType::ExactMatch => {
-                                    match_prefix_idx = None;
-                                }
-                            }
-                            break;
-                        }
+                    ),
+                SizedStrideNode::Stride7(n) => n
+                    .search_stride_for_covering_prefixes_at(
+                        search_pfx,
+                        nibble,
+                        nibble_len,
+                        stride_end - stride,
+                        &mut less_specifics_vec,
+                    ),
+                SizedStrideNode::Stride8(n) => n
+                    .search_stride_for_covering_prefixes_at(
+                        search_pfx,
+                        nibble,
+                        nibble_len,
+                        stride_end - stride,
+                        &mut less_specifics_vec,
+                    ),
+            };
+
+            if found_pfx.is_some() {
+                exact_match = found_pfx;
+            }
+
+            match next_node {
+                Some(n) => {
+                    node = self.retrieve_node(n).unwrap();
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+
+        (exact_match, less_specifics_vec)
+    }
+
+    // Walks down the tree to the node that holds `search_pfx` (or would
+    // hold it, if it existed), and collects all the more-specifics of
+    // `search_pfx` from there.
+    fn more_specifics_vec_from(
+        &self,
+        search_pfx: PrefixId<Store::AF>,
+    ) -> Vec<Store::NodeType> {
+        let mut stride_end = 0;
+        let mut node = self.retrieve_node(self.get_root_node_id()).unwrap();
+
+        for stride in self.strides.iter() {
+            stride_end += stride;
+            let last_stride = search_pfx.get_len() <= stride_end;
+
+            let nibble_len = if search_pfx.get_len() < stride_end {
+                stride + search_pfx.get_len() - stride_end
+            } else {
+                *stride
+            };
+
+            let nibble = AddressFamily::get_nibble(
+                search_pfx.get_net(),
+                stride_end - stride,
+                nibble_len,
+            );
+
+            let next_node = match node {
+                SizedStrideNode::Stride3(n) => {
+                    if last_stride {
+                        return self
+                            .get_all_more_specifics_from_nibble(
+                                n, nibble, nibble_len,
+                            )
+                            .unwrap_or_default();
                     }
+                    n.search_stride_for_exact_match_at(
+                        search_pfx,
+                        nibble,
+                        nibble_len,
+                        stride_end - stride,
+                    )
+                    .0
                 }
-                SizedStrideNode::Stride7(current_node) => {
-                    let search_fn = match options.match_type {
-                        MatchType::ExactMatch => {
-                            if options.include_less_specifics {
-                                TreeBitMapNode::search_stride_for_exact_match_with_less_specifics_at
-                            } else {
-                                TreeBitMapNode::search_stride_for_exact_match_at
-                            }
-                        }
-                        MatchType::LongestMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                        MatchType::EmptyMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                    };
-                    match search_fn(
-                        current_node,
+                SizedStrideNode::Stride4(n) => {
+                    if last_stride {
+                        return self
+                            .get_all_more_specifics_from_nibble(
+                                n, nibble, nibble_len,
+                            )
+                            .unwrap_or_default();
+                    }
+                    n.search_stride_for_exact_match_at(
                         search_pfx,
                         nibble,
                         nibble_len,
                         stride_end - stride,
-                        &mut less_specifics_vec,
-                    ) {
-                        (Some(n), Some(pfx_idx)) => {
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (Some(n), None) => {
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (None, Some(pfx_idx)) => {
-                            if options.include_more_specifics {
-                                more_specifics_vec = self
-                                    .get_all_more_specifics_from_nibble(
-                                        current_node,
-                                        nibble,
-                                        nibble_len,
-                                    );
-                            }
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            break;
-                        }
-                        (None, None) => {
-                            match options.match_type {
-                                MatchType::EmptyMatch => {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-
-                                    match_prefix_idx = None;
-                                    break;
-                                }
-                                MatchType::LongestMatch => {}
-                                MatchType::ExactMatch => {
-                                    match_prefix_idx = None;
-                                }
-                            }
-                            break;
-                        }
+                    )
+                    .0
+                }
+                SizedStrideNode::Stride5(n) => {
+                    if last_stride {
+                        return self
+                            .get_all_more_specifics_from_nibble(
+                                n, nibble, nibble_len,
+                            )
+                            .unwrap_or_default();
                     }
+                    n.search_stride_for_exact_match_at(
+                        search_pfx,
+                        nibble,
+                        nibble_len,
+                        stride_end - stride,
+                    )
+                    .0
                 }
-                SizedStrideNode::Stride8(current_node) => {
-                    let search_fn = match options.match_type {
-                        MatchType::ExactMatch => {
-                            if options.include_less_specifics {
-                                TreeBitMapNode::search_stride_for_exact_match_with_less_specifics_at
-                            } else {
-                                TreeBitMapNode::search_stride_for_exact_match_at
-                            }
-                        }
-                        MatchType::LongestMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                        MatchType::EmptyMatch => {
-                            TreeBitMapNode::search_stride_for_longest_match_at
-                        }
-                    };
-                    match search_fn(
-                        current_node,
+                SizedStrideNode::Stride6(n) => {
+                    if last_stride {
+                        return self
+                            .get_all_more_specifics_from_nibble(
+                                n, nibble, nibble_len,
+                            )
+                            .unwrap_or_default();
+                    }
+                    n.search_stride_for_exact_match_at(
                         search_pfx,
                         nibble,
                         nibble_len,
                         stride_end - stride,
-                        &mut less_specifics_vec,
-                    ) {
-                        (Some(n), Some(pfx_idx)) => {
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (Some(n), None) => {
-                            node = self.retrieve_node(n).unwrap();
-                            if last_stride {
-                                if options.include_more_specifics {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-                                }
-                                break;
-                            }
-                        }
-                        (None, Some(pfx_idx)) => {
-                            if options.include_more_specifics {
-                                more_specifics_vec = self
-                                    .get_all_more_specifics_from_nibble(
-                                        current_node,
-                                        nibble,
-                                        nibble_len,
-                                    );
-                            }
-                            match_prefix_idx = Some(pfx_idx.get_part());
-                            break;
-                        }
-                        (None, None) => {
-                            match options.match_type {
-                                MatchType::EmptyMatch => {
-                                    more_specifics_vec = self
-                                        .get_all_more_specifics_from_nibble(
-                                            current_node,
-                                            nibble,
-                                            nibble_len,
-                                        );
-
-                                    match_prefix_idx = None;
-                                    break;
-                                }
-                                MatchType::LongestMatch => {}
-                                MatchType::ExactMatch => {
-                                    match_prefix_idx = None;
-                                }
-                            }
-                            break;
-                        }
+                    )
+                    .0
+                }
+                SizedStrideNode::Stride7(n) => {
+                    if last_stride {
+                        return self
+                            .get_all_more_specifics_from_nibble(
+                                n, nibble, nibble_len,
+                            )
+                            .unwrap_or_default();
+                    }
+                    n.search_stride_for_exact_match_at(
+                        search_pfx,
+                        nibble,
+                        nibble_len,
+                        stride_end - stride,
+                    )
+                    .0
+                }
+                SizedStrideNode::Stride8(n) => {
+                    if last_stride {
+                        return self
+                            .get_all_more_specifics_from_nibble(
+                                n, nibble, nibble_len,
+                            )
+                            .unwrap_or_default();
                     }
+                    n.search_stride_for_exact_match_at(
+                        search_pfx,
+                        nibble,
+                        nibble_len,
+                        stride_end - stride,
+                    )
+                    .0
+                }
+            };
+
+            match next_node {
+                Some(n) => {
+                    node = self.retrieve_node(n).unwrap();
+                }
+                // No child node, so no more-specifics either.
+                None => {
+                    return vec![];
                 }
             }
         }
 
-        //------------------ post-processing --------------------------------
+        vec![]
+    }
 
-        // If the above loop finishes (so not hitting a break) we have processed all strides and have found a child node and maybe a prefix.
-        // Now we will look up more-specifics for longest-matching prefixes that were found in the last stride only,
-        // Note that still any of the match_types (as specified by the user, not the return type) may end up here.
+    // Iterator over all more-specifics of the given prefix.
+    pub(crate) fn more_specifics_iter_from(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+    ) -> impl Iterator<Item = &'a InternalPrefixRecord<Store::AF, Store::Meta>>
+    {
+        self.more_specifics_vec_from(search_pfx)
+            .into_iter()
+            .filter_map(move |p| self.retrieve_prefix(p.get_part()))
+    }
 
-        let mut match_type: MatchType = MatchType::EmptyMatch;
-        let mut prefix = None;
-        if let Some(pfx_idx) = match_prefix_idx {
-            prefix = self.retrieve_prefix(pfx_idx);
-            match_type = if prefix.unwrap().len == search_pfx.get_len() {
-                MatchType::ExactMatch
-            } else {
-                MatchType::LongestMatch
-            }
-        };
+    pub(crate) fn more_specifics_records_from(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+    ) -> QueryRecords<'a, Store::AF, Store::Meta> {
+        QueryRecords {
+            prefix: self
+                .covering_prefixes(search_pfx)
+                .0
+                .and_then(|p| self.retrieve_prefix(p.get_part())),
+            match_type: MatchType::EmptyMatch,
+            less_specifics: None,
+            more_specifics: Some(
+                self.more_specifics_iter_from(search_pfx).collect(),
+            ),
+        }
+    }
 
-        QueryResult {
-            prefix: if let Some(pfx) = prefix {
-                Prefix::new(pfx.net.into_ipaddr(), pfx.len).ok()
-            } else {
-                None
-            },
-            prefix_meta: if let Some(pfx) = prefix {
-                Some(&pfx.meta)
-            } else {
-                None
-            },
+    pub(crate) fn less_specifics_records_from(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+    ) -> QueryRecords<'a, Store::AF, Store::Meta> {
+        let (exact_match, less_specifics_vec) =
+            self.covering_prefixes(search_pfx);
+
+        QueryRecords {
+            prefix: exact_match
+                .and_then(|p| self.retrieve_prefix(p.get_part())),
+            match_type: MatchType::EmptyMatch,
+            less_specifics: Some(
+                less_specifics_vec
+                    .iter()
+                    .filter_map(|p| self.retrieve_prefix(p.get_part()))
+                    .collect(),
+            ),
+            more_specifics: None,
+        }
+    }
+
+    pub(crate) fn match_prefix_records(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+        options: &MatchOptions,
+    ) -> QueryRecords<'a, Store::AF, Store::Meta> {
+        let (exact_match, mut less_specifics_vec) =
+            self.covering_prefixes(search_pfx);
+
+        // Check if we have an actual exact match, if not then take the
+        // less-specific with the greatest length, that's the longest
+        // matching prefix, but only if the user requested a longest match
+        // or empty match.
+        let mut include_more_specifics = false;
+        let mut include_less_specifics = false;
+        let (prefix_idx, match_type) =
+            match (&options.match_type, exact_match) {
+                // we found an exact match, we don't need to do anything.
+                (_, Some(pfx_idx)) => {
+                    include_more_specifics = options.include_more_specifics;
+                    include_less_specifics = options.include_less_specifics;
+                    (Some(pfx_idx), MatchType::ExactMatch)
+                }
+                // we didn't find an exact match, but the user requested it
+                // so we need to find the longest matching prefix. The
+                // less-specifics are ordered by length, so that's the last
+                // one, and the remaining ones are its less-specifics.
+                (MatchType::LongestMatch | MatchType::EmptyMatch, None) => {
+                    include_more_specifics = options.include_more_specifics;
+                    include_less_specifics = options.include_less_specifics;
+                    match less_specifics_vec.pop() {
+                        Some(pfx_idx) => {
+                            (Some(pfx_idx), MatchType::LongestMatch)
+                        }
+                        None => (None, MatchType::EmptyMatch),
+                    }
+                }
+                // We got an empty match, but the user requested an exact
+                // match
+                (MatchType::ExactMatch, None) => {
+                    (None, MatchType::EmptyMatch)
+                }
+            };
+
+        let prefix =
+            prefix_idx.and_then(|p| self.retrieve_prefix(p.get_part()));
+
+        QueryRecords {
+            prefix,
             match_type,
-            less_specifics: if options.include_less_specifics {
-                less_specifics_vec.map(|vec| {
-                    vec.iter()
-                        .map(|p| self.retrieve_prefix(p.get_part()).unwrap())
-                        .collect::<RecordSet<'a, Store::Meta>>()
-                })
+            less_specifics: if include_less_specifics {
+                Some(
+                    less_specifics_vec
+                        .iter()
+                        .filter_map(|p| self.retrieve_prefix(p.get_part()))
+                        .collect(),
+                )
+            } else if options.include_less_specifics {
+                Some(vec![])
             } else {
                 None
             },
-            more_specifics: if options.include_more_specifics {
-                more_specifics_vec.map(|vec| {
-                    vec.iter()
-                        .map(|p| self.retrieve_prefix(p.get_part()).unwrap())
-                        .collect()
-                })
+            more_specifics: if include_more_specifics {
+                Some(
+                    self.more_specifics_iter_from(match prefix {
+                        Some(pfx) => PrefixId::new(pfx.net, pfx.len),
+                        None => search_pfx,
+                    })
+                    .collect(),
+                )
+                // The user requested more specifics, but there aren't any,
+                // so we need to return an empty vec, not a None.
+            } else if options.include_more_specifics {
+                Some(vec![])
             } else {
                 None
             },
         }
     }
 }
+
+// The queries that return `Prefix`es are only available for the IP address
+// families.
+impl<'a, Store> TreeBitMap<Store>
+where
+    Store: StorageBackend,
+    Store::AF: IpAddressFamily,
+{
+    pub(crate) fn more_specifics_from(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+    ) -> QueryResult<'a, Store::Meta> {
+        self.more_specifics_records_from(search_pfx).into_query_result()
+    }
+
+    pub(crate) fn less_specifics_from(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+    ) -> QueryResult<'a, Store::Meta> {
+        self.less_specifics_records_from(search_pfx).into_query_result()
+    }
+
+    pub(crate) fn match_prefix(
+        &'a self,
+        search_pfx: PrefixId<Store::AF>,
+        options: &MatchOptions,
+    ) -> QueryResult<'a, Store::Meta> {
+        self.match_prefix_records(search_pfx, options).into_query_result()
+    }
+}
+
+//------------ QueryRecords -------------------------------------------------
+
+// The records found by a query on the tree, before they're turned into the
+// result type of the store that does the query.
+pub(crate) struct QueryRecords<'a, AF: AddressFamily, M: Meta> {
+    pub match_type: MatchType,
+    pub prefix: Option<&'a InternalPrefixRecord<AF, M>>,
+    pub less_specifics: Option<Vec<&'a InternalPrefixRecord<AF, M>>>,
+    pub more_specifics: Option<Vec<&'a InternalPrefixRecord<AF, M>>>,
+}
+
+impl<'a, AF: AddressFamily, M: Meta> QueryRecords<'a, AF, M> {
+    pub(crate) fn into_bit_query_result(self) -> BitQueryResult<'a, AF, M> {
+        let into_records = |recs: Vec<&'a InternalPrefixRecord<AF, M>>| {
+            recs.into_iter()
+                .map(BitPrefixRecord::<AF, M>::from)
+                .collect::<Vec<_>>()
+        };
+
+        BitQueryResult {
+            match_type: self.match_type,
+            prefix: self.prefix.map(BitPrefix::from_record),
+            prefix_meta: self.prefix.map(|p| &p.meta),
+            less_specifics: self.less_specifics.map(into_records),
+            more_specifics: self.more_specifics.map(into_records),
+        }
+    }
+}
+
+impl<'a, AF: IpAddressFamily, M: Meta> QueryRecords<'a, AF, M> {
+    pub(crate) fn into_query_result(self) -> QueryResult<'a, M> {
+        QueryResult {
+            match_type: self.match_type,
+            prefix: self.prefix.map(|p| p.prefix_into_pub()),
+            prefix_meta: self.prefix.map(|p| &p.meta),
+            less_specifics: self
+                .less_specifics
+                .map(|recs| recs.into_iter().collect()),
+            more_specifics: self
+                .more_specifics
+                .map(|recs| recs.into_iter().collect()),
+        }
+    }
+}
@@ -1,21 +1,33 @@
-use crate::node_id::{InMemNodeId, SortableNodeId};
-use crate::prefix_record::InternalPrefixRecord;
+pub use crate::local_vec::node::TreeBitMapNode;
+pub use crate::local_vec::tree::{SizedNodeResult, SizedStrideNode};
+pub use crate::node_id::{InMemNodeId, SortableNodeId};
+pub use crate::prefix_record::InternalPrefixRecord;
 pub use crate::stride::*;
 
-use crate::local_vec::tree::*;
-
 use crate::af::AddressFamily;
 use routecore::record::MergeUpdate;
 
 use std::fmt::Debug;
 use std::io::{Error, ErrorKind};
 
-pub(crate) type PrefixIter<'a, AF, Meta> = Result<
-    std::slice::Iter<'a, InternalPrefixRecord<AF, Meta>>,
-    Box<dyn std::error::Error>,
->;
+//------------ StorageBackend -----------------------------------------------
+
+/// An iterator over the prefixes in a [`StorageBackend`].
+pub type PrefixIter<'a, AF, Meta> =
+    Box<dyn Iterator<Item = &'a InternalPrefixRecord<AF, Meta>> + 'a>;
 
-pub(crate) trait StorageBackend
+/// The storage for the nodes and the prefixes of a single tree.
+///
+/// The tree itself only ever refers to nodes and prefixes by their ids, it
+/// leaves it up to the backend where and how they are kept. Implement this
+/// trait to keep them somewhere else than in the [`InMemStorage`], e.g. in
+/// an arena, or in a memory-mapped file, and use the backend with the
+/// [`crate::BitPrefixStore`].
+///
+/// The `Part` of a [`SortableNodeId`] is the index of the node or the
+/// prefix in the backend, the `Sort` is used by the tree to keep the ids
+/// in its nodes ordered, and must be handed back as is.
+pub trait StorageBackend
 where
     Self::NodeType: SortableNodeId + Copy,
 {
@@ -23,9 +35,12 @@ where
     type AF: AddressFamily;
     type Meta: routecore::record::Meta + MergeUpdate;
 
+    /// Creates the backend, holding the root node if it is given.
     fn init(
         start_node: Option<SizedStrideNode<Self::AF, Self::NodeType>>,
     ) -> Self;
+    /// Returns the id that the next call to [`Self::store_node`] will store
+    /// its node under.
     fn acquire_new_node_id(
         &self,
         sort: <<Self as StorageBackend>::NodeType as SortableNodeId>::Sort,
@@ -49,16 +64,13 @@ where
         &mut self,
         index: Self::NodeType,
     ) -> SizedNodeResult<Self::AF, Self::NodeType>;
-    fn retrieve_node_with_guard(
-        &self,
-        index: Self::NodeType,
-    ) -> CacheGuard<Self::AF, Self::NodeType>;
     fn get_root_node_id(&self) -> Self::NodeType;
     fn get_root_node_mut(
         &mut self,
     ) -> Option<&mut SizedStrideNode<Self::AF, Self::NodeType>>;
-    fn get_nodes(&self) -> &Vec<SizedStrideNode<Self::AF, Self::NodeType>>;
     fn get_nodes_len(&self) -> usize;
+    /// Returns the id that the next call to [`Self::store_prefix`] will
+    /// store its prefix under.
     fn acquire_new_prefix_id(
         &self,
         sort: &<<Self as StorageBackend>::NodeType as SortableNodeId>::Sort,
@@ -79,24 +91,42 @@ where
         &mut self,
         index: <<Self as StorageBackend>::NodeType as SortableNodeId>::Part,
     ) -> Option<&mut InternalPrefixRecord<Self::AF, Self::Meta>>;
-    fn retrieve_prefix_with_guard(
-        &self,
-        index: Self::NodeType,
-    ) -> PrefixCacheGuard<Self::AF, Self::Meta>;
-    fn get_prefixes(
-        &self,
-    ) -> &Vec<InternalPrefixRecord<Self::AF, Self::Meta>>;
+    /// Removes the prefix and returns its meta-data, or `None` if there is
+    /// no prefix with this index. The tree has already removed the prefix
+    /// from the node that referred to it.
+    fn remove_prefix(
+        &mut self,
+        index: <<Self as StorageBackend>::NodeType as SortableNodeId>::Part,
+    ) -> Option<Self::Meta>;
+    /// Reclaims the space taken up by removed prefixes, and drops the
+    /// nodes that have neither prefixes nor child nodes anymore, see
+    /// [`SizedStrideNode::reindex`]. The root node is never dropped.
+    ///
+    /// Returns the stride size and the depth level of the parent of every
+    /// node that was dropped, so the tree can take them off its stats.
+    fn compact(&mut self) -> Vec<(u8, u8)>;
+    /// The number of prefixes in the backend, not counting removed ones.
     fn get_prefixes_len(&self) -> usize;
+    /// Iterates over all the prefixes in the backend, skipping the
+    /// removed ones.
     fn prefixes_iter(&self) -> PrefixIter<'_, Self::AF, Self::Meta>;
 }
 
+//------------ InMemStorage -------------------------------------------------
+
+/// A [`StorageBackend`] that keeps the nodes and the prefixes in vecs.
+///
+/// Removed prefixes stay behind as tombstones until the backend gets
+/// compacted, which rewrites both vecs.
 #[derive(Debug)]
-pub(crate) struct InMemStorage<
-    AF: AddressFamily,
-    Meta: routecore::record::Meta,
-> {
-    pub nodes: Vec<SizedStrideNode<AF, InMemNodeId>>,
-    pub prefixes: Vec<InternalPrefixRecord<AF, Meta>>,
+pub struct InMemStorage<AF: AddressFamily, Meta: routecore::record::Meta> {
+    pub(crate) nodes: Vec<SizedStrideNode<AF, InMemNodeId>>,
+    pub(crate) prefixes: Vec<InternalPrefixRecord<AF, Meta>>,
+    // Tombstones for the prefixes, a removed prefix keeps its spot in
+    // `prefixes` (so that the indexes into it stay valid), until the
+    // storage gets compacted.
+    pub(crate) removed: Vec<bool>,
+    pub(crate) removed_len: usize,
 }
 
 impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
@@ -116,6 +146,8 @@ impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
         InMemStorage {
             nodes,
             prefixes: vec![],
+            removed: vec![],
+            removed_len: 0,
         }
     }
 
@@ -171,15 +203,6 @@ impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
             })
     }
 
-    // Don't use this function, this is just a placeholder and a really
-    // inefficient implementation.
-    fn retrieve_node_with_guard(
-        &self,
-        _id: Self::NodeType,
-    ) -> CacheGuard<Self::AF, Self::NodeType> {
-        panic!("Not Implemented for InMeMStorage");
-    }
-
     fn get_root_node_id(&self) -> Self::NodeType {
         InMemNodeId(0, 0)
     }
@@ -190,10 +213,6 @@ impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
         Some(&mut self.nodes[0])
     }
 
-    fn get_nodes(&self) -> &Vec<SizedStrideNode<Self::AF, Self::NodeType>> {
-        &self.nodes
-    }
-
     fn get_nodes_len(&self) -> usize {
         self.nodes.len()
     }
@@ -217,6 +236,7 @@ impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
     ) -> Result<u32, Box<dyn std::error::Error>> {
         let id = self.prefixes.len() as u32;
         self.prefixes.push(next_node);
+        self.removed.push(false);
         Ok(id)
     }
 
@@ -234,26 +254,396 @@ impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
         self.prefixes.get_mut(index as usize)
     }
 
-    fn retrieve_prefix_with_guard(
+    // Marks the prefix as removed. The caller is responsible for removing
+    // the prefix from the node that refers to it.
+    fn remove_prefix(&mut self, index: u32) -> Option<Self::Meta> {
+        let removed = self.removed.get_mut(index as usize)?;
+        if *removed {
+            return None;
+        }
+        *removed = true;
+        self.removed_len += 1;
+        self.prefixes.get(index as usize).map(|pfx| pfx.meta.clone())
+    }
+
+    // Rewrites the `prefixes` vec without the removed prefixes and the
+    // `nodes` vec without the nodes that have neither prefixes nor child
+    // nodes anymore, and re-indexes all the nodes accordingly.
+    fn compact(&mut self) -> Vec<(u8, u8)> {
+        // The new index for every prefix in the old `prefixes` vec, or None
+        // if it was removed.
+        let mut prefix_map = Vec::with_capacity(self.prefixes.len());
+        let mut prefixes =
+            Vec::with_capacity(self.prefixes.len() - self.removed_len);
+
+        for (pfx, removed) in std::mem::take(&mut self.prefixes)
+            .into_iter()
+            .zip(self.removed.iter())
+        {
+            if *removed {
+                prefix_map.push(None);
+            } else {
+                prefix_map.push(Some(prefixes.len() as u32));
+                prefixes.push(pfx);
+            }
+        }
+
+        self.removed = vec![false; prefixes.len()];
+        self.removed_len = 0;
+        self.prefixes = prefixes;
+
+        // The nodes are moved over depth-first, starting at the root node,
+        // so the root node ends up at index 0 again.
+        let mut old_nodes = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let mut nodes = Vec::with_capacity(old_nodes.len());
+        let mut pruned = vec![];
+
+        compact_node(
+            &mut old_nodes,
+            &mut nodes,
+            &prefix_map,
+            self.get_root_node_id(),
+            None,
+            &mut pruned,
+        );
+
+        self.nodes = nodes;
+        pruned
+    }
+
+    fn get_prefixes_len(&self) -> usize {
+        self.prefixes.len() - self.removed_len
+    }
+
+    fn prefixes_iter(&self) -> PrefixIter<'_, AF, Meta> {
+        Box::new(
+            self.prefixes
+                .iter()
+                .zip(self.removed.iter())
+                .filter(|(_, removed)| !**removed)
+                .map(|(pfx, _)| pfx),
+        )
+    }
+}
+
+// Moves the node with `id` from `old_nodes` into `new_nodes`, after moving
+// all its child nodes recursively, and re-indexes its child nodes and
+// prefixes. Returns the new id of the node, or None if the node has become
+// empty, in which case it's dropped and the stride size and the depth
+// level of its parent go into `pruned`. The root node, the one without a
+// `parent`, is never dropped.
+fn compact_node<AF: AddressFamily>(
+    old_nodes: &mut [Option<SizedStrideNode<AF, InMemNodeId>>],
+    new_nodes: &mut Vec<SizedStrideNode<AF, InMemNodeId>>,
+    prefix_map: &[Option<u32>],
+    id: InMemNodeId,
+    parent: Option<(u8, u8)>,
+    pruned: &mut Vec<(u8, u8)>,
+) -> Option<InMemNodeId> {
+    let mut node = old_nodes[id.get_part() as usize].take()?;
+    let level = parent.map_or(0, |(_, level)| level + 1);
+    let this = Some((node.stride_len(), level));
+
+    // Claim the spot for this node before its children claim theirs.
+    let index = new_nodes.len();
+    new_nodes.push(SizedStrideNode::default());
+
+    let is_empty = node.reindex(
+        |child| {
+            compact_node(
+                old_nodes, new_nodes, prefix_map, child, this, pruned,
+            )
+        },
+        |pfx| {
+            InMemNodeId(
+                pfx.get_sort(),
+                prefix_map[pfx.get_part() as usize]
+                    .expect("removed prefix still referenced by a node"),
+            )
+        },
+    );
+
+    if let (true, Some(parent)) = (is_empty, parent) {
+        // All the children of this node were dropped as well, so the spot
+        // of this node is still the last one.
+        new_nodes.pop();
+        pruned.push(parent);
+        return None;
+    }
+
+    new_nodes[index] = node;
+    Some(InMemNodeId(id.get_sort(), index as u32))
+}
+
+//------------ SlabStorage --------------------------------------------------
+
+/// A [`StorageBackend`] that keeps the nodes and the prefixes in slabs,
+/// vecs of slots that get reused once they are freed.
+///
+/// Unlike with the [`InMemStorage`], removing a prefix frees its slot right
+/// away, and the next prefix that gets stored takes it over, so a tree
+/// with a lot of churn doesn't keep on growing until it gets compacted.
+/// Compacting only has to free the nodes that have become empty, all the
+/// other nodes and prefixes stay where they are.
+#[derive(Debug)]
+pub struct SlabStorage<AF: AddressFamily, Meta: routecore::record::Meta> {
+    nodes: Slab<SizedStrideNode<AF, InMemNodeId>>,
+    prefixes: Slab<InternalPrefixRecord<AF, Meta>>,
+}
+
+impl<AF: AddressFamily, Meta: routecore::record::Meta + MergeUpdate>
+    StorageBackend for SlabStorage<AF, Meta>
+{
+    type NodeType = InMemNodeId;
+    type AF = AF;
+    type Meta = Meta;
+
+    fn init(
+        start_node: Option<SizedStrideNode<Self::AF, Self::NodeType>>,
+    ) -> SlabStorage<AF, Meta> {
+        let mut nodes = Slab::new();
+        if let Some(n) = start_node {
+            nodes.insert(n);
+        }
+        SlabStorage {
+            nodes,
+            prefixes: Slab::new(),
+        }
+    }
+
+    fn acquire_new_node_id(
         &self,
-        _index: Self::NodeType,
-    ) -> PrefixCacheGuard<Self::AF, Self::Meta> {
-        panic!("nOt ImPlEmEnTed for InMemNode");
+        sort: <<Self as StorageBackend>::NodeType as SortableNodeId>::Sort,
+        _part: <<Self as StorageBackend>::NodeType as SortableNodeId>::Part,
+    ) -> <Self as StorageBackend>::NodeType {
+        InMemNodeId(sort, self.nodes.next_index())
     }
 
-    fn get_prefixes(
+    fn store_node(
+        &mut self,
+        _id: Option<Self::NodeType>,
+        next_node: SizedStrideNode<Self::AF, Self::NodeType>,
+    ) -> Option<Self::NodeType> {
+        Some(InMemNodeId::new(&0, &self.nodes.insert(next_node)))
+    }
+
+    fn update_node(
+        &mut self,
+        current_node_id: Self::NodeType,
+        updated_node: SizedStrideNode<Self::AF, Self::NodeType>,
+    ) {
+        let _default_val = std::mem::replace(
+            self.retrieve_node_mut(current_node_id).unwrap(),
+            updated_node,
+        );
+    }
+
+    fn retrieve_node(
         &self,
-    ) -> &Vec<InternalPrefixRecord<Self::AF, Self::Meta>> {
-        &self.prefixes
+        id: Self::NodeType,
+    ) -> Option<&SizedStrideNode<Self::AF, Self::NodeType>> {
+        self.nodes.get(id.get_part())
+    }
+
+    fn retrieve_node_mut(
+        &mut self,
+        index: Self::NodeType,
+    ) -> SizedNodeResult<Self::AF, Self::NodeType> {
+        self.nodes.get_mut(index.get_part()).ok_or_else(|| {
+            Box::new(Error::new(ErrorKind::Other, "Retrieve Node Error"))
+                .into()
+        })
+    }
+
+    fn get_root_node_id(&self) -> Self::NodeType {
+        InMemNodeId(0, 0)
+    }
+
+    fn get_root_node_mut(
+        &mut self,
+    ) -> Option<&mut SizedStrideNode<Self::AF, Self::NodeType>> {
+        self.nodes.get_mut(0)
+    }
+
+    fn get_nodes_len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn acquire_new_prefix_id(
+        &self,
+        sort: &<<Self as StorageBackend>::NodeType as SortableNodeId>::Sort,
+        _part: &InternalPrefixRecord<
+            <Self as StorageBackend>::AF,
+            <Self as StorageBackend>::Meta,
+        >,
+    ) -> <Self as StorageBackend>::NodeType {
+        InMemNodeId(*sort, self.prefixes.next_index())
+    }
+
+    fn store_prefix(
+        &mut self,
+        next_node: InternalPrefixRecord<Self::AF, Self::Meta>,
+    ) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(self.prefixes.insert(next_node))
+    }
+
+    fn retrieve_prefix(
+        &self,
+        index: u32,
+    ) -> Option<&InternalPrefixRecord<Self::AF, Self::Meta>> {
+        self.prefixes.get(index)
+    }
+
+    fn retrieve_prefix_mut(
+        &mut self,
+        index: u32,
+    ) -> Option<&mut InternalPrefixRecord<Self::AF, Self::Meta>> {
+        self.prefixes.get_mut(index)
+    }
+
+    // Frees the slot of the prefix, the next stored prefix will take it.
+    fn remove_prefix(&mut self, index: u32) -> Option<Self::Meta> {
+        self.prefixes.remove(index).map(|pfx| pfx.meta)
+    }
+
+    // The slots of the removed prefixes have already been freed, so this
+    // only frees the nodes that have become empty.
+    fn compact(&mut self) -> Vec<(u8, u8)> {
+        let mut pruned = vec![];
+        prune_node(
+            &mut self.nodes,
+            self.get_root_node_id(),
+            None,
+            &mut pruned,
+        );
+        pruned
     }
 
     fn get_prefixes_len(&self) -> usize {
         self.prefixes.len()
     }
 
-    fn prefixes_iter(
-        &self,
-    ) -> PrefixIter<'_, AF, Meta> {
-        Ok(self.prefixes.iter())
+    fn prefixes_iter(&self) -> PrefixIter<'_, AF, Meta> {
+        Box::new(self.prefixes.iter())
+    }
+}
+
+// Frees the node with `id` if it has become empty, after doing the same
+// for all its child nodes recursively. Returns the id of the node, or None
+// if it was freed, in which case the stride size and the depth level of
+// its parent go into `pruned`. The root node, the one without a `parent`,
+// is never freed.
+fn prune_node<AF: AddressFamily>(
+    nodes: &mut Slab<SizedStrideNode<AF, InMemNodeId>>,
+    id: InMemNodeId,
+    parent: Option<(u8, u8)>,
+    pruned: &mut Vec<(u8, u8)>,
+) -> Option<InMemNodeId> {
+    let mut node = std::mem::take(nodes.get_mut(id.get_part())?);
+    let level = parent.map_or(0, |(_, level)| level + 1);
+    let this = Some((node.stride_len(), level));
+
+    let is_empty = node
+        .reindex(|child| prune_node(nodes, child, this, pruned), |pfx| pfx);
+
+    if let (true, Some(parent)) = (is_empty, parent) {
+        nodes.remove(id.get_part());
+        pruned.push(parent);
+        return None;
+    }
+
+    *nodes.get_mut(id.get_part())? = node;
+    Some(id)
+}
+
+//------------ Slab ---------------------------------------------------------
+
+#[derive(Debug)]
+enum Slot<T> {
+    Occupied(T),
+    // A free slot, with the index of the next free slot, if any.
+    Free(Option<u32>),
+}
+
+// A vec of slots, where the free slots form a linked list, so that the
+// most recently freed slot is the first one to be reused.
+#[derive(Debug)]
+struct Slab<T> {
+    slots: Vec<Slot<T>>,
+    next_free: Option<u32>,
+    len: usize,
+}
+
+impl<T> Slab<T> {
+    fn new() -> Self {
+        Slab {
+            slots: vec![],
+            next_free: None,
+            len: 0,
+        }
+    }
+
+    // The index the next inserted value will end up at.
+    fn next_index(&self) -> u32 {
+        self.next_free.unwrap_or(self.slots.len() as u32)
+    }
+
+    fn insert(&mut self, value: T) -> u32 {
+        let index = self.next_index();
+        match self.next_free {
+            Some(free) => {
+                let slot = std::mem::replace(
+                    &mut self.slots[free as usize],
+                    Slot::Occupied(value),
+                );
+                if let Slot::Free(next) = slot {
+                    self.next_free = next;
+                }
+            }
+            None => self.slots.push(Slot::Occupied(value)),
+        }
+        self.len += 1;
+        index
+    }
+
+    fn remove(&mut self, index: u32) -> Option<T> {
+        let slot = self.slots.get_mut(index as usize)?;
+        if let Slot::Free(_) = slot {
+            return None;
+        }
+        let next_free = self.next_free.replace(index);
+        self.len -= 1;
+        match std::mem::replace(slot, Slot::Free(next_free)) {
+            Slot::Occupied(value) => Some(value),
+            Slot::Free(_) => unreachable!(),
+        }
+    }
+
+    fn get(&self, index: u32) -> Option<&T> {
+        match self.slots.get(index as usize)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Free(_) => None,
+        }
+    }
+
+    fn get_mut(&mut self, index: u32) -> Option<&mut T> {
+        match self.slots.get_mut(index as usize)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Free(_) => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Occupied(value) => Some(value),
+            Slot::Free(_) => None,
+        })
     }
 }
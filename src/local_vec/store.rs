@@ -2,11 +2,17 @@ use crate::local_vec::storage_backend::{InMemStorage, StorageBackend};
 use crate::local_vec::TreeBitMap;
 use crate::node_id::InMemNodeId;
 use crate::prefix_record::InternalPrefixRecord;
-use super::query::QueryResult;
-use crate::{MatchOptions, Stats, Strides};
+use crate::{
+    AfIter, AfSelector, MatchOptions, PrefixStore, PrefixStoreMut,
+    QueryResult, Stats, Strides,
+};
 
-use crate::af::{IPv4, IPv6};
+use crate::af::{
+    IPv4, IPv6, IpAddressFamily, RouteDistinguisher, VpnIPv4, VpnIPv6,
+};
+use crossbeam_epoch::Guard;
 use routecore::addr::Prefix;
+use routecore::bgp::PrefixRecord;
 use routecore::record::MergeUpdate;
 
 use super::query::PrefixId;
@@ -16,19 +22,50 @@ use super::tree::SizedStrideNode;
 /// Can be used in multi-threaded contexts by wrapping it in a `Arc<Mutex<_>>`.
 /// Be aware that this is undesirable in cases with high contention.
 /// Use cases with high contention are best served by the [`crate::MultiThreadedStore`].
+///
+/// The VPN-IPv4 and VPN-IPv6 prefixes go in with [`Store::insert_vpn`] and
+/// friends. In multi-threaded contexts, they go into a separate
+/// [`crate::MultiThreadedVpnStore`].
 pub struct Store<Meta: routecore::record::Meta>
 where
     Meta: MergeUpdate,
 {
     v4: TreeBitMap<InMemStorage<IPv4, Meta>>,
     v6: TreeBitMap<InMemStorage<IPv6, Meta>>,
+    vpn_v4: TreeBitMap<InMemStorage<VpnIPv4, Meta>>,
+    vpn_v6: TreeBitMap<InMemStorage<VpnIPv6, Meta>>,
 }
 
 impl<Meta: routecore::record::Meta + MergeUpdate> Store<Meta> {
+    /// Creates a new store with the given stride sizes for the IPv4 and
+    /// IPv6 trees. The trees for the VPN-IPv4 and VPN-IPv6 prefixes get
+    /// strides of 4 bits.
+    ///
+    /// The stride sizes are repeated until they cover the 32 bits of an
+    /// IPv4 address, or the 128 bits of an IPv6 address. This panics if
+    /// they don't add up to exactly that.
     pub fn new(v4_strides: Vec<u8>, v6_strides: Vec<u8>) -> Self {
+        Self::new_with_vpn_strides(v4_strides, v6_strides, vec![4], vec![4])
+    }
+
+    /// Creates a new store with the given stride sizes for the IPv4, IPv6,
+    /// VPN-IPv4 and VPN-IPv6 trees.
+    ///
+    /// The stride sizes for the VPN trees are repeated until they cover
+    /// the route distinguisher and the address, so 96 bits for VPN-IPv4,
+    /// and 192 bits for VPN-IPv6. This panics if the stride sizes of any
+    /// of the trees don't add up to exactly the length of its keys.
+    pub fn new_with_vpn_strides(
+        v4_strides: Vec<u8>,
+        v6_strides: Vec<u8>,
+        vpn_v4_strides: Vec<u8>,
+        vpn_v6_strides: Vec<u8>,
+    ) -> Self {
         Store {
             v4: TreeBitMap::new(v4_strides),
             v6: TreeBitMap::new(v6_strides),
+            vpn_v4: TreeBitMap::new(vpn_v4_strides),
+            vpn_v6: TreeBitMap::new(vpn_v6_strides),
         }
     }
 }
@@ -51,6 +88,34 @@ impl<'a, Meta: routecore::record::Meta + MergeUpdate> Store<Meta> {
         }
     }
 
+    pub fn more_specifics_from(
+        &'a self,
+        search_pfx: &Prefix,
+    ) -> QueryResult<'a, Meta> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.more_specifics_from(
+                PrefixId::<IPv4>::new(addr.into(), search_pfx.len()),
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.more_specifics_from(
+                PrefixId::<IPv6>::new(addr.into(), search_pfx.len()),
+            ),
+        }
+    }
+
+    pub fn less_specifics_from(
+        &'a self,
+        search_pfx: &Prefix,
+    ) -> QueryResult<'a, Meta> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => self.v4.less_specifics_from(
+                PrefixId::<IPv4>::new(addr.into(), search_pfx.len()),
+            ),
+            std::net::IpAddr::V6(addr) => self.v6.less_specifics_from(
+                PrefixId::<IPv6>::new(addr.into(), search_pfx.len()),
+            ),
+        }
+    }
+
     pub fn insert(
         &mut self,
         prefix: &Prefix,
@@ -74,6 +139,41 @@ impl<'a, Meta: routecore::record::Meta + MergeUpdate> Store<Meta> {
         }
     }
 
+    /// Removes the prefix from the store and returns its meta-data, or
+    /// `None` if the prefix wasn't in the store.
+    ///
+    /// The space taken by the prefix (and by the nodes that have become
+    /// empty because of it) is only reclaimed by [`Store::compact`].
+    pub fn remove(&mut self, prefix: &Prefix) -> Option<Meta> {
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => self
+                .v4
+                .remove(PrefixId::<IPv4>::new(addr.into(), prefix.len())),
+            std::net::IpAddr::V6(addr) => self
+                .v6
+                .remove(PrefixId::<IPv6>::new(addr.into(), prefix.len())),
+        }
+    }
+
+    /// Reclaims the space of all the removed prefixes, and of the nodes
+    /// that have become empty, by rewriting the backing vecs of the store.
+    pub fn compact(&mut self) {
+        self.v4.compact();
+        self.v6.compact();
+        self.vpn_v4.compact();
+        self.vpn_v6.compact();
+    }
+
+    /// The number of removed prefixes that are still taking up space in
+    /// the store, i.e. the prefixes that will be cleaned up by the next
+    /// [`Store::compact`].
+    pub fn removed_prefixes_len(&self) -> usize {
+        self.v4.store.removed_len
+            + self.v6.store.removed_len
+            + self.vpn_v4.store.removed_len
+            + self.vpn_v6.store.removed_len
+    }
+
     pub fn prefixes_iter(&'a self) -> crate::PrefixRecordIter<'a, Meta> {
         let rs4: std::slice::Iter<InternalPrefixRecord<IPv4, Meta>> =
             self.v4.store.prefixes[..].iter();
@@ -82,9 +182,127 @@ impl<'a, Meta: routecore::record::Meta + MergeUpdate> Store<Meta> {
         crate::PrefixRecordIter::<'a, Meta> {
             v4: Some(rs4),
             v6: rs6,
+            v4_removed: self.v4.store.removed[..].iter(),
+            v6_removed: self.v6.store.removed[..].iter(),
+        }
+    }
+
+    pub fn prefixes_iter_af(
+        &'a self,
+        af: AfSelector,
+    ) -> impl Iterator<Item = PrefixRecord<'a, Meta>> + 'a {
+        let v4 = || crate::PrefixRecordIter::<'a, Meta> {
+            v4: Some(self.v4.store.prefixes[..].iter()),
+            v6: [].iter(),
+            v4_removed: self.v4.store.removed[..].iter(),
+            v6_removed: [].iter(),
+        };
+        let v6 = || crate::PrefixRecordIter::<'a, Meta> {
+            v4: None,
+            v6: self.v6.store.prefixes[..].iter(),
+            v4_removed: [].iter(),
+            v6_removed: self.v6.store.removed[..].iter(),
+        };
+        AfIter::new(af, v4, v6)
+    }
+
+    /// Inserts a VPN prefix, i.e. a prefix in the VPN identified by `rd`,
+    /// with its meta-data into the store.
+    pub fn insert_vpn(
+        &mut self,
+        rd: RouteDistinguisher,
+        prefix: &Prefix,
+        meta: Meta,
+    ) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => {
+                self.vpn_v4.insert(InternalPrefixRecord::new_with_meta(
+                    VpnIPv4::new(rd, addr),
+                    prefix.len() + VpnIPv4::IP_OFFSET,
+                    meta,
+                ))
+            }
+            std::net::IpAddr::V6(addr) => {
+                self.vpn_v6.insert(InternalPrefixRecord::new_with_meta(
+                    VpnIPv6::new(rd, addr),
+                    prefix.len() + VpnIPv6::IP_OFFSET,
+                    meta,
+                ))
+            }
+        }
+    }
+
+    /// Searches for the VPN prefix `search_pfx` in the VPN identified by
+    /// `rd`. Only prefixes within that same VPN can turn up as the result,
+    /// or as its less- or more-specifics, so the prefixes in the result
+    /// carry no RD.
+    pub fn match_vpn_prefix(
+        &'a self,
+        rd: RouteDistinguisher,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+    ) -> QueryResult<'a, Meta> {
+        match search_pfx.addr() {
+            std::net::IpAddr::V4(addr) => self.vpn_v4.match_prefix(
+                PrefixId::new(
+                    VpnIPv4::new(rd, addr),
+                    search_pfx.len() + VpnIPv4::IP_OFFSET,
+                ),
+                options,
+            ),
+            std::net::IpAddr::V6(addr) => self.vpn_v6.match_prefix(
+                PrefixId::new(
+                    VpnIPv6::new(rd, addr),
+                    search_pfx.len() + VpnIPv6::IP_OFFSET,
+                ),
+                options,
+            ),
         }
     }
 
+    /// Removes the VPN prefix from the store and returns its meta-data, or
+    /// `None` if the prefix wasn't in the store.
+    pub fn remove_vpn(
+        &mut self,
+        rd: RouteDistinguisher,
+        prefix: &Prefix,
+    ) -> Option<Meta> {
+        match prefix.addr() {
+            std::net::IpAddr::V4(addr) => self.vpn_v4.remove(PrefixId::new(
+                VpnIPv4::new(rd, addr),
+                prefix.len() + VpnIPv4::IP_OFFSET,
+            )),
+            std::net::IpAddr::V6(addr) => self.vpn_v6.remove(PrefixId::new(
+                VpnIPv6::new(rd, addr),
+                prefix.len() + VpnIPv6::IP_OFFSET,
+            )),
+        }
+    }
+
+    /// Iterates over all the VPN prefixes in the store, together with the
+    /// RD of the VPN they're in.
+    pub fn vpn_prefixes_iter(
+        &'a self,
+    ) -> impl Iterator<Item = (RouteDistinguisher, PrefixRecord<'a, Meta>)>
+    {
+        let v4 = self
+            .vpn_v4
+            .store
+            .prefixes_iter()
+            .map(|pfx| (pfx.net.rd(), PrefixRecord::from(pfx)));
+        let v6 = self
+            .vpn_v6
+            .store
+            .prefixes_iter()
+            .map(|pfx| (pfx.net.rd(), PrefixRecord::from(pfx)));
+        v4.chain(v6)
+    }
+
+    pub fn vpn_prefixes_len(&self) -> usize {
+        self.vpn_v4.store.get_prefixes_len()
+            + self.vpn_v6.store.get_prefixes_len()
+    }
+
     pub fn nodes_v4_iter(
         &'a self,
     ) -> impl Iterator<Item = &'a SizedStrideNode<IPv4, InMemNodeId>> + 'a
@@ -100,15 +318,15 @@ impl<'a, Meta: routecore::record::Meta + MergeUpdate> Store<Meta> {
     }
 
     pub fn prefixes_len(&self) -> usize {
-        self.v4.store.prefixes.len() + self.v6.store.prefixes.len()
+        self.v4.store.get_prefixes_len() + self.v6.store.get_prefixes_len()
     }
 
     pub fn prefixes_v4_len(&self) -> usize {
-        self.v4.store.prefixes.len()
+        self.v4.store.get_prefixes_len()
     }
 
     pub fn prefixes_v6_len(&self) -> usize {
-        self.v6.store.prefixes.len()
+        self.v6.store.get_prefixes_len()
     }
 
     pub fn nodes_len(&self) -> usize {
@@ -143,3 +361,86 @@ impl<'a, Meta: routecore::record::Meta + MergeUpdate> Store<Meta> {
         }
     }
 }
+
+//------------ PrefixStore impl ---------------------------------------------
+
+impl<Meta: routecore::record::Meta + MergeUpdate> PrefixStore<Meta>
+    for Store<Meta>
+{
+    fn match_prefix<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+        _guard: &'a Guard,
+    ) -> QueryResult<'a, Meta> {
+        Store::match_prefix(self, search_pfx, options)
+    }
+
+    fn more_specifics_from<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        _guard: &'a Guard,
+    ) -> QueryResult<'a, Meta> {
+        Store::more_specifics_from(self, search_pfx)
+    }
+
+    fn less_specifics_from<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        _guard: &'a Guard,
+    ) -> QueryResult<'a, Meta> {
+        Store::less_specifics_from(self, search_pfx)
+    }
+
+    fn prefixes_iter<'a>(
+        &'a self,
+        _guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, Meta>> + 'a>
+    where
+        Meta: 'a,
+    {
+        Box::new(Store::prefixes_iter(self))
+    }
+
+    fn prefixes_iter_af<'a>(
+        &'a self,
+        af: AfSelector,
+        _guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, Meta>> + 'a>
+    where
+        Meta: 'a,
+    {
+        Box::new(Store::prefixes_iter_af(self, af))
+    }
+
+    fn prefixes_len(&self) -> usize {
+        Store::prefixes_len(self)
+    }
+
+    fn nodes_len(&self) -> usize {
+        Store::nodes_len(self)
+    }
+
+    fn stats(&self) -> Stats<'_> {
+        Store::stats(self)
+    }
+}
+
+impl<Meta: routecore::record::Meta + MergeUpdate> PrefixStoreMut<Meta>
+    for Store<Meta>
+{
+    fn insert(
+        &mut self,
+        prefix: &Prefix,
+        meta: Meta,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Store::insert(self, prefix, meta)
+    }
+
+    fn remove(
+        &mut self,
+        prefix: &Prefix,
+    ) -> Result<Option<Meta>, Box<dyn std::error::Error>> {
+        Ok(Store::remove(self, prefix))
+    }
+}
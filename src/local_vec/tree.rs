@@ -6,7 +6,9 @@ use std::{
 use routecore::record::MergeUpdate;
 
 use crate::af::{AddressFamily, Zero};
+use crate::errors::PrefixStoreError;
 use crate::local_vec::node::TreeBitMapNode;
+use crate::local_vec::query::PrefixId;
 use crate::local_vec::storage_backend::StorageBackend;
 use crate::match_node_for_strides_with_local_vec;
 use crate::node_id::SortableNodeId;
@@ -30,7 +32,7 @@ pub enum SizedStrideNode<AF: AddressFamily, NodeId: SortableNodeId + Copy> {
     Stride8(TreeBitMapNode<AF, Stride8, NodeId>),
 }
 
-pub(crate) type SizedNodeResult<'a, AF, NodeType> =
+pub type SizedNodeResult<'a, AF, NodeType> =
     Result<&'a mut SizedStrideNode<AF, NodeType>, Box<dyn std::error::Error>>;
 
 impl<AF, NodeId> Default for SizedStrideNode<AF, NodeId>
@@ -49,39 +51,43 @@ where
     }
 }
 
-pub struct CacheGuard<
-    'a,
-    AF: 'static + AddressFamily,
+impl<AF, NodeId> SizedStrideNode<AF, NodeId>
+where
+    AF: AddressFamily,
     NodeId: SortableNodeId + Copy,
-> {
-    pub guard: std::cell::Ref<'a, SizedStrideNode<AF, NodeId>>,
-}
-
-impl<'a, AF: 'static + AddressFamily, NodeId: SortableNodeId + Copy>
-    std::ops::Deref for CacheGuard<'a, AF, NodeId>
 {
-    type Target = SizedStrideNode<AF, NodeId>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.guard
+    /// The number of bits of the stride of this node.
+    pub fn stride_len(&self) -> u8 {
+        match self {
+            SizedStrideNode::Stride3(_) => 3,
+            SizedStrideNode::Stride4(_) => 4,
+            SizedStrideNode::Stride5(_) => 5,
+            SizedStrideNode::Stride6(_) => 6,
+            SizedStrideNode::Stride7(_) => 7,
+            SizedStrideNode::Stride8(_) => 8,
+        }
     }
-}
-
-pub(crate) struct PrefixCacheGuard<
-    'a,
-    AF: 'static + AddressFamily,
-    Meta: routecore::record::Meta,
-> {
-    pub guard: std::cell::Ref<'a, InternalPrefixRecord<AF, Meta>>,
-}
-
-impl<'a, AF: 'static + AddressFamily, Meta: routecore::record::Meta>
-    std::ops::Deref for PrefixCacheGuard<'a, AF, Meta>
-{
-    type Target = InternalPrefixRecord<AF, Meta>;
 
-    fn deref(&self) -> &Self::Target {
-        &self.guard
+    /// Re-indexes the child nodes and the prefixes of this node with the
+    /// new ids returned by `map_ptr` and `map_pfx`, for use by
+    /// [`StorageBackend::compact`]. A child node for which `map_ptr`
+    /// returns `None` is removed from this node.
+    ///
+    /// The mapping functions must leave the sort part of the ids as is.
+    /// Returns whether this node has neither prefixes nor child nodes left.
+    pub fn reindex(
+        &mut self,
+        map_ptr: impl FnMut(NodeId) -> Option<NodeId>,
+        map_pfx: impl FnMut(NodeId) -> NodeId,
+    ) -> bool {
+        match self {
+            SizedStrideNode::Stride3(n) => n.reindex(map_ptr, map_pfx),
+            SizedStrideNode::Stride4(n) => n.reindex(map_ptr, map_pfx),
+            SizedStrideNode::Stride5(n) => n.reindex(map_ptr, map_pfx),
+            SizedStrideNode::Stride6(n) => n.reindex(map_ptr, map_pfx),
+            SizedStrideNode::Stride7(n) => n.reindex(map_ptr, map_pfx),
+            SizedStrideNode::Stride8(n) => n.reindex(map_ptr, map_pfx),
+        }
     }
 }
 
@@ -290,6 +296,119 @@ where
         }
     }
 
+    // Removes the prefix from the tree. The record for the prefix stays
+    // behind in the store as a tombstone, until the store is compacted.
+    // Returns the meta-data of the removed prefix, or None if the prefix
+    // wasn't in the tree.
+    pub(crate) fn remove(
+        &mut self,
+        pfx: PrefixId<Store::AF>,
+    ) -> Option<Store::Meta> {
+        let mut stride_end: u8 = 0;
+        let mut cur_i = self.store.get_root_node_id();
+        let mut level: u8 = 0;
+
+        // Find the node that would hold the prefix.
+        let (nibble, nibble_len) = loop {
+            let stride = self.strides[level as usize];
+            stride_end += stride;
+            let nibble_len = if pfx.get_len() < stride_end {
+                stride + pfx.get_len() - stride_end
+            } else {
+                stride
+            };
+
+            let nibble = Store::AF::get_nibble(
+                pfx.get_net(),
+                stride_end - stride,
+                nibble_len,
+            );
+
+            if pfx.get_len() <= stride_end {
+                break (nibble, nibble_len);
+            }
+
+            let start_bit = stride_end - stride;
+            cur_i = match self.retrieve_node(cur_i)? {
+                SizedStrideNode::Stride3(n) => n
+                    .search_stride_for_exact_match_at(
+                        pfx, nibble, nibble_len, start_bit,
+                    )
+                    .0,
+                SizedStrideNode::Stride4(n) => n
+                    .search_stride_for_exact_match_at(
+                        pfx, nibble, nibble_len, start_bit,
+                    )
+                    .0,
+                SizedStrideNode::Stride5(n) => n
+                    .search_stride_for_exact_match_at(
+                        pfx, nibble, nibble_len, start_bit,
+                    )
+                    .0,
+                SizedStrideNode::Stride6(n) => n
+                    .search_stride_for_exact_match_at(
+                        pfx, nibble, nibble_len, start_bit,
+                    )
+                    .0,
+                SizedStrideNode::Stride7(n) => n
+                    .search_stride_for_exact_match_at(
+                        pfx, nibble, nibble_len, start_bit,
+                    )
+                    .0,
+                SizedStrideNode::Stride8(n) => n
+                    .search_stride_for_exact_match_at(
+                        pfx, nibble, nibble_len, start_bit,
+                    )
+                    .0,
+            }?;
+            level += 1;
+        };
+
+        // Take it out of the node, the index into `stats` follows the
+        // stride size, just like in `insert`.
+        let (pfx_id, stats_level) =
+            match self.retrieve_node_mut(cur_i).ok()? {
+                SizedStrideNode::Stride3(n) => {
+                    (n.remove_prefix_at(nibble, nibble_len), 0)
+                }
+                SizedStrideNode::Stride4(n) => {
+                    (n.remove_prefix_at(nibble, nibble_len), 1)
+                }
+                SizedStrideNode::Stride5(n) => {
+                    (n.remove_prefix_at(nibble, nibble_len), 2)
+                }
+                SizedStrideNode::Stride6(n) => {
+                    (n.remove_prefix_at(nibble, nibble_len), 3)
+                }
+                SizedStrideNode::Stride7(n) => {
+                    (n.remove_prefix_at(nibble, nibble_len), 4)
+                }
+                SizedStrideNode::Stride8(n) => {
+                    (n.remove_prefix_at(nibble, nibble_len), 5)
+                }
+            };
+
+        let pfx_id = pfx_id?;
+        self.stats[stats_level].dec_prefix_count(level);
+        self.store.remove_prefix(pfx_id.get_part())
+    }
+
+    // Rewrites the backing store without the removed prefixes and the
+    // nodes that have become empty through removals. The dropped nodes
+    // come off the stats the way `insert` put them on: under the stride
+    // and the depth level of the node they were created from.
+    pub(crate) fn compact(&mut self) {
+        for (stride_len, level) in self.store.compact() {
+            if let Some(stats) = self
+                .stats
+                .iter_mut()
+                .find(|stats| stats.stride_len == stride_len)
+            {
+                stats.dec(level);
+            }
+        }
+    }
+
     pub(crate) fn store_node(
         &mut self,
         id: Option<Store::NodeType>,
@@ -339,9 +458,7 @@ where
             Some(update_pfx) => {
                 <Store::Meta>::merge_update(&mut update_pfx.meta, meta)
             }
-            // TODO
-            // Use/create proper error types
-            None => Err("Prefix not found".into()),
+            None => Err(Box::new(PrefixStoreError::PrefixNotFound)),
         }
     }
 
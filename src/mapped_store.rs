@@ -0,0 +1,564 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::marker::PhantomData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use crossbeam_epoch as epoch;
+use memmap2::Mmap;
+use routecore::addr::Prefix;
+use routecore::bgp::{PrefixRecord, RecordSet};
+use routecore::record::{MergeUpdate, Meta};
+
+use crate::meta_codec::MetaCodec;
+use crate::{MatchOptions, MatchType, PrefixStore};
+
+// The layout of a mapped store file, all integers are big-endian:
+//
+// magic         8 bytes   b"RTSTMAP1"
+// v4 count      u64
+// v6 count      u64
+// v4 entries    v4 count * (address u32, length u8, meta offset u64)
+// v6 entries    v6 count * (address u128, length u8, meta offset u64)
+// meta-data     the encoded meta-data of all the entries, in the order of
+//               the entries, the v4 entries first.
+//
+// The entries of both families are sorted on (address, length), so all
+// the more-specifics of a prefix directly follow it. The meta offsets are
+// relative to the start of the meta-data, the meta-data of an entry ends
+// where the meta-data of the next entry starts.
+
+const MAGIC: &[u8; 8] = b"RTSTMAP1";
+const HEADER_LEN: usize = 24;
+
+//------------ MappedStore --------------------------------------------------
+
+/// A read-only store that answers queries directly on a memory-mapped
+/// file.
+///
+/// The file is written once from a [`crate::MultiThreadedStore`] or a
+/// [`crate::SingleThreadedStore`] with [`MappedStore::write_file`], after
+/// which any number of processes can open it with [`MappedStore::open`],
+/// sharing the pages of the file between them. Queries follow the same
+/// [`MatchOptions`] semantics as the other stores, but as the meta-data
+/// has to be decoded from the file, they return it by value in a
+/// [`MappedQueryResult`].
+pub struct MappedStore<M: MetaCodec> {
+    map: Mmap,
+    v4: Table,
+    v6: Table,
+    metas_start: usize,
+    _meta: PhantomData<M>,
+}
+
+impl<M: MetaCodec> MappedStore<M> {
+    /// Writes all the prefixes in `store` with their meta-data to a new
+    /// file at `path`.
+    pub fn write_file(
+        store: &impl PrefixStore<M>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), MappedStoreError>
+    where
+        M: MergeUpdate,
+    {
+        let guard = &epoch::pin();
+        let mut v4 = vec![];
+        let mut v6 = vec![];
+        for record in store.prefixes_iter(guard) {
+            let mut meta = vec![];
+            record.meta.encode(&mut meta);
+            let len = record.prefix.len();
+            match record.prefix.addr() {
+                IpAddr::V4(addr) => {
+                    v4.push((u128::from(u32::from(addr)), len, meta))
+                }
+                IpAddr::V6(addr) => v6.push((u128::from(addr), len, meta)),
+            }
+        }
+        v4.sort_by_key(|(addr, len, _)| (*addr, *len));
+        v6.sort_by_key(|(addr, len, _)| (*addr, *len));
+
+        let mut target = BufWriter::new(File::create(path)?);
+        target.write_all(MAGIC)?;
+        target.write_all(&(v4.len() as u64).to_be_bytes())?;
+        target.write_all(&(v6.len() as u64).to_be_bytes())?;
+
+        let mut offset = 0_u64;
+        for (addr, len, meta) in &v4 {
+            target.write_all(&(*addr as u32).to_be_bytes())?;
+            target.write_all(&[*len])?;
+            target.write_all(&offset.to_be_bytes())?;
+            offset += meta.len() as u64;
+        }
+        for (addr, len, meta) in &v6 {
+            target.write_all(&addr.to_be_bytes())?;
+            target.write_all(&[*len])?;
+            target.write_all(&offset.to_be_bytes())?;
+            offset += meta.len() as u64;
+        }
+        for (_, _, meta) in v4.iter().chain(v6.iter()) {
+            target.write_all(meta)?;
+        }
+        target.flush()?;
+
+        Ok(())
+    }
+
+    /// Maps the file at `path`, written by [`MappedStore::write_file`].
+    ///
+    /// The whole file is checked once, so that queries don't have to,
+    /// without copying any of it into memory.
+    ///
+    /// The file must not be changed while it is mapped, as that changes
+    /// the memory of the store behind its back.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MappedStoreError> {
+        let file = File::open(path)?;
+        // Safety: see the note on changing the file above, this is the
+        // same contract as for any other memory-mapped file.
+        let map = unsafe { Mmap::map(&file)? };
+
+        if map.len() < HEADER_LEN || &map[..8] != MAGIC {
+            return Err(MappedStoreError::InvalidFormat("no mapped store"));
+        }
+        let v4_len = read_usize(&map[8..16])?;
+        let v6_len = read_usize(&map[16..24])?;
+
+        let v4 = Table {
+            start: HEADER_LEN,
+            len: v4_len,
+            first: 0,
+            bits: 32,
+        };
+        let v6 = Table {
+            start: v4.end().ok_or(MappedStoreError::Truncated)?,
+            len: v6_len,
+            first: v4_len,
+            bits: 128,
+        };
+        let metas_start = v6.end().ok_or(MappedStoreError::Truncated)?;
+        if metas_start > map.len() {
+            return Err(MappedStoreError::Truncated);
+        }
+
+        let store = MappedStore {
+            map,
+            v4,
+            v6,
+            metas_start,
+            _meta: PhantomData,
+        };
+        store.check_table(&store.v4)?;
+        store.check_table(&store.v6)?;
+
+        Ok(store)
+    }
+
+    // Checks that the entries of the table are valid prefixes, sorted
+    // without duplicates, and that their meta-data can be decoded.
+    fn check_table(&self, table: &Table) -> Result<(), MappedStoreError> {
+        let metas_len = self.map.len() - self.metas_start;
+        let mut prev = None;
+        for i in 0..table.len {
+            let (addr, len) = self.key(table, i);
+            if len > table.bits || table.truncate(addr, len) != addr {
+                return Err(MappedStoreError::InvalidFormat("bad prefix"));
+            }
+            if prev >= Some((addr, len)) {
+                return Err(MappedStoreError::InvalidFormat("not sorted"));
+            }
+            prev = Some((addr, len));
+
+            let start = self.meta_offset(table.first + i);
+            let end = self.meta_offset(table.first + i + 1);
+            if start > end || end > metas_len {
+                return Err(MappedStoreError::InvalidFormat("bad offset"));
+            }
+            M::decode(self.meta_bytes(table.first + i))
+                .ok_or(MappedStoreError::InvalidMeta)?;
+        }
+        Ok(())
+    }
+
+    /// Searches the store for `search_pfx` with the type of match, and the
+    /// extra results, as requested in `options`.
+    pub fn match_prefix(
+        &self,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+    ) -> MappedQueryResult<M> {
+        let (table, addr, len) = self.search_key(search_pfx);
+        let (exact_match, mut less_specifics) =
+            self.covering_prefixes(table, addr, len);
+
+        // The same as the other stores: without an exact match the
+        // longest less-specific is the longest matching prefix, but only
+        // if the user requested a longest or an empty match.
+        let mut include_more_specifics = false;
+        let mut include_less_specifics = false;
+        let (prefix_idx, match_type) =
+            match (&options.match_type, exact_match) {
+                (_, Some(i)) => {
+                    include_more_specifics = options.include_more_specifics;
+                    include_less_specifics = options.include_less_specifics;
+                    (Some(i), MatchType::ExactMatch)
+                }
+                (MatchType::LongestMatch | MatchType::EmptyMatch, None) => {
+                    include_more_specifics = options.include_more_specifics;
+                    include_less_specifics = options.include_less_specifics;
+                    match less_specifics.pop() {
+                        Some(i) => (Some(i), MatchType::LongestMatch),
+                        None => (None, MatchType::EmptyMatch),
+                    }
+                }
+                (MatchType::ExactMatch, None) => {
+                    (None, MatchType::EmptyMatch)
+                }
+            };
+
+        MappedQueryResult {
+            match_type,
+            prefix: prefix_idx.map(|i| self.prefix(table, i)),
+            prefix_meta: prefix_idx.map(|i| self.meta(table.first + i)),
+            less_specifics: if include_less_specifics {
+                Some(self.record_set(table, less_specifics))
+            } else if options.include_less_specifics {
+                Some(self.record_set(table, vec![]))
+            } else {
+                None
+            },
+            more_specifics: if include_more_specifics {
+                let (addr, len) = match prefix_idx {
+                    Some(i) => self.key(table, i),
+                    None => (addr, len),
+                };
+                Some(self.record_set(
+                    table,
+                    self.more_specifics(table, addr, len),
+                ))
+            } else if options.include_more_specifics {
+                Some(self.record_set(table, vec![]))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Returns the exactly matching prefix for `search_pfx`, if any, and
+    /// all of its more-specifics.
+    pub fn more_specifics_from(
+        &self,
+        search_pfx: &Prefix,
+    ) -> MappedQueryResult<M> {
+        let (table, addr, len) = self.search_key(search_pfx);
+        let exact_match = self.find(table, addr, len);
+
+        MappedQueryResult {
+            match_type: MatchType::EmptyMatch,
+            prefix: exact_match.map(|i| self.prefix(table, i)),
+            prefix_meta: exact_match.map(|i| self.meta(table.first + i)),
+            less_specifics: None,
+            more_specifics: Some(
+                self.record_set(table, self.more_specifics(table, addr, len)),
+            ),
+        }
+    }
+
+    /// Returns the exactly matching prefix for `search_pfx`, if any, and
+    /// all of its less-specifics.
+    pub fn less_specifics_from(
+        &self,
+        search_pfx: &Prefix,
+    ) -> MappedQueryResult<M> {
+        let (table, addr, len) = self.search_key(search_pfx);
+        let (exact_match, less_specifics) =
+            self.covering_prefixes(table, addr, len);
+
+        MappedQueryResult {
+            match_type: MatchType::EmptyMatch,
+            prefix: exact_match.map(|i| self.prefix(table, i)),
+            prefix_meta: exact_match.map(|i| self.meta(table.first + i)),
+            less_specifics: Some(self.record_set(table, less_specifics)),
+            more_specifics: None,
+        }
+    }
+
+    /// Iterates over all the prefixes in the store, the IPv4 prefixes
+    /// first, each in order of address and length.
+    pub fn prefixes_iter(
+        &self,
+    ) -> impl Iterator<Item = PrefixRecord<'static, M>> + '_ {
+        let v4 = (0..self.v4.len).map(move |i| (&self.v4, i));
+        let v6 = (0..self.v6.len).map(move |i| (&self.v6, i));
+        v4.chain(v6).map(move |(table, i)| {
+            PrefixRecord::new_with_local_meta(
+                self.prefix(table, i),
+                self.meta(table.first + i),
+            )
+        })
+    }
+
+    pub fn prefixes_len(&self) -> usize {
+        self.v4.len + self.v6.len
+    }
+
+    pub fn v4_prefixes_len(&self) -> usize {
+        self.v4.len
+    }
+
+    pub fn v6_prefixes_len(&self) -> usize {
+        self.v6.len
+    }
+
+    //--- Reading the entries
+
+    fn search_key(&self, prefix: &Prefix) -> (&Table, u128, u8) {
+        match prefix.addr() {
+            IpAddr::V4(addr) => {
+                (&self.v4, u128::from(u32::from(addr)), prefix.len())
+            }
+            IpAddr::V6(addr) => (&self.v6, u128::from(addr), prefix.len()),
+        }
+    }
+
+    fn key(&self, table: &Table, i: usize) -> (u128, u8) {
+        let pos = table.entry_pos(i);
+        let addr_len = table.addr_len();
+        let addr = self.map[pos..pos + addr_len]
+            .iter()
+            .fold(0_u128, |addr, b| addr << 8 | u128::from(*b));
+        (addr, self.map[pos + addr_len])
+    }
+
+    fn prefix(&self, table: &Table, i: usize) -> Prefix {
+        let (addr, len) = self.key(table, i);
+        let addr = match table.bits {
+            32 => IpAddr::V4(Ipv4Addr::from(addr as u32)),
+            _ => IpAddr::V6(Ipv6Addr::from(addr)),
+        };
+        // All the entries have been checked to be valid prefixes.
+        Prefix::new(addr, len).unwrap()
+    }
+
+    // The offset of the meta-data of the entry with the index `index` over
+    // both tables. The index right after the last entry gives the end of
+    // the meta-data.
+    fn meta_offset(&self, index: usize) -> usize {
+        let (table, i) = if index < self.v4.len {
+            (&self.v4, index)
+        } else {
+            (&self.v6, index - self.v4.len)
+        };
+        if i == table.len {
+            return self.map.len() - self.metas_start;
+        }
+        let pos = table.entry_pos(i) + table.addr_len() + 1;
+        // Every offset fits in an usize, as it's at most the length of the
+        // mapped file.
+        u64::from_be_bytes(self.map[pos..pos + 8].try_into().unwrap())
+            as usize
+    }
+
+    fn meta_bytes(&self, index: usize) -> &[u8] {
+        &self.map[self.metas_start + self.meta_offset(index)
+            ..self.metas_start + self.meta_offset(index + 1)]
+    }
+
+    fn meta(&self, index: usize) -> M {
+        // All the meta-data has been checked to decode.
+        M::decode(self.meta_bytes(index)).unwrap()
+    }
+
+    fn record_set(
+        &self,
+        table: &Table,
+        idxs: Vec<usize>,
+    ) -> RecordSet<'static, M> {
+        let records = idxs
+            .into_iter()
+            .map(|i| {
+                PrefixRecord::new_with_local_meta(
+                    self.prefix(table, i),
+                    self.meta(table.first + i),
+                )
+            })
+            .collect::<Vec<_>>();
+        match table.bits {
+            32 => RecordSet {
+                v4: records,
+                v6: vec![],
+            },
+            _ => RecordSet {
+                v4: vec![],
+                v6: records,
+            },
+        }
+    }
+
+    //--- Searching the entries
+
+    // The index of the first entry that is not smaller than (addr, len).
+    fn lower_bound(&self, table: &Table, addr: u128, len: u8) -> usize {
+        let (mut lo, mut hi) = (0, table.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.key(table, mid) < (addr, len) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    fn find(&self, table: &Table, addr: u128, len: u8) -> Option<usize> {
+        let addr = table.truncate(addr, len);
+        let i = self.lower_bound(table, addr, len);
+        if i < table.len && self.key(table, i) == (addr, len) {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    // Returns the index of the exactly matching entry for the prefix, if
+    // any, together with the indexes of all its less-specifics, in order
+    // of increasing length.
+    fn covering_prefixes(
+        &self,
+        table: &Table,
+        addr: u128,
+        len: u8,
+    ) -> (Option<usize>, Vec<usize>) {
+        let less_specifics =
+            (0..len).filter_map(|l| self.find(table, addr, l)).collect();
+        (self.find(table, addr, len), less_specifics)
+    }
+
+    // Returns the indexes of all the more-specifics of the prefix. As the
+    // entries are sorted on (address, length), these are all the entries
+    // after the prefix itself, up to the last address that it covers.
+    fn more_specifics(
+        &self,
+        table: &Table,
+        addr: u128,
+        len: u8,
+    ) -> Vec<usize> {
+        if len >= table.bits {
+            return vec![];
+        }
+        let addr = table.truncate(addr, len);
+        let last = addr | table.host_mask(len);
+        let start = self.lower_bound(table, addr, len + 1);
+        let end = match last.checked_add(1) {
+            Some(next) => self.lower_bound(table, next, 0),
+            None => table.len,
+        };
+        (start..end).collect()
+    }
+}
+
+//------------ Table --------------------------------------------------------
+
+// The entries of one address family in the file.
+#[derive(Clone, Copy, Debug)]
+struct Table {
+    // The position of the first entry in the file.
+    start: usize,
+    // The number of entries.
+    len: usize,
+    // The index of the first entry over both tables.
+    first: usize,
+    bits: u8,
+}
+
+impl Table {
+    fn addr_len(&self) -> usize {
+        self.bits as usize / 8
+    }
+
+    fn entry_len(&self) -> usize {
+        self.addr_len() + 1 + 8
+    }
+
+    fn entry_pos(&self, i: usize) -> usize {
+        self.start + i * self.entry_len()
+    }
+
+    // The position right after the last entry, or None if that doesn't
+    // fit in an usize, which surely means the file is truncated.
+    fn end(&self) -> Option<usize> {
+        self.len
+            .checked_mul(self.entry_len())
+            .and_then(|len| len.checked_add(self.start))
+    }
+
+    fn host_mask(&self, len: u8) -> u128 {
+        match self.bits - len {
+            0 => 0,
+            128 => u128::MAX,
+            host_len => (1 << host_len) - 1,
+        }
+    }
+
+    fn truncate(&self, addr: u128, len: u8) -> u128 {
+        addr & !self.host_mask(len)
+    }
+}
+
+fn read_usize(bytes: &[u8]) -> Result<usize, MappedStoreError> {
+    // The caller always hands in 8 bytes.
+    usize::try_from(u64::from_be_bytes(bytes.try_into().unwrap()))
+        .map_err(|_| MappedStoreError::Truncated)
+}
+
+//------------ MappedQueryResult --------------------------------------------
+
+/// The result of a query on a [`MappedStore`], the counterpart of
+/// [`crate::QueryResult`] with the meta-data decoded from the file.
+#[derive(Clone, Debug)]
+pub struct MappedQueryResult<M: Meta> {
+    pub match_type: MatchType,
+    pub prefix: Option<Prefix>,
+    pub prefix_meta: Option<M>,
+    pub less_specifics: Option<RecordSet<'static, M>>,
+    pub more_specifics: Option<RecordSet<'static, M>>,
+}
+
+//------------ MappedStoreError ---------------------------------------------
+
+#[derive(Debug)]
+pub enum MappedStoreError {
+    Io(io::Error),
+    /// The file is shorter than its header says it is.
+    Truncated,
+    /// The file is not a mapped store, or it's corrupt.
+    InvalidFormat(&'static str),
+    /// The meta-data in the file could not be decoded.
+    InvalidMeta,
+}
+
+impl std::error::Error for MappedStoreError {}
+
+impl fmt::Display for MappedStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MappedStoreError::Io(err) => write!(f, "Error: {}", err),
+            MappedStoreError::Truncated => {
+                write!(f, "Error: mapped store file is truncated.")
+            }
+            MappedStoreError::InvalidFormat(reason) => {
+                write!(f, "Error: invalid mapped store file: {}.", reason)
+            }
+            MappedStoreError::InvalidMeta => {
+                write!(f, "Error: invalid meta-data in mapped store file.")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for MappedStoreError {
+    fn from(err: io::Error) -> Self {
+        MappedStoreError::Io(err)
+    }
+}
@@ -0,0 +1,31 @@
+use routecore::record::Meta;
+
+use crate::PrefixAs;
+
+//------------ MetaCodec ----------------------------------------------------
+
+/// Meta-data that can be written to a file, and read back from it.
+///
+/// This is what the stores that keep their prefixes in files, like the
+/// `JournaledStore` of the `journal` feature, need from the meta-data.
+pub trait MetaCodec: Meta + 'static {
+    /// Appends the encoded meta-data to `target`.
+    fn encode(&self, target: &mut Vec<u8>);
+
+    /// Decodes the meta-data from exactly the bytes written by
+    /// [`MetaCodec::encode`], or returns `None` if they are invalid.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+impl MetaCodec for PrefixAs {
+    fn encode(&self, target: &mut Vec<u8>) {
+        target.extend_from_slice(&self.0.to_be_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        bytes
+            .try_into()
+            .ok()
+            .map(|asn| PrefixAs(u32::from_be_bytes(asn)))
+    }
+}
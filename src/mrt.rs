@@ -0,0 +1,419 @@
+//! [`load`] reads the RIB dumps published by RouteViews and RIPE RIS (RFC
+//! 6396), decompressed, into a [`MultiThreadedStore`]. Only the
+//! `PEER_INDEX_TABLE`, `RIB_IPV4_UNICAST` and `RIB_IPV6_UNICAST` records
+//! are used, all other records are skipped. Every RIB entry, that is,
+//! every route for a prefix from one peer, is handed to a closure that
+//! turns it into meta-data, and all the meta-data for a prefix is merged
+//! with [`MergeUpdate::merge_update`].
+//!
+//! [`dump`] writes a store back out as a TABLE_DUMP_V2 file, with a
+//! single peer and one RIB entry per prefix, with path attributes made by
+//! a closure from the meta-data.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crossbeam_epoch as epoch;
+use routecore::addr::Prefix;
+use routecore::record::{MergeUpdate, Meta};
+
+use crate::io_util::read_full;
+use crate::MultiThreadedStore;
+
+const TABLE_DUMP_V2: u16 = 13;
+const PEER_INDEX_TABLE: u16 = 1;
+const RIB_IPV4_UNICAST: u16 = 2;
+const RIB_IPV6_UNICAST: u16 = 4;
+
+const ATTR_ORIGIN: u8 = 1;
+const ATTR_AS_PATH: u8 = 2;
+const AS_SEQUENCE: u8 = 2;
+
+//------------ MrtPeer ------------------------------------------------------
+
+/// A peer from the `PEER_INDEX_TABLE` of an MRT file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MrtPeer {
+    pub bgp_id: u32,
+    pub addr: IpAddr,
+    pub asn: u32,
+}
+
+//------------ MrtRibEntry --------------------------------------------------
+
+/// A route for a prefix from one peer, from a RIB record in an MRT file.
+#[derive(Clone, Copy, Debug)]
+pub struct MrtRibEntry<'a> {
+    pub prefix: Prefix,
+    pub peer: &'a MrtPeer,
+    pub originated_time: u32,
+    /// The BGP path attributes of the route, as they are on the wire,
+    /// with 4-byte AS numbers in the AS_PATH.
+    pub attributes: &'a [u8],
+}
+
+impl<'a> MrtRibEntry<'a> {
+    /// Returns the value of the first path attribute with `type_code`.
+    pub fn attribute(&self, type_code: u8) -> Option<&'a [u8]> {
+        let mut parser = Parser(self.attributes);
+        while !parser.0.is_empty() {
+            let flags = parser.u8().ok()?;
+            let code = parser.u8().ok()?;
+            // The extended length bit.
+            let len = if flags & 0x10 != 0 {
+                parser.u16().ok()? as usize
+            } else {
+                parser.u8().ok()? as usize
+            };
+            let value = parser.take(len).ok()?;
+            if code == type_code {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Returns the origin AS, the last AS in the AS_PATH, or `None` if
+    /// the path is empty, or if it ends in an AS_SET.
+    pub fn origin_as(&self) -> Option<u32> {
+        let mut parser = Parser(self.attribute(ATTR_AS_PATH)?);
+        let mut origin = None;
+        while !parser.0.is_empty() {
+            let segment_type = parser.u8().ok()?;
+            let count = parser.u8().ok()? as usize;
+            let asns = parser.take(count * 4).ok()?;
+            origin = match (segment_type, asns.len()) {
+                (_, 0) => origin,
+                (AS_SEQUENCE, len) => Some(u32::from_be_bytes(
+                    asns[len - 4..].try_into().unwrap(),
+                )),
+                _ => None,
+            };
+        }
+        origin
+    }
+}
+
+//------------ Loading ------------------------------------------------------
+
+/// Reads the MRT file at `path` into `store`.
+///
+/// See [`load`].
+pub fn load_file<M, F>(
+    store: &MultiThreadedStore<M>,
+    path: impl AsRef<Path>,
+    meta: F,
+) -> Result<usize, MrtError>
+where
+    M: Meta + MergeUpdate,
+    F: FnMut(&MrtRibEntry) -> Option<M>,
+{
+    load(store, BufReader::new(File::open(path)?), meta)
+}
+
+/// Reads the MRT records from `source` into `store`, and returns the
+/// number of RIB entries that were inserted.
+///
+/// The closure `meta` makes the meta-data for each RIB entry. Entries for
+/// which it returns `None` are skipped.
+pub fn load<M, F>(
+    store: &MultiThreadedStore<M>,
+    mut source: impl Read,
+    mut meta: F,
+) -> Result<usize, MrtError>
+where
+    M: Meta + MergeUpdate,
+    F: FnMut(&MrtRibEntry) -> Option<M>,
+{
+    let mut peers: Option<Vec<MrtPeer>> = None;
+    let mut inserted = 0;
+    let mut body = vec![];
+
+    loop {
+        let mut header = [0; 12];
+        match read_full(&mut source, &mut header)? {
+            0 => break,
+            12 => {}
+            _ => return Err(MrtError::InvalidFormat("truncated header")),
+        }
+        let mut parser = Parser(&header);
+        let _timestamp = parser.u32()?;
+        let mrt_type = parser.u16()?;
+        let subtype = parser.u16()?;
+        let len = parser.u32()? as u64;
+
+        body.clear();
+        source.by_ref().take(len).read_to_end(&mut body)?;
+        if body.len() as u64 != len {
+            return Err(MrtError::InvalidFormat("truncated record"));
+        }
+        if mrt_type != TABLE_DUMP_V2 {
+            continue;
+        }
+
+        match subtype {
+            PEER_INDEX_TABLE => {
+                peers = Some(parse_peer_index_table(&body)?);
+            }
+            RIB_IPV4_UNICAST | RIB_IPV6_UNICAST => {
+                let peers = peers.as_ref().ok_or(MrtError::InvalidFormat(
+                    "RIB record before the peer index table",
+                ))?;
+                let mut parser = Parser(&body);
+                let _sequence = parser.u32()?;
+                let prefix = parser.prefix(subtype == RIB_IPV6_UNICAST)?;
+                for _ in 0..parser.u16()? {
+                    let peer = peers
+                        .get(parser.u16()? as usize)
+                        .ok_or(MrtError::InvalidFormat("unknown peer"))?;
+                    let originated_time = parser.u32()?;
+                    let attr_len = parser.u16()? as usize;
+                    let entry = MrtRibEntry {
+                        prefix,
+                        peer,
+                        originated_time,
+                        attributes: parser.take(attr_len)?,
+                    };
+                    if let Some(record_meta) = meta(&entry) {
+                        store
+                            .insert(&prefix, record_meta)
+                            .map_err(MrtError::Store)?;
+                        inserted += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(inserted)
+}
+
+fn parse_peer_index_table(body: &[u8]) -> Result<Vec<MrtPeer>, MrtError> {
+    let mut parser = Parser(body);
+    let _collector_id = parser.u32()?;
+    let view_name_len = parser.u16()? as usize;
+    parser.take(view_name_len)?;
+
+    let count = parser.u16()?;
+    let mut peers = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let peer_type = parser.u8()?;
+        let bgp_id = parser.u32()?;
+        let addr = if peer_type & 0x01 != 0 {
+            IpAddr::V6(Ipv6Addr::from(parser.u128()?))
+        } else {
+            IpAddr::V4(Ipv4Addr::from(parser.u32()?))
+        };
+        let asn = if peer_type & 0x02 != 0 {
+            parser.u32()?
+        } else {
+            parser.u16()? as u32
+        };
+        peers.push(MrtPeer { bgp_id, addr, asn });
+    }
+    Ok(peers)
+}
+
+//------------ Dumping ------------------------------------------------------
+
+/// Writes `store` as an MRT file at `path`.
+///
+/// See [`dump`].
+pub fn dump_file<M, F>(
+    store: &MultiThreadedStore<M>,
+    path: impl AsRef<Path>,
+    attributes: F,
+) -> Result<(), MrtError>
+where
+    M: Meta + MergeUpdate,
+    F: FnMut(&Prefix, &M) -> Vec<u8>,
+{
+    let mut target = BufWriter::new(File::create(path)?);
+    dump(store, &mut target, attributes)?;
+    target.flush()?;
+    Ok(())
+}
+
+/// Writes all the prefixes in `store` to `target` as MRT TABLE_DUMP_V2
+/// records, IPv4 before IPv6, and ordered by address and length within
+/// each family.
+///
+/// The peer index table has a single peer, with all zeroes for its BGP
+/// identifier, address and AS number. The closure `attributes` makes the
+/// BGP path attributes for the route of that peer to each prefix, see
+/// [`origin_as_attributes`] for an example.
+pub fn dump<M, F>(
+    store: &MultiThreadedStore<M>,
+    mut target: impl Write,
+    mut attributes: F,
+) -> Result<(), MrtError>
+where
+    M: Meta + MergeUpdate,
+    F: FnMut(&Prefix, &M) -> Vec<u8>,
+{
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0);
+
+    let mut body = vec![];
+    body.extend_from_slice(&0_u32.to_be_bytes()); // collector BGP id
+    body.extend_from_slice(&0_u16.to_be_bytes()); // view name length
+    body.extend_from_slice(&1_u16.to_be_bytes()); // peer count
+    body.push(0x02); // IPv4 address, 4-byte AS number
+    body.extend_from_slice(&0_u32.to_be_bytes()); // BGP id
+    body.extend_from_slice(&0_u32.to_be_bytes()); // address
+    body.extend_from_slice(&0_u32.to_be_bytes()); // AS number
+    write_record(&mut target, timestamp, PEER_INDEX_TABLE, &body)?;
+
+    let guard = &epoch::pin();
+    let mut records = store.prefixes_iter(guard).collect::<Vec<_>>();
+    records.sort_by_key(|r| (r.prefix.addr(), r.prefix.len()));
+
+    for (sequence, record) in records.iter().enumerate() {
+        let attrs = attributes(&record.prefix, &*record.meta);
+        let attr_len = u16::try_from(attrs.len()).map_err(|_| {
+            MrtError::InvalidFormat("path attributes too long")
+        })?;
+
+        body.clear();
+        body.extend_from_slice(&(sequence as u32).to_be_bytes());
+        body.push(record.prefix.len());
+        let net_len = (record.prefix.len() as usize + 7) / 8;
+        let subtype = match record.prefix.addr() {
+            IpAddr::V4(addr) => {
+                body.extend_from_slice(&addr.octets()[..net_len]);
+                RIB_IPV4_UNICAST
+            }
+            IpAddr::V6(addr) => {
+                body.extend_from_slice(&addr.octets()[..net_len]);
+                RIB_IPV6_UNICAST
+            }
+        };
+        body.extend_from_slice(&1_u16.to_be_bytes()); // entry count
+        body.extend_from_slice(&0_u16.to_be_bytes()); // peer index
+        body.extend_from_slice(&timestamp.to_be_bytes());
+        body.extend_from_slice(&attr_len.to_be_bytes());
+        body.extend_from_slice(&attrs);
+        write_record(&mut target, timestamp, subtype, &body)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the path attributes for a route originated by `asn`: an ORIGIN
+/// of IGP, and an AS_PATH with just `asn`.
+///
+/// This is what [`MrtRibEntry::origin_as`] reads back, so with
+/// [`crate::PrefixAs`] as meta-data a store can be dumped with
+/// `|_, meta| origin_as_attributes(meta.0)` and loaded again with
+/// `|entry| entry.origin_as().map(PrefixAs)`.
+pub fn origin_as_attributes(asn: u32) -> Vec<u8> {
+    let mut attrs = vec![0x40, ATTR_ORIGIN, 1, 0];
+    attrs.extend_from_slice(&[0x40, ATTR_AS_PATH, 6, AS_SEQUENCE, 1]);
+    attrs.extend_from_slice(&asn.to_be_bytes());
+    attrs
+}
+
+fn write_record(
+    target: &mut impl Write,
+    timestamp: u32,
+    subtype: u16,
+    body: &[u8],
+) -> Result<(), MrtError> {
+    target.write_all(&timestamp.to_be_bytes())?;
+    target.write_all(&TABLE_DUMP_V2.to_be_bytes())?;
+    target.write_all(&subtype.to_be_bytes())?;
+    target.write_all(&(body.len() as u32).to_be_bytes())?;
+    target.write_all(body)?;
+    Ok(())
+}
+
+//------------ Parser -------------------------------------------------------
+
+// Takes big-endian integers off the front of a record.
+struct Parser<'a>(&'a [u8]);
+
+impl<'a> Parser<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MrtError> {
+        if self.0.len() < len {
+            return Err(MrtError::InvalidFormat("truncated record"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, MrtError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MrtError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, MrtError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u128(&mut self) -> Result<u128, MrtError> {
+        Ok(u128::from_be_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    // A prefix length, followed by just enough bytes of the address to
+    // hold it.
+    fn prefix(&mut self, v6: bool) -> Result<Prefix, MrtError> {
+        let len = self.u8()?;
+        let max_len = if v6 { 128 } else { 32 };
+        if len > max_len {
+            return Err(MrtError::InvalidFormat("invalid prefix length"));
+        }
+        let net = self.take((len as usize + 7) / 8)?;
+        let addr = if v6 {
+            let mut octets = [0; 16];
+            octets[..net.len()].copy_from_slice(net);
+            IpAddr::V6(octets.into())
+        } else {
+            let mut octets = [0; 4];
+            octets[..net.len()].copy_from_slice(net);
+            IpAddr::V4(octets.into())
+        };
+        Prefix::new(addr, len)
+            .map_err(|_| MrtError::InvalidFormat("invalid prefix"))
+    }
+}
+
+//------------ MrtError -----------------------------------------------------
+
+#[derive(Debug)]
+pub enum MrtError {
+    Io(io::Error),
+    /// The file is not a valid MRT file.
+    InvalidFormat(&'static str),
+    /// The store refused an insert.
+    Store(Box<dyn std::error::Error>),
+}
+
+impl std::error::Error for MrtError {}
+
+impl fmt::Display for MrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MrtError::Io(err) => write!(f, "Error: {}", err),
+            MrtError::InvalidFormat(reason) => {
+                write!(f, "Error: invalid MRT file: {}.", reason)
+            }
+            MrtError::Store(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for MrtError {
+    fn from(err: io::Error) -> Self {
+        MrtError::Io(err)
+    }
+}
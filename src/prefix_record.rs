@@ -2,7 +2,8 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Debug;
 
-use crate::{af::AddressFamily, local_array::node::PrefixId};
+use crate::af::{AddressFamily, IpAddressFamily};
+use crate::local_array::node::PrefixId;
 use routecore::record::{MergeUpdate, Meta, Record};
 
 //------------ InternalPrefixRecord -----------------------------------------
@@ -38,15 +39,36 @@ where
         Self { net, len, meta }
     }
 
+    pub fn get_prefix_id(&self) -> PrefixId<AF> {
+        PrefixId::new(self.net, self.len)
+    }
+}
+
+impl<M, AF> InternalPrefixRecord<AF, M>
+where
+    M: Meta,
+    AF: AddressFamily,
+{
+    // The bits of the prefix shifted all the way to the right, the key
+    // for comparing records. A zero length prefix has no bits, and can't
+    // be shifted by the full width of the family.
+    fn right_aligned_net(&self) -> AF {
+        match self.len {
+            0 => AF::zero(),
+            len => self.net >> (AF::BITS - len),
+        }
+    }
+}
+
+impl<M, AF> InternalPrefixRecord<AF, M>
+where
+    M: Meta,
+    AF: IpAddressFamily,
+{
     // This should never fail, since there shouldn't be a invalid prefix in
     // this record in the first place.
     pub fn prefix_into_pub(&self) -> routecore::addr::Prefix {
-        routecore::addr::Prefix::new(self.net.into_ipaddr(), self.len)
-            .unwrap_or_else(|p| panic!("can't convert {:?} into prefix.", p))
-    }
-
-    pub fn get_prefix_id(&self) -> PrefixId<AF> {
-        PrefixId::new(self.net, self.len)
+        self.net.into_prefix(self.len)
     }
 }
 
@@ -72,8 +94,7 @@ where
     AF: AddressFamily,
 {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.net >> (AF::BITS - self.len))
-            .cmp(&(other.net >> ((AF::BITS - other.len) % 32)))
+        self.right_aligned_net().cmp(&other.right_aligned_net())
     }
 }
 
@@ -83,8 +104,7 @@ where
     AF: AddressFamily,
 {
     fn eq(&self, other: &Self) -> bool {
-        self.net >> (AF::BITS - self.len)
-            == other.net >> ((AF::BITS - other.len) % 32)
+        self.right_aligned_net() == other.right_aligned_net()
     }
 }
 
@@ -94,10 +114,7 @@ where
     AF: AddressFamily,
 {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(
-            (self.net >> (AF::BITS - self.len))
-                .cmp(&(other.net >> ((AF::BITS - other.len) % 32))),
-        )
+        Some(self.cmp(other))
     }
 }
 
@@ -157,16 +174,12 @@ where
 impl<'a, AF, M> From<&'a InternalPrefixRecord<AF, M>>
     for routecore::bgp::PrefixRecord<'a, M>
 where
-    AF: AddressFamily,
+    AF: IpAddressFamily,
     M: Meta,
 {
     fn from(record: &'a InternalPrefixRecord<AF, M>) -> Self {
         routecore::bgp::PrefixRecord::new(
-            routecore::addr::Prefix::new(
-                record.net.into_ipaddr(),
-                record.len,
-            )
-            .unwrap(),
+            record.net.into_prefix(record.len),
             &record.meta,
         )
     }
@@ -175,13 +188,13 @@ where
 impl<'a, AF, M> From<routecore::bgp::PrefixRecord<'a, M>>
     for InternalPrefixRecord<AF, M>
 where
-    AF: AddressFamily,
+    AF: IpAddressFamily,
     M: Meta,
 {
     fn from(record: routecore::bgp::PrefixRecord<'a, M>) -> Self {
         Self {
             net: AF::from_ipaddr(record.key().addr()),
-            len: record.key().len(),
+            len: record.key().len() + AF::IP_OFFSET,
             meta: record.meta().into_owned(),
         }
     }
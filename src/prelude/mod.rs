@@ -4,7 +4,10 @@ pub use routecore::addr::Prefix;
 pub use routecore::bgp::PrefixRecord;
 pub use routecore::record::{Meta, Record};
 
-pub use crate::{AddressFamily, IPv4, IPv6, PrefixRecordMap};
+pub use crate::{
+    AddressFamily, IPv4, IPv6, IpAddressFamily, PrefixRecordMap,
+    RouteDistinguisher, VpnIPv4, VpnIPv6,
+};
 
 pub use rotonda_macros::create_store;
 pub use rotonda_macros::stride_sizes;
@@ -16,4 +19,7 @@ pub use crate::local_array::store::atomic_types::{
 
 pub use crate::local_array::tree::{PrefixId, StrideNodeId, TreeBitMap};
 pub use crate::stride::{Stride3, Stride4, Stride5};
-pub use crate::{MatchOptions, QueryResult, Stats};
+pub use crate::{
+    AfSelector, ConcurrentPrefixStore, MatchOptions, PrefixStore,
+    PrefixStoreMut, QueryResult, Stats,
+};
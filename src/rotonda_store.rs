@@ -7,24 +7,186 @@ use routecore::bgp::MetaDataSet;
 use routecore::{
     addr::Prefix,
     bgp::{PrefixRecord, RecordSet},
-    record::{MergeUpdate, Record},
+    record::{MergeUpdate, Meta, Record},
 };
 
-pub use crate::af::{AddressFamily, IPv4, IPv6};
+use crossbeam_epoch::Guard;
 
+pub use crate::af::{
+    AddressFamily, IPv4, IPv6, IpAddressFamily, RouteDistinguisher, VpnIPv4,
+    VpnIPv6,
+};
+
+pub use crate::meta_codec::MetaCodec;
+
+pub use crate::bit_prefix::{
+    BitPrefix, BitPrefixError, BitPrefixRecord, BitQueryResult,
+};
+
+pub use crate::errors::PrefixStoreError;
 pub use crate::local_array::store::custom_alloc;
+pub use crate::local_vec::storage_backend;
 
 pub const RECORDS_MAX_NUM: usize = 3;
 
 //------------ The publicly available Rotonda Stores ------------------------
 
 pub use crate::local_array::store::DefaultStore as MultiThreadedStore;
+pub use crate::local_array::store::{
+    BitPrefixStore as MultiThreadedBitPrefixStore,
+    VpnStore as MultiThreadedVpnStore,
+};
+pub use crate::local_array::store::custom_alloc::{UpsertReport, Upserted};
 pub use crate::local_vec::store::Store as SingleThreadedStore;
+pub use crate::local_vec::bit_store::BitPrefixStore;
+pub use crate::local_vec::frozen::FrozenStore;
+#[cfg(feature = "journal")]
+pub use crate::journal::{Durability, JournalError, JournaledStore};
+pub use crate::local_vec::storage_backend::{
+    InMemStorage, SlabStorage, StorageBackend,
+};
+#[cfg(feature = "mmap")]
+pub use crate::mapped_store::{
+    MappedQueryResult, MappedStore, MappedStoreError,
+};
+
+//------------ PrefixStore --------------------------------------------------
+
+/// The read interface shared by all the Rotonda Stores.
+///
+/// All methods take a [`Prefix`] and dispatch on its address family, so
+/// application code (and tests) can be written once against this trait and
+/// run with either the [`MultiThreadedStore`] or the
+/// [`SingleThreadedStore`]. Writing to a store goes through
+/// [`PrefixStoreMut`], or through [`ConcurrentPrefixStore`] for stores
+/// that can be written to from many threads at once.
+///
+/// Methods that return references into the store take an epoch [`Guard`].
+/// Stores that don't need one, like the [`SingleThreadedStore`], ignore it.
+pub trait PrefixStore<M: Meta + MergeUpdate> {
+    /// Searches the store for `search_pfx` with the type of match, and the
+    /// extra results, as requested in `options`.
+    fn match_prefix<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        options: &MatchOptions,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M>;
+
+    /// Returns the exactly matching prefix for `search_pfx`, if any, and
+    /// all of its more-specifics.
+    fn more_specifics_from<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M>;
+
+    /// Returns the exactly matching prefix for `search_pfx`, if any, and
+    /// all of its less-specifics.
+    fn less_specifics_from<'a>(
+        &'a self,
+        search_pfx: &Prefix,
+        guard: &'a Guard,
+    ) -> QueryResult<'a, M>;
+
+    /// Iterates over all the prefixes in the store, IPv4 and IPv6 alike.
+    fn prefixes_iter<'a>(
+        &'a self,
+        guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, M>> + 'a>
+    where
+        M: 'a;
+
+    /// Iterates over the prefixes of the address families selected with
+    /// `af`. The tree of an address family that's not selected isn't
+    /// visited at all.
+    fn prefixes_iter_af<'a>(
+        &'a self,
+        af: AfSelector,
+        guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, M>> + 'a>
+    where
+        M: 'a;
+
+    /// Iterates over all the prefixes in the store for which `filter`
+    /// returns `true` on their meta-data.
+    fn prefixes_iter_filtered<'a, F>(
+        &'a self,
+        filter: F,
+        guard: &'a Guard,
+    ) -> Box<dyn Iterator<Item = PrefixRecord<'a, M>> + 'a>
+    where
+        Self: Sized,
+        M: 'a,
+        F: Fn(&M) -> bool + 'a,
+    {
+        Box::new(
+            self.prefixes_iter(guard)
+                .filter(move |record| filter(&*record.meta)),
+        )
+    }
+
+    /// The number of prefixes in the store, IPv4 and IPv6 alike.
+    fn prefixes_len(&self) -> usize;
+
+    /// The number of nodes in the trees of the store, IPv4 and IPv6 alike.
+    fn nodes_len(&self) -> usize;
+
+    /// The stride statistics of the trees of the store.
+    fn stats(&self) -> Stats<'_>;
+}
+
+//------------ PrefixStoreMut -----------------------------------------------
+
+/// Writing to a Rotonda Store that is owned by one thread at a time.
+///
+/// Both the [`MultiThreadedStore`] and the [`SingleThreadedStore`]
+/// implement this trait.
+pub trait PrefixStoreMut<M: Meta + MergeUpdate>: PrefixStore<M> {
+    /// Inserts a prefix with its meta-data into the store. If the prefix
+    /// already exists, the meta-data is merged into the existing meta-data
+    /// with [`MergeUpdate::merge_update`].
+    fn insert(
+        &mut self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Removes a prefix from the store and returns its meta-data, or
+    /// `None` if the prefix wasn't in the store. Stores that do not
+    /// support removal return an error.
+    fn remove(
+        &mut self,
+        prefix: &Prefix,
+    ) -> Result<Option<M>, Box<dyn std::error::Error>>;
+}
+
+//------------ ConcurrentPrefixStore ----------------------------------------
+
+/// Writing to a Rotonda Store that is shared between threads.
+///
+/// Unlike [`PrefixStoreMut`], inserting only takes a shared reference, so
+/// the store can sit behind an `Arc` with any number of threads writing
+/// and reading at the same time. Only the [`MultiThreadedStore`]
+/// implements this trait.
+pub trait ConcurrentPrefixStore<M: Meta + MergeUpdate>:
+    PrefixStore<M> + Sync
+{
+    /// Inserts a prefix with its meta-data into the store. If the prefix
+    /// already exists, the meta-data is merged into the existing meta-data
+    /// with [`MergeUpdate::merge_update`].
+    fn insert(
+        &self,
+        prefix: &Prefix,
+        meta: M,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
 
 //------------ Types for strides displaying/monitoring ----------------------
 
 type AfStrideStats = Vec<StrideStats>;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Stats<'a> {
     pub v4: &'a AfStrideStats,
     pub v6: &'a AfStrideStats,
@@ -44,9 +206,10 @@ impl<'a> std::fmt::Display for Stats<'a> {
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Strides<'a> {
-    pub v4: &'a Vec<u8>,
-    pub v6: &'a Vec<u8>,
+    pub v4: &'a [u8],
+    pub v6: &'a [u8],
 }
 
 impl<'a> std::fmt::Debug for Strides<'a> {
@@ -63,8 +226,94 @@ impl<'a> std::fmt::Debug for Strides<'a> {
     }
 }
 
+//------------ AfSelector ---------------------------------------------------
+
+/// Selects the address families to include when iterating over a store,
+/// and in what order.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum AfSelector {
+    /// Only the IPv4 prefixes.
+    Ipv4,
+    /// Only the IPv6 prefixes.
+    Ipv6,
+    /// All the IPv4 prefixes, followed by all the IPv6 prefixes.
+    Both,
+    /// The IPv4 and IPv6 prefixes taking turns, starting with IPv4. Once
+    /// one of the families runs out, the rest of the other one follows.
+    Interleaved,
+}
+
+impl AfSelector {
+    pub fn includes_v4(&self) -> bool {
+        !matches!(self, Self::Ipv6)
+    }
+
+    pub fn includes_v6(&self) -> bool {
+        !matches!(self, Self::Ipv4)
+    }
+}
+
+//------------ AfIter -------------------------------------------------------
+
+// Iterates over the IPv4 and the IPv6 prefixes of a store in the order
+// an AfSelector asks for. The iterator of a family that isn't selected is
+// never created.
+pub(crate) struct AfIter<V4, V6> {
+    v4: Option<V4>,
+    v6: Option<V6>,
+    interleaved: bool,
+    v6_turn: bool,
+}
+
+impl<V4, V6> AfIter<V4, V6> {
+    pub(crate) fn new(
+        af: AfSelector,
+        v4: impl FnOnce() -> V4,
+        v6: impl FnOnce() -> V6,
+    ) -> Self {
+        AfIter {
+            v4: af.includes_v4().then(v4),
+            v6: af.includes_v6().then(v6),
+            interleaved: af == AfSelector::Interleaved,
+            v6_turn: false,
+        }
+    }
+}
+
+// The next item of an iterator that is dropped once it runs out.
+fn next_of<I: Iterator>(iter: &mut Option<I>) -> Option<I::Item> {
+    let item = iter.as_mut()?.next();
+    if item.is_none() {
+        *iter = None;
+    }
+    item
+}
+
+impl<T, V4, V6> Iterator for AfIter<V4, V6>
+where
+    V4: Iterator<Item = T>,
+    V6: Iterator<Item = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.interleaved {
+            let v6_turn = self.v6_turn;
+            self.v6_turn = !v6_turn;
+            if v6_turn {
+                return next_of(&mut self.v6)
+                    .or_else(|| next_of(&mut self.v4));
+            }
+        }
+        next_of(&mut self.v4).or_else(|| next_of(&mut self.v6))
+    }
+}
+
 //------------ MatchOptions / MatchType -------------------------------------
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MatchOptions {
     pub match_type: MatchType,
     pub include_all_records: bool,
@@ -73,6 +322,8 @@ pub struct MatchOptions {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum MatchType {
     ExactMatch,
     LongestMatch,
@@ -97,7 +348,8 @@ impl std::fmt::Display for MatchType {
 
 //------------ Metadata Types -----------------------------------------------
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PrefixAs(pub u32);
 
 impl MergeUpdate for PrefixAs {
@@ -126,7 +378,7 @@ impl fmt::Display for PrefixAs {
     }
 }
 
-impl<'a, AF: 'a + AddressFamily, Meta: routecore::record::Meta>
+impl<'a, AF: 'a + IpAddressFamily, Meta: routecore::record::Meta>
     std::iter::FromIterator<&'a InternalPrefixRecord<AF, Meta>>
     for RecordSet<'a, Meta>
 {
@@ -138,7 +390,7 @@ impl<'a, AF: 'a + AddressFamily, Meta: routecore::record::Meta>
         let mut v4 = vec![];
         let mut v6 = vec![];
         for pfx in iter {
-            let u_pfx = Prefix::new(pfx.net.into_ipaddr(), pfx.len).unwrap();
+            let u_pfx = pfx.net.into_prefix(pfx.len);
             match u_pfx.addr() {
                 std::net::IpAddr::V4(_) => {
                     v4.push(PrefixRecord::new(u_pfx, &pfx.meta));
@@ -163,11 +415,14 @@ impl std::hash::Hash for PrefixAs {
 //------------ PrefixRecordIter ---------------------------------------------
 
 // Converts from the InternalPrefixRecord to the (public) PrefixRecord
-// while iterating.
+// while iterating. Records that are marked as removed in the accompanying
+// tombstones are skipped.
 #[derive(Clone, Debug)]
 pub struct PrefixRecordIter<'a, Meta: routecore::record::Meta> {
     pub(crate) v4: Option<slice::Iter<'a, InternalPrefixRecord<IPv4, Meta>>>,
     pub(crate) v6: slice::Iter<'a, InternalPrefixRecord<IPv6, Meta>>,
+    pub(crate) v4_removed: slice::Iter<'a, bool>,
+    pub(crate) v6_removed: slice::Iter<'a, bool>,
 }
 
 impl<'a, Meta: routecore::record::Meta> Iterator
@@ -178,15 +433,22 @@ impl<'a, Meta: routecore::record::Meta> Iterator
     fn next(&mut self) -> Option<Self::Item> {
         // V4 is already done.
         if self.v4.is_none() {
-            return self.v6.next().map(|res| {
-                PrefixRecord::new(
+            for res in self.v6.by_ref() {
+                if self.v6_removed.next() == Some(&true) {
+                    continue;
+                }
+                return Some(PrefixRecord::new(
                     Prefix::new(res.net.into_ipaddr(), res.len).unwrap(),
                     &res.meta,
-                )
-            });
+                ));
+            }
+            return None;
         }
 
-        if let Some(res) = self.v4.as_mut().and_then(|v4| v4.next()) {
+        while let Some(res) = self.v4.as_mut().and_then(|v4| v4.next()) {
+            if self.v4_removed.next() == Some(&true) {
+                continue;
+            }
             return Some(PrefixRecord::new(
                 Prefix::new(res.net.into_ipaddr(), res.len).unwrap(),
                 &res.meta,
@@ -274,6 +536,28 @@ impl<'a, M: routecore::record::Meta> std::fmt::Display
 
 //------------- QueryResult -------------------------------------------------
 
+/// The result of a query on a store.
+///
+/// With the `serde` feature, query results serialize to an object with the
+/// same fields, with the prefixes as strings, and the records in the less-
+/// and more-specifics as objects with a `prefix` and its `meta`. In JSON:
+///
+/// ```text
+/// {
+///   "match_type": "exact-match" | "longest-match" | "empty-match",
+///   "prefix": "192.0.2.0/24" | null,
+///   "prefix_meta": <meta> | null,
+///   "less_specifics": [{ "prefix": "192.0.0.0/16", "meta": <meta> }]
+///     | null,
+///   "more_specifics": [{ "prefix": "192.0.2.0/25", "meta": <meta> }]
+///     | null
+/// }
+/// ```
+///
+/// The less- and more-specifics are `null` if they weren't asked for, and
+/// list the IPv4 records before the IPv6 records. The
+/// [`crate::BitQueryResult`] and the `MappedQueryResult` serialize to the
+/// same shape.
 #[derive(Clone, Debug)]
 pub struct QueryResult<'a, M: routecore::record::Meta> {
     pub match_type: MatchType,
@@ -0,0 +1,206 @@
+//! Serialization of the query results, for the `serde` feature.
+//!
+//! The result types borrow from the store, or hold types from routecore
+//! that can't be serialized, so they are serialized by hand here. The
+//! types that own all their data derive `Serialize` and `Deserialize`
+//! where they are defined.
+//!
+//! All query results serialize to the same shape, see
+//! [`crate::QueryResult`]. Prefixes are written as strings, in their
+//! `Display` format.
+use std::fmt::Display;
+
+use routecore::bgp::RecordSet;
+use routecore::record::Meta;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct};
+use serde::Serializer;
+
+use crate::af::AddressFamily;
+use crate::{BitPrefix, BitPrefixRecord, BitQueryResult};
+use crate::{MatchType, PrefixRecordMap, QueryResult};
+
+//------------ QueryResult --------------------------------------------------
+
+impl<'a, M> Serialize for QueryResult<'a, M>
+where
+    M: Meta + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_query_result(
+            serializer,
+            &self.match_type,
+            self.prefix.as_ref().map(AsString),
+            self.prefix_meta,
+            self.less_specifics.as_ref().map(Records),
+            self.more_specifics.as_ref().map(Records),
+        )
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<M> Serialize for crate::MappedQueryResult<M>
+where
+    M: Meta + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_query_result(
+            serializer,
+            &self.match_type,
+            self.prefix.as_ref().map(AsString),
+            self.prefix_meta.as_ref(),
+            self.less_specifics.as_ref().map(Records),
+            self.more_specifics.as_ref().map(Records),
+        )
+    }
+}
+
+impl<'a, AF, M> Serialize for BitQueryResult<'a, AF, M>
+where
+    AF: AddressFamily,
+    M: Meta + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_query_result(
+            serializer,
+            &self.match_type,
+            self.prefix.as_ref().map(AsString),
+            self.prefix_meta,
+            self.less_specifics.as_ref(),
+            self.more_specifics.as_ref(),
+        )
+    }
+}
+
+fn serialize_query_result<S, P, M, R>(
+    serializer: S,
+    match_type: &MatchType,
+    prefix: Option<P>,
+    prefix_meta: Option<&M>,
+    less_specifics: Option<R>,
+    more_specifics: Option<R>,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    P: Serialize,
+    M: Serialize,
+    R: Serialize,
+{
+    let mut res = serializer.serialize_struct("QueryResult", 5)?;
+    res.serialize_field("match_type", match_type)?;
+    res.serialize_field("prefix", &prefix)?;
+    res.serialize_field("prefix_meta", &prefix_meta)?;
+    res.serialize_field("less_specifics", &less_specifics)?;
+    res.serialize_field("more_specifics", &more_specifics)?;
+    res.end()
+}
+
+//------------ Records ------------------------------------------------------
+
+// A record set as a sequence of records, IPv4 before IPv6.
+struct Records<'r, 'a, M: Meta>(&'r RecordSet<'a, M>);
+
+impl<'r, 'a, M> Serialize for Records<'r, 'a, M>
+where
+    M: Meta + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let records = &self.0;
+        let mut seq = serializer
+            .serialize_seq(Some(records.v4.len() + records.v6.len()))?;
+        for record in records.v4.iter().chain(records.v6.iter()) {
+            seq.serialize_element(&Record {
+                prefix: AsString(&record.prefix),
+                meta: &*record.meta,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+impl<'a, AF, M> Serialize for BitPrefixRecord<'a, AF, M>
+where
+    AF: AddressFamily,
+    M: Meta + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Record {
+            prefix: AsString(&self.prefix),
+            meta: self.meta,
+        }
+        .serialize(serializer)
+    }
+}
+
+struct Record<'r, P: Display, M> {
+    prefix: AsString<'r, P>,
+    meta: &'r M,
+}
+
+impl<'r, P: Display, M: Serialize> Serialize for Record<'r, P, M> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut record = serializer.serialize_struct("Record", 2)?;
+        record.serialize_field("prefix", &self.prefix)?;
+        record.serialize_field("meta", self.meta)?;
+        record.end()
+    }
+}
+
+//------------ PrefixRecordMap ----------------------------------------------
+
+/// A map from prefix to its meta-data set. The meta-data sets from
+/// routecore can only be displayed, so they are written as strings.
+impl<'a, M: Meta> Serialize for PrefixRecordMap<'a, M> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (prefix, meta) in self.iter() {
+            map.serialize_entry(&AsString(prefix), &AsString(meta))?;
+        }
+        map.end()
+    }
+}
+
+//------------ BitPrefix ----------------------------------------------------
+
+impl<AF: AddressFamily> Serialize for BitPrefix<AF> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+//------------ AsString -----------------------------------------------------
+
+// Serializes a value as its `Display` string.
+struct AsString<'r, T: Display>(&'r T);
+
+impl<'r, T: Display> Serialize for AsString<'r, T> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self.0)
+    }
+}
@@ -4,6 +4,7 @@ use crate::stride::{Stride3, Stride4, Stride5, Stride6, Stride7, Stride8};
 use std::fmt::{Debug, Display};
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SizedStride {
     Stride3,
     Stride4,
@@ -12,6 +13,7 @@ pub enum SizedStride {
     Stride7,
     Stride8,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StrideStats {
     pub stride_type: SizedStride,
     pub stride_size: usize,
@@ -98,9 +100,17 @@ impl StrideStats {
         self.created_nodes[depth_level as usize].count += 1;
     }
 
+    pub fn dec(&mut self, depth_level: u8) {
+        self.created_nodes[depth_level as usize].count -= 1;
+    }
+
     pub fn inc_prefix_count(&mut self, depth_level: u8) {
         self.prefixes_num[depth_level as usize].count += 1;
     }
+
+    pub fn dec_prefix_count(&mut self, depth_level: u8) {
+        self.prefixes_num[depth_level as usize].count -= 1;
+    }
 }
 
 impl Debug for StrideStats {
@@ -136,6 +146,7 @@ impl Display for StrideStats {
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CreatedNodes {
     pub depth_level: u8,
     pub count: usize,
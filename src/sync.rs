@@ -0,0 +1,43 @@
+//! The atomics the lock-free parts of the multi-threaded store are built
+//! on.
+//!
+//! Building with `--cfg loom` swaps them for those of [loom], so the model
+//! tests in `tests/loom.rs` can explore all the ways the threads of a test
+//! interleave. The epoch based memory reclamation and the back-off of
+//! crossbeam follow along with `--cfg crossbeam_loom`:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom --cfg crossbeam_loom" \
+//!     cargo test --release --test loom
+//! ```
+//!
+//! Loom has no `into_inner` on its atomics, so atomics that are owned are
+//! read with a relaxed load instead.
+//!
+//! Loom only sees the atomics that go through this module or through
+//! crossbeam. What it can't see:
+//!
+//! * Any atomics of its own in the code that the `create_store` macro of
+//!   `rotonda-macros` generates for the [`crate::MultiThreadedStore`],
+//!   i.e. the store type itself and its node and prefix buckets. The
+//!   buckets hold the node and prefix sets of this crate, and loom sees
+//!   their atomics. But the macro isn't part of this crate, so nothing
+//!   here makes the rest of its code use this module. The models for the
+//!   [`crate::MultiThreadedBitPrefixStore`] avoid the macro, because that
+//!   store uses the hand-written buckets in `local_array::store::buckets`.
+//! * The `Mutex` of the `JournaledStore` of the `journal` feature, which
+//!   comes from `std::sync`. There are no models for the journal.
+//!
+//! The modules that import `Ordering` from `std::sync::atomic` only use it
+//! as an argument to these atomics. Loom uses the same type, so that
+//! doesn't hide anything.
+//!
+//! [loom]: https://docs.rs/loom
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{
+    fence, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize,
+};
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{
+    fence, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize,
+};
@@ -1,7 +1,9 @@
 use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::fmt::{Binary, Debug};
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering as AtomicOrdering;
+
+use crate::sync::AtomicU64;
 
 use crate::af::Zero;
 
@@ -102,12 +104,66 @@ impl std::ops::BitAnd<Self> for U256 {
     }
 }
 
+impl std::ops::BitXor<Self> for U256 {
+    type Output = Self;
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self(self.0 ^ rhs.0, self.1 ^ rhs.1)
+    }
+}
+
 impl PartialEq for U256 {
     fn eq(&self, other: &Self) -> bool {
         self.0 == other.0 && self.1 == other.1
     }
 }
 
+impl std::hash::Hash for U256 {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.1.hash(state);
+    }
+}
+
+impl std::ops::Shl<u8> for U256 {
+    type Output = Self;
+    fn shl(self, rhs: u8) -> Self::Output {
+        match rhs {
+            0 => self,
+            1..=127 => Self(
+                self.0 << rhs | self.1 >> (128 - rhs),
+                self.1 << rhs,
+            ),
+            _ => Self(self.1 << (rhs - 128), 0),
+        }
+    }
+}
+
+impl std::ops::Shr<u8> for U256 {
+    type Output = Self;
+    fn shr(self, rhs: u8) -> Self::Output {
+        match rhs {
+            0 => self,
+            1..=127 => Self(
+                self.0 >> rhs,
+                self.1 >> rhs | self.0 << (128 - rhs),
+            ),
+            _ => Self(0, self.0 >> (rhs - 128)),
+        }
+    }
+}
+
+impl std::ops::Sub for U256 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        let (lo, borrow) = self.1.overflowing_sub(other.1);
+        U256(
+            self.0.wrapping_sub(other.0).wrapping_sub(borrow as u128),
+            lo,
+        )
+    }
+}
+
 //------------ U512 Synthetic Integer Type ----------------------------------
 
 #[derive(Debug, Copy, Clone)]
@@ -234,6 +290,18 @@ impl std::ops::BitAnd<Self> for U512 {
     }
 }
 
+impl std::ops::BitXor<Self> for U512 {
+    type Output = Self;
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self(
+            self.0 ^ rhs.0,
+            self.1 ^ rhs.1,
+            self.2 ^ rhs.2,
+            self.3 ^ rhs.3,
+        )
+    }
+}
+
 //------------ Atomic U128 Synthetic Integer Type -------------------------------------
 
 #[allow(dead_code)]
@@ -249,8 +317,8 @@ impl AtomicU128 {
 
     pub fn into_be_bytes(self) -> [u8; 16] {
         [
-            self.0.into_inner().to_be_bytes(),
-            self.1.into_inner().to_be_bytes(),
+            self.0.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.1.load(AtomicOrdering::Relaxed).to_be_bytes(),
         ]
         .concat()
         .try_into()
@@ -309,10 +377,10 @@ impl AtomicU256 {
 
     pub fn into_be_bytes(self) -> [u8; 32] {
         [
-            self.0.into_inner().to_be_bytes(),
-            self.1.into_inner().to_be_bytes(),
-            self.2.into_inner().to_be_bytes(),
-            self.3.into_inner().to_be_bytes(),
+            self.0.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.1.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.2.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.3.load(AtomicOrdering::Relaxed).to_be_bytes(),
         ]
         .concat()
         .try_into()
@@ -386,14 +454,14 @@ impl AtomicU512 {
 
     pub fn into_be_bytes(self) -> [u8; 64] {
         [
-            self.0.into_inner().to_be_bytes(),
-            self.1.into_inner().to_be_bytes(),
-            self.2.into_inner().to_be_bytes(),
-            self.3.into_inner().to_be_bytes(),
-            self.4.into_inner().to_be_bytes(),
-            self.5.into_inner().to_be_bytes(),
-            self.6.into_inner().to_be_bytes(),
-            self.7.into_inner().to_be_bytes(),
+            self.0.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.1.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.2.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.3.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.4.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.5.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.6.load(AtomicOrdering::Relaxed).to_be_bytes(),
+            self.7.load(AtomicOrdering::Relaxed).to_be_bytes(),
         ]
         .concat()
         .try_into()
@@ -0,0 +1,230 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, BitPrefix, BitPrefixError, BitPrefixRecord,
+        BitPrefixStore, IPv4, MatchOptions, MatchType,
+        MultiThreadedBitPrefixStore, PrefixAs,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::sync::Arc;
+    use std::thread;
+
+    // A MAC address, left-aligned in an u64.
+    fn mac(bytes: [u8; 6]) -> u64 {
+        let mut b = [0_u8; 8];
+        b[..6].copy_from_slice(&bytes);
+        u64::from_be_bytes(b)
+    }
+
+    // The prefixes of the records in a query result, in order.
+    fn prefixes(
+        recs: Option<Vec<BitPrefixRecord<u64, PrefixAs>>>,
+    ) -> Vec<BitPrefix<u64>> {
+        let mut pfxs =
+            recs.unwrap().iter().map(|r| r.prefix).collect::<Vec<_>>();
+        pfxs.sort();
+        pfxs
+    }
+
+    fn options() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::LongestMatch,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    #[test]
+    fn test_bit_prefix() {
+        let oui = mac([0x00, 0x1b, 0x21, 0, 0, 0]);
+        let pfx = BitPrefix::new(oui, 24).unwrap();
+        assert_eq!(pfx.net(), oui);
+        assert_eq!(pfx.len(), 24);
+        assert_eq!(pfx.to_string(), "0x001b210000000000/24");
+
+        let host = mac([0x00, 0x1b, 0x21, 0x3c, 0x4d, 0x5e]);
+        assert_eq!(
+            BitPrefix::new(host, 24),
+            Err(BitPrefixError::HostBitsSet)
+        );
+        assert_eq!(BitPrefix::new_relaxed(host, 24), Ok(pfx));
+        assert_eq!(
+            BitPrefix::new(0_u64, 65),
+            Err(BitPrefixError::LenTooLong)
+        );
+
+        let host = BitPrefix::new(host, 48).unwrap();
+        assert!(pfx.covers(&host));
+        assert!(pfx.covers(&pfx));
+        assert!(!host.covers(&pfx));
+        assert!(BitPrefix::new(0_u64, 0).unwrap().covers(&host));
+
+        // Prefixes of IP address families convert into IP prefixes.
+        let pfx = BitPrefix::<IPv4>::new(0xc000_0200, 24).unwrap();
+        assert_eq!(
+            Prefix::from(pfx),
+            Prefix::new("192.0.2.0".parse().unwrap(), 24).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mac_store() -> Result<(), Box<dyn Error>> {
+        let mut store = BitPrefixStore::<u64, PrefixAs>::new(vec![8]);
+        assert_eq!(store.strides(), &vec![8; 8]);
+
+        // An OUI, an MA-M block in it, and a host in that block.
+        let oui = BitPrefix::new(mac([0x00, 0x1b, 0x21, 0, 0, 0]), 24)?;
+        let ma_m = BitPrefix::new(mac([0x00, 0x1b, 0x21, 0x30, 0, 0]), 28)?;
+        let host =
+            BitPrefix::new(mac([0x00, 0x1b, 0x21, 0x3c, 0x4d, 0x5e]), 48)?;
+        let other = BitPrefix::new(mac([0x00, 0x50, 0x56, 0, 0, 0]), 24)?;
+
+        store.insert(oui, PrefixAs(1))?;
+        store.insert(ma_m, PrefixAs(2))?;
+        store.insert(host, PrefixAs(3))?;
+        store.insert(other, PrefixAs(4))?;
+        assert_eq!(store.prefixes_len(), 4);
+        assert_eq!(store.prefixes_iter().count(), 4);
+
+        let res = store.match_prefix(host, &options());
+        assert!(matches!(res.match_type, MatchType::ExactMatch));
+        assert_eq!(res.prefix, Some(host));
+        assert_eq!(res.prefix_meta.map(|m| m.0), Some(3));
+        assert_eq!(
+            res.less_specifics
+                .unwrap()
+                .iter()
+                .map(|r| r.prefix)
+                .collect::<Vec<_>>(),
+            vec![oui, ma_m]
+        );
+        assert!(res.more_specifics.unwrap().is_empty());
+
+        // Another host in the MA-M block.
+        let search =
+            BitPrefix::new(mac([0x00, 0x1b, 0x21, 0x3f, 0xff, 0xff]), 48)?;
+        let res = store.match_prefix(search, &options());
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+        assert_eq!(res.prefix, Some(ma_m));
+        assert_eq!(res.less_specifics.unwrap().len(), 1);
+        assert_eq!(res.more_specifics.unwrap().len(), 1);
+
+        let res = store.more_specifics_from(oui);
+        assert_eq!(res.prefix, Some(oui));
+        let mut more = res
+            .more_specifics
+            .unwrap()
+            .iter()
+            .map(|r| r.prefix)
+            .collect::<Vec<_>>();
+        more.sort();
+        assert_eq!(more, vec![ma_m, host]);
+
+        let res = store.less_specifics_from(host);
+        assert_eq!(res.less_specifics.unwrap().len(), 2);
+
+        // Unknown OUIs don't match anything.
+        let search = BitPrefix::new(mac([0x00, 0x0c, 0x29, 0, 0, 0]), 24)?;
+        let res = store.match_prefix(search, &options());
+        assert!(matches!(res.match_type, MatchType::EmptyMatch));
+        assert_eq!(res.prefix, None);
+
+        assert_eq!(store.remove(ma_m).map(|m| m.0), Some(2));
+        store.compact();
+        assert_eq!(store.prefixes_len(), 3);
+        let res = store.match_prefix(host, &options());
+        assert_eq!(res.less_specifics.unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_asn_store() -> Result<(), Box<dyn Error>> {
+        let mut store = BitPrefixStore::<u32, PrefixAs>::new(vec![4]);
+
+        // AS numbers for private use (64512-65535) and documentation
+        // (64496-64511).
+        let private = BitPrefix::new(64512_u32, 22)?;
+        let documentation = BitPrefix::new(64496_u32, 28)?;
+        store.insert(private, PrefixAs(1))?;
+        store.insert(documentation, PrefixAs(2))?;
+
+        for (asn, exp) in [
+            (65000, Some(private)),
+            (65535, Some(private)),
+            (64500, Some(documentation)),
+            (64495, None),
+            (3333, None),
+        ] {
+            let res =
+                store.match_prefix(BitPrefix::new(asn, 32)?, &options());
+            assert_eq!(res.prefix, exp, "AS{}", asn);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_threaded_mac_store() -> Result<(), Box<dyn Error>> {
+        let store =
+            Arc::new(MultiThreadedBitPrefixStore::<u64, PrefixAs>::new()?);
+        assert_eq!(store.strides(), &[4; 16]);
+
+        let oui = BitPrefix::new(mac([0x00, 0x1b, 0x21, 0, 0, 0]), 24)?;
+        let ma_m = BitPrefix::new(mac([0x00, 0x1b, 0x21, 0x30, 0, 0]), 28)?;
+        let host =
+            BitPrefix::new(mac([0x00, 0x1b, 0x21, 0x3c, 0x4d, 0x5e]), 48)?;
+        let other = BitPrefix::new(mac([0x00, 0x50, 0x56, 0, 0, 0]), 24)?;
+
+        let writers = [(oui, 1), (ma_m, 2), (host, 3), (other, 4)]
+            .into_iter()
+            .map(|(pfx, asn)| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    store.insert(pfx, PrefixAs(asn)).unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(store.prefixes_len(), 4);
+
+        let guard = &epoch::pin();
+
+        let res = store.match_prefix(host, &options(), guard);
+        assert!(matches!(res.match_type, MatchType::ExactMatch));
+        assert_eq!(res.prefix, Some(host));
+        assert_eq!(res.prefix_meta.map(|m| m.0), Some(3));
+        assert_eq!(prefixes(res.less_specifics), vec![oui, ma_m]);
+        assert!(res.more_specifics.unwrap().is_empty());
+
+        // Another host in the MA-M block.
+        let search =
+            BitPrefix::new(mac([0x00, 0x1b, 0x21, 0x3f, 0xff, 0xff]), 48)?;
+        let res = store.match_prefix(search, &options(), guard);
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+        assert_eq!(res.prefix, Some(ma_m));
+        assert_eq!(prefixes(res.less_specifics), vec![oui]);
+        assert_eq!(prefixes(res.more_specifics), vec![host]);
+
+        let res = store.more_specifics_from(oui, guard);
+        assert_eq!(res.prefix, Some(oui));
+        assert_eq!(prefixes(res.more_specifics), vec![ma_m, host]);
+
+        let res = store.less_specifics_from(host, guard);
+        assert_eq!(res.prefix, Some(host));
+        assert_eq!(prefixes(res.less_specifics), vec![oui, ma_m]);
+
+        // Unknown OUIs don't match anything.
+        let search = BitPrefix::new(mac([0x00, 0x0c, 0x29, 0, 0, 0]), 24)?;
+        let res = store.match_prefix(search, &options(), guard);
+        assert!(matches!(res.match_type, MatchType::EmptyMatch));
+        assert_eq!(res.prefix, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+// A conformance suite that runs the exact same queries against both the
+// MultiThreadedStore and the SingleThreadedStore and checks that they return
+// the same results.
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+    use routecore::bgp::RecordSet;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn stored_prefixes() -> Vec<Prefix> {
+        [
+            "10.0.0.0/8",
+            "10.1.0.0/16",
+            "10.1.1.0/24",
+            "10.1.1.128/25",
+            "10.2.0.0/16",
+            "2001:db8::/32",
+            "2001:db8:1::/48",
+            "2001:db8:1:1::/64",
+            "2001:db8:1:1::/127",
+            "2001:db8:2::/48",
+        ]
+        .iter()
+        .map(|p| pfx(p))
+        .collect()
+    }
+
+    fn sorted(record_set: Option<RecordSet<PrefixAs>>) -> Vec<Prefix> {
+        let mut pfxs = record_set
+            .expect("record set should be present")
+            .iter()
+            .map(|r| r.prefix)
+            .collect::<Vec<_>>();
+        pfxs.sort_by_key(|p| (p.addr(), p.len()));
+        pfxs
+    }
+
+    fn prefixes(pfxs: &[&str]) -> Vec<Prefix> {
+        let mut pfxs = pfxs.iter().map(|p| pfx(p)).collect::<Vec<_>>();
+        pfxs.sort_by_key(|p| (p.addr(), p.len()));
+        pfxs
+    }
+
+    fn options(match_type: MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    fn conformance(
+        store: &mut impl PrefixStoreMut<PrefixAs>,
+    ) -> Result<(), Box<dyn Error>> {
+        for (i, p) in stored_prefixes().iter().enumerate() {
+            store.insert(p, PrefixAs(i as u32))?;
+        }
+        assert_eq!(store.prefixes_len(), stored_prefixes().len());
+
+        let guard = &epoch::pin();
+
+        // (search prefix, requested match type, expected match type,
+        //  expected prefix, expected less-specifics, expected
+        //  more-specifics)
+        #[allow(clippy::type_complexity)]
+        let cases: Vec<(
+            &str,
+            MatchType,
+            MatchType,
+            Option<&str>,
+            Vec<&str>,
+            Vec<&str>,
+        )> = vec![
+            // Exact matches
+            (
+                "10.1.0.0/16",
+                MatchType::ExactMatch,
+                MatchType::ExactMatch,
+                Some("10.1.0.0/16"),
+                vec!["10.0.0.0/8"],
+                vec!["10.1.1.0/24", "10.1.1.128/25"],
+            ),
+            (
+                "2001:db8:1::/48",
+                MatchType::ExactMatch,
+                MatchType::ExactMatch,
+                Some("2001:db8:1::/48"),
+                vec!["2001:db8::/32"],
+                vec!["2001:db8:1:1::/64", "2001:db8:1:1::/127"],
+            ),
+            // No exact match available
+            (
+                "10.3.0.0/16",
+                MatchType::ExactMatch,
+                MatchType::EmptyMatch,
+                None,
+                vec![],
+                vec![],
+            ),
+            (
+                "2001:db8:3::/48",
+                MatchType::ExactMatch,
+                MatchType::EmptyMatch,
+                None,
+                vec![],
+                vec![],
+            ),
+            // Longest matches, the less- and more-specifics are the ones
+            // of the longest matching prefix.
+            (
+                "10.1.2.0/24",
+                MatchType::LongestMatch,
+                MatchType::LongestMatch,
+                Some("10.1.0.0/16"),
+                vec!["10.0.0.0/8"],
+                vec!["10.1.1.0/24", "10.1.1.128/25"],
+            ),
+            (
+                "10.1.1.129/32",
+                MatchType::LongestMatch,
+                MatchType::LongestMatch,
+                Some("10.1.1.128/25"),
+                vec!["10.0.0.0/8", "10.1.0.0/16", "10.1.1.0/24"],
+                vec![],
+            ),
+            (
+                "2001:db8:1:1::1/128",
+                MatchType::LongestMatch,
+                MatchType::LongestMatch,
+                Some("2001:db8:1:1::/127"),
+                vec![
+                    "2001:db8::/32",
+                    "2001:db8:1::/48",
+                    "2001:db8:1:1::/64",
+                ],
+                vec![],
+            ),
+            // Longest match asked for, but exact match found
+            (
+                "10.2.0.0/16",
+                MatchType::LongestMatch,
+                MatchType::ExactMatch,
+                Some("10.2.0.0/16"),
+                vec!["10.0.0.0/8"],
+                vec![],
+            ),
+            // Nothing covers these
+            (
+                "11.0.0.0/8",
+                MatchType::LongestMatch,
+                MatchType::EmptyMatch,
+                None,
+                vec![],
+                vec![],
+            ),
+            (
+                "2001:db9::/32",
+                MatchType::EmptyMatch,
+                MatchType::EmptyMatch,
+                None,
+                vec![],
+                vec![],
+            ),
+            // An empty match for a prefix that only has more-specifics
+            (
+                "10.0.0.0/7",
+                MatchType::EmptyMatch,
+                MatchType::EmptyMatch,
+                None,
+                vec![],
+                vec![
+                    "10.0.0.0/8",
+                    "10.1.0.0/16",
+                    "10.1.1.0/24",
+                    "10.1.1.128/25",
+                    "10.2.0.0/16",
+                ],
+            ),
+        ];
+
+        for (search, req_type, exp_type, exp_pfx, exp_ls, exp_ms) in cases {
+            println!("search {} {}", search, req_type);
+            let res =
+                store.match_prefix(&pfx(search), &options(req_type), guard);
+            println!("{}", res);
+            assert_eq!(
+                format!("{}", res.match_type),
+                format!("{}", exp_type)
+            );
+            assert_eq!(res.prefix, exp_pfx.map(pfx));
+            assert_eq!(sorted(res.less_specifics), prefixes(&exp_ls));
+            assert_eq!(sorted(res.more_specifics), prefixes(&exp_ms));
+        }
+
+        // more_specifics_from and less_specifics_from
+        let res = store.more_specifics_from(&pfx("10.1.0.0/16"), guard);
+        assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+        assert!(res.less_specifics.is_none());
+        assert_eq!(
+            sorted(res.more_specifics),
+            prefixes(&["10.1.1.0/24", "10.1.1.128/25"])
+        );
+
+        let res = store.more_specifics_from(&pfx("2001:db8::/31"), guard);
+        assert_eq!(res.prefix, None);
+        assert_eq!(
+            sorted(res.more_specifics),
+            prefixes(&[
+                "2001:db8::/32",
+                "2001:db8:1::/48",
+                "2001:db8:1:1::/64",
+                "2001:db8:1:1::/127",
+                "2001:db8:2::/48",
+            ])
+        );
+
+        let res = store.less_specifics_from(&pfx("10.1.1.128/25"), guard);
+        assert_eq!(res.prefix, Some(pfx("10.1.1.128/25")));
+        assert!(res.more_specifics.is_none());
+        assert_eq!(
+            sorted(res.less_specifics),
+            prefixes(&["10.0.0.0/8", "10.1.0.0/16", "10.1.1.0/24"])
+        );
+
+        let res =
+            store.less_specifics_from(&pfx("2001:db8:1:1::8/125"), guard);
+        assert_eq!(res.prefix, None);
+        assert_eq!(
+            sorted(res.less_specifics),
+            prefixes(&[
+                "2001:db8::/32",
+                "2001:db8:1::/48",
+                "2001:db8:1:1::/64"
+            ])
+        );
+
+        // Iteration filtered on meta-data
+        let even = store
+            .prefixes_iter_filtered(|meta| meta.0 % 2 == 0, guard)
+            .count();
+        assert_eq!(even, (stored_prefixes().len() + 1) / 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conformance_multi_threaded() -> Result<(), Box<dyn Error>> {
+        conformance(&mut MultiThreadedStore::<PrefixAs>::new()?)
+    }
+
+    #[test]
+    fn test_conformance_single_threaded() -> Result<(), Box<dyn Error>> {
+        conformance(&mut SingleThreadedStore::<PrefixAs>::new(
+            vec![4],
+            vec![4],
+        ))
+    }
+
+    // The strides are repeated until they cover the whole address, so
+    // they have to add up to a divisor of its length.
+    #[test]
+    fn test_conformance_single_threaded_mixed_strides(
+    ) -> Result<(), Box<dyn Error>> {
+        conformance(&mut SingleThreadedStore::<PrefixAs>::new(
+            vec![3, 4, 5, 6, 7, 7],
+            vec![8, 7, 6, 5, 3, 3],
+        ))
+    }
+}
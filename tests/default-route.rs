@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+
+    fn default_routes() -> Vec<Prefix> {
+        vec![
+            Prefix::new_relaxed(
+                std::net::Ipv4Addr::new(0, 0, 0, 0).into(),
+                0,
+            )
+            .unwrap(),
+            Prefix::new_relaxed(
+                std::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0).into(),
+                0,
+            )
+            .unwrap(),
+        ]
+    }
+
+    // For each default route a pair of (prefix in the store, search prefix
+    // without an exact match in the store).
+    fn prefixes() -> Vec<(Prefix, Prefix)> {
+        vec![
+            (
+                Prefix::new_relaxed(
+                    std::net::Ipv4Addr::new(192, 0, 2, 0).into(),
+                    24,
+                )
+                .unwrap(),
+                Prefix::new_relaxed(
+                    std::net::Ipv4Addr::new(198, 51, 100, 0).into(),
+                    24,
+                )
+                .unwrap(),
+            ),
+            (
+                Prefix::new_relaxed(
+                    std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0)
+                        .into(),
+                    32,
+                )
+                .unwrap(),
+                Prefix::new_relaxed(
+                    std::net::Ipv6Addr::new(0x2a00, 0, 0, 0, 0, 0, 0, 0)
+                        .into(),
+                    16,
+                )
+                .unwrap(),
+            ),
+        ]
+    }
+
+    fn longest_match_options() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::LongestMatch,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: false,
+        }
+    }
+
+    #[test]
+    fn test_default_route_multi_threaded() -> Result<(), Box<dyn Error>> {
+        let tree_bitmap = MultiThreadedStore::<PrefixAs>::new()?;
+        for (dr, (pfx, _)) in default_routes().iter().zip(prefixes()) {
+            tree_bitmap.insert(dr, PrefixAs(1))?;
+            tree_bitmap.insert(&pfx, PrefixAs(2))?;
+        }
+
+        let guard = &epoch::pin();
+        for (dr, (pfx, no_pfx)) in default_routes().iter().zip(prefixes()) {
+            // The default route itself
+            let res = tree_bitmap.match_prefix(
+                dr,
+                &longest_match_options(),
+                guard,
+            );
+            println!("default route: {:#?}", res);
+            assert_eq!(res.prefix, Some(*dr));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(1));
+            assert_eq!(res.less_specifics.unwrap().len(), 0);
+
+            // An exact match has the default route as less-specific
+            let res = tree_bitmap.match_prefix(
+                &pfx,
+                &longest_match_options(),
+                guard,
+            );
+            println!("exact match: {:#?}", res);
+            assert_eq!(res.prefix, Some(pfx));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            let less_specifics = res.less_specifics.unwrap();
+            assert_eq!(less_specifics.len(), 1);
+            assert!(less_specifics.iter().any(|p| p.prefix == *dr));
+
+            // Without anything else covering it, the default route is the
+            // longest matching prefix.
+            let res = tree_bitmap.match_prefix(
+                &no_pfx,
+                &longest_match_options(),
+                guard,
+            );
+            println!("longest match: {:#?}", res);
+            assert_eq!(res.prefix, Some(*dr));
+            assert!(matches!(res.match_type, MatchType::LongestMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(1));
+
+            // And it shows up in the less-specifics
+            let res = tree_bitmap.less_specifics_from(&pfx, guard);
+            let less_specifics = res.less_specifics.unwrap();
+            assert_eq!(less_specifics.len(), 1);
+            assert!(less_specifics.iter().any(|p| p.prefix == *dr));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_route_single_threaded() -> Result<(), Box<dyn Error>> {
+        let mut tree_bitmap =
+            SingleThreadedStore::<PrefixAs>::new(vec![4], vec![4]);
+        for (dr, (pfx, _)) in default_routes().iter().zip(prefixes()) {
+            tree_bitmap.insert(dr, PrefixAs(1))?;
+            tree_bitmap.insert(&pfx, PrefixAs(2))?;
+        }
+
+        for (dr, (pfx, no_pfx)) in default_routes().iter().zip(prefixes()) {
+            // The default route itself
+            let res =
+                tree_bitmap.match_prefix(dr, &longest_match_options());
+            println!("default route: {:#?}", res);
+            assert_eq!(res.prefix, Some(*dr));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(1));
+            assert_eq!(res.less_specifics.unwrap().len(), 0);
+
+            // An exact match has the default route as less-specific
+            let res =
+                tree_bitmap.match_prefix(&pfx, &longest_match_options());
+            println!("exact match: {:#?}", res);
+            assert_eq!(res.prefix, Some(pfx));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            let less_specifics = res.less_specifics.unwrap();
+            assert_eq!(less_specifics.len(), 1);
+            assert!(less_specifics.iter().any(|p| p.prefix == *dr));
+
+            // Without anything else covering it, the default route is the
+            // longest matching prefix.
+            let res =
+                tree_bitmap.match_prefix(&no_pfx, &longest_match_options());
+            println!("longest match: {:#?}", res);
+            assert_eq!(res.prefix, Some(*dr));
+            assert!(matches!(res.match_type, MatchType::LongestMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(1));
+
+            // And it shows up in the less-specifics
+            let res = tree_bitmap.less_specifics_from(&pfx);
+            let less_specifics = res.less_specifics.unwrap();
+            assert_eq!(less_specifics.len(), 1);
+            assert!(less_specifics.iter().any(|p| p.prefix == *dr));
+
+            // The other way around, the prefix in the store is the only
+            // more-specific of the default route.
+            let res = tree_bitmap.more_specifics_from(dr);
+            println!("more-specifics: {:#?}", res);
+            let more_specifics = res.more_specifics.unwrap();
+            assert_eq!(more_specifics.len(), 1);
+            assert!(more_specifics.iter().any(|p| p.prefix == pfx));
+
+            let res = tree_bitmap.match_prefix(
+                dr,
+                &MatchOptions {
+                    match_type: MatchType::ExactMatch,
+                    include_all_records: false,
+                    include_less_specifics: false,
+                    include_more_specifics: true,
+                },
+            );
+            println!("default route with more-specifics: {:#?}", res);
+            assert_eq!(res.prefix, Some(*dr));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            let more_specifics = res.more_specifics.unwrap();
+            assert_eq!(more_specifics.len(), 1);
+            assert!(more_specifics.iter().any(|p| p.prefix == pfx));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+// A differential test that runs random sequences of inserts and queries
+// against the MultiThreadedStore, the SingleThreadedStore and a naive
+// reference RIB, and checks that all three give the same answers.
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use proptest::sample::Index;
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+    use routecore::bgp::RecordSet;
+
+    use std::collections::BTreeMap;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    // The network mask for a length in an address family of `bits` bits,
+    // with the addresses in the low bits of a u128.
+    fn mask(bits: u8, len: u8) -> u128 {
+        let all = u128::MAX >> (128 - u32::from(bits));
+        all & !all.checked_shr(u32::from(len)).unwrap_or(0)
+    }
+
+    fn to_prefix(bits: u8, addr: u128, len: u8) -> Prefix {
+        let addr = addr & mask(bits, len);
+        let addr = if bits == 32 {
+            IpAddr::V4(Ipv4Addr::from(addr as u32))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(addr))
+        };
+        Prefix::new(addr, len).unwrap()
+    }
+
+    fn from_prefix(prefix: &Prefix) -> (u128, u8) {
+        match prefix.addr() {
+            IpAddr::V4(addr) => (u128::from(u32::from(addr)), prefix.len()),
+            IpAddr::V6(addr) => (u128::from(addr), prefix.len()),
+        }
+    }
+
+    type Records = Vec<(Prefix, u32)>;
+
+    // The result of a query, in a form that can be compared.
+    #[derive(Debug, PartialEq)]
+    struct Outcome {
+        match_type: String,
+        prefix: Option<(Prefix, u32)>,
+        less_specifics: Option<Records>,
+        more_specifics: Option<Records>,
+    }
+
+    fn records(record_set: RecordSet<PrefixAs>) -> Records {
+        let mut records = record_set
+            .iter()
+            .map(|r| (r.prefix, r.meta.0))
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(p, _)| (p.addr(), p.len()));
+        records
+    }
+
+    impl From<QueryResult<'_, PrefixAs>> for Outcome {
+        fn from(res: QueryResult<'_, PrefixAs>) -> Self {
+            Outcome {
+                match_type: res.match_type.to_string(),
+                prefix: res.prefix.zip(res.prefix_meta.map(|m| m.0)),
+                less_specifics: res.less_specifics.map(records),
+                more_specifics: res.more_specifics.map(records),
+            }
+        }
+    }
+
+    // A RIB for one address family that is too simple to get wrong: the
+    // prefixes sorted by address and then length, with their origin AS.
+    struct Reference {
+        bits: u8,
+        rib: BTreeMap<(u128, u8), u32>,
+    }
+
+    impl Reference {
+        fn new(bits: u8) -> Self {
+            Reference {
+                bits,
+                rib: BTreeMap::new(),
+            }
+        }
+
+        fn insert(&mut self, prefix: &Prefix, asn: u32) {
+            self.rib.insert(from_prefix(prefix), asn);
+        }
+
+        fn record(&self, addr: u128, len: u8) -> Option<(Prefix, u32)> {
+            self.rib
+                .get(&(addr, len))
+                .map(|asn| (to_prefix(self.bits, addr, len), *asn))
+        }
+
+        // The prefixes covering `addr/len`, shortest first, which is also
+        // the order of their addresses.
+        fn less_specifics(&self, addr: u128, len: u8) -> Records {
+            (0..len)
+                .filter_map(|l| self.record(addr & mask(self.bits, l), l))
+                .collect()
+        }
+
+        // The prefixes covered by `addr/len`. They all sort between the
+        // first and the last address of `addr/len`.
+        fn more_specifics(&self, addr: u128, len: u8) -> Records {
+            if len == self.bits {
+                return vec![];
+            }
+            let host = mask(self.bits, self.bits) & !mask(self.bits, len);
+            self.rib
+                .range((addr, len + 1)..=(addr | host, self.bits))
+                .filter(|((a, l), _)| {
+                    *l > len && a & mask(self.bits, len) == addr
+                })
+                .map(|((a, l), asn)| (to_prefix(self.bits, *a, *l), *asn))
+                .collect()
+        }
+
+        fn query(&self, prefix: &Prefix, query: &Query) -> Outcome {
+            let (addr, len) = from_prefix(prefix);
+            let exact = self.record(addr, len);
+            match query {
+                Query::MoreSpecificsFrom => Outcome {
+                    match_type: MatchType::EmptyMatch.to_string(),
+                    prefix: exact,
+                    less_specifics: None,
+                    more_specifics: Some(self.more_specifics(addr, len)),
+                },
+                Query::LessSpecificsFrom => Outcome {
+                    match_type: MatchType::EmptyMatch.to_string(),
+                    prefix: exact,
+                    less_specifics: Some(self.less_specifics(addr, len)),
+                    more_specifics: None,
+                },
+                Query::Match(match_type) => {
+                    self.match_prefix(addr, len, exact, match_type)
+                }
+            }
+        }
+
+        fn match_prefix(
+            &self,
+            addr: u128,
+            len: u8,
+            exact: Option<(Prefix, u32)>,
+            match_type: &MatchType,
+        ) -> Outcome {
+            let (found, match_type) = match (exact, match_type) {
+                (Some(record), _) => (Some(record), MatchType::ExactMatch),
+                (None, MatchType::ExactMatch) => {
+                    return Outcome {
+                        match_type: MatchType::EmptyMatch.to_string(),
+                        prefix: None,
+                        less_specifics: Some(vec![]),
+                        more_specifics: Some(vec![]),
+                    }
+                }
+                (None, _) => {
+                    match self.less_specifics(addr, len).pop() {
+                        Some(record) => {
+                            (Some(record), MatchType::LongestMatch)
+                        }
+                        None => (None, MatchType::EmptyMatch),
+                    }
+                }
+            };
+            // The less- and more-specifics are those of the prefix found,
+            // or of the search prefix if there is none.
+            let (addr, len) =
+                found.map_or((addr, len), |(p, _)| from_prefix(&p));
+            Outcome {
+                match_type: match_type.to_string(),
+                prefix: found,
+                less_specifics: Some(self.less_specifics(addr, len)),
+                more_specifics: Some(self.more_specifics(addr, len)),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    enum Query {
+        Match(MatchType),
+        MoreSpecificsFrom,
+        LessSpecificsFrom,
+    }
+
+    // The prefixes of the operations are made from a handful of addresses
+    // close to each other, at random lengths, so that they nest a lot.
+    #[derive(Clone, Debug)]
+    enum Op {
+        Insert(Index, u8, u32),
+        Query(Index, u8, Query),
+    }
+
+    fn addrs(bits: u8) -> impl Strategy<Value = Vec<u128>> {
+        let shift = 128 - u32::from(bits);
+        (
+            any::<u128>(),
+            prop::collection::vec((any::<u128>(), 0..u32::from(bits)), 1..8),
+        )
+            .prop_map(move |(base, others)| {
+                let base = base >> shift;
+                others
+                    .into_iter()
+                    .map(|(other, bit)| base ^ ((other >> shift) >> bit))
+                    .collect()
+            })
+    }
+
+    fn query() -> impl Strategy<Value = Query> {
+        prop_oneof![
+            Just(Query::Match(MatchType::ExactMatch)),
+            Just(Query::Match(MatchType::LongestMatch)),
+            Just(Query::Match(MatchType::EmptyMatch)),
+            Just(Query::MoreSpecificsFrom),
+            Just(Query::LessSpecificsFrom),
+        ]
+    }
+
+    fn ops(bits: u8) -> impl Strategy<Value = Vec<Op>> {
+        prop::collection::vec(
+            prop_oneof![
+                (any::<Index>(), 0..=bits, 0_u32..16)
+                    .prop_map(|(addr, len, asn)| Op::Insert(addr, len, asn)),
+                (any::<Index>(), 0..=bits, query())
+                    .prop_map(|(addr, len, q)| Op::Query(addr, len, q)),
+            ],
+            1..100,
+        )
+    }
+
+    fn options(match_type: &MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type: match_type.clone(),
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    fn outcome(
+        store: &impl PrefixStore<PrefixAs>,
+        prefix: &Prefix,
+        query: &Query,
+    ) -> Outcome {
+        let guard = &epoch::pin();
+        match query {
+            Query::Match(match_type) => {
+                store.match_prefix(prefix, &options(match_type), guard)
+            }
+            Query::MoreSpecificsFrom => {
+                store.more_specifics_from(prefix, guard)
+            }
+            Query::LessSpecificsFrom => {
+                store.less_specifics_from(prefix, guard)
+            }
+        }
+        .into()
+    }
+
+    fn check(
+        multi: &MultiThreadedStore<PrefixAs>,
+        single: &SingleThreadedStore<PrefixAs>,
+        reference: &Reference,
+        prefix: &Prefix,
+        query: &Query,
+    ) -> Result<(), TestCaseError> {
+        let expected = reference.query(prefix, query);
+        prop_assert_eq!(
+            &outcome(multi, prefix, query),
+            &expected,
+            "multi-threaded store, {:?} {}",
+            query,
+            prefix
+        );
+        prop_assert_eq!(
+            &outcome(single, prefix, query),
+            &expected,
+            "single-threaded store, {:?} {}",
+            query,
+            prefix
+        );
+        Ok(())
+    }
+
+    // Runs the operations on both stores and the reference, checking
+    // every query on the way, and in the end every kind of query for every
+    // prefix that was inserted.
+    fn differential(
+        bits: u8,
+        addrs: &[u128],
+        ops: &[Op],
+    ) -> Result<(), TestCaseError> {
+        let multi = MultiThreadedStore::<PrefixAs>::new().unwrap();
+        // Uneven strides, but each set adds up to 32 bits, so that it
+        // fills both address families when repeated.
+        let mut single = SingleThreadedStore::<PrefixAs>::new(
+            vec![3, 4, 5, 6, 7, 7],
+            vec![8, 7, 6, 5, 3, 3],
+        );
+        let mut reference = Reference::new(bits);
+        let pfx = |addr: &Index, len: u8| {
+            to_prefix(bits, addrs[addr.index(addrs.len())], len)
+        };
+
+        for op in ops {
+            match op {
+                Op::Insert(addr, len, asn) => {
+                    let prefix = pfx(addr, *len);
+                    multi.insert(&prefix, PrefixAs(*asn)).unwrap();
+                    single.insert(&prefix, PrefixAs(*asn)).unwrap();
+                    reference.insert(&prefix, *asn);
+                }
+                Op::Query(addr, len, query) => {
+                    let prefix = pfx(addr, *len);
+                    check(&multi, &single, &reference, &prefix, query)?;
+                }
+            }
+        }
+        prop_assert_eq!(multi.prefixes_len(), reference.rib.len());
+        prop_assert_eq!(single.prefixes_len(), reference.rib.len());
+
+        let queries = [
+            Query::Match(MatchType::ExactMatch),
+            Query::Match(MatchType::LongestMatch),
+            Query::Match(MatchType::EmptyMatch),
+            Query::MoreSpecificsFrom,
+            Query::LessSpecificsFrom,
+        ];
+        for (addr, len) in reference.rib.keys() {
+            let prefix = to_prefix(bits, *addr, *len);
+            for query in &queries {
+                check(&multi, &single, &reference, &prefix, query)?;
+            }
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_differential_v4(addrs in addrs(32), ops in ops(32)) {
+            differential(32, &addrs, &ops)?;
+        }
+
+        #[test]
+        fn test_differential_v6(addrs in addrs(128), ops in ops(128)) {
+            differential(128, &addrs, &ops)?;
+        }
+    }
+}
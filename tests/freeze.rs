@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        QueryResult,
+    };
+    use routecore::addr::Prefix;
+    use routecore::bgp::RecordSet;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn stored_prefixes() -> Vec<Prefix> {
+        [
+            "10.0.0.0/8",
+            "10.1.0.0/16",
+            "10.1.1.0/24",
+            "10.1.1.128/25",
+            "10.2.0.0/16",
+            "2001:db8::/32",
+            "2001:db8:1::/48",
+            "2001:db8:1:1::/64",
+            "2001:db8:1:1::/127",
+            "2001:db8:2::/48",
+        ]
+        .iter()
+        .map(|p| pfx(p))
+        .collect()
+    }
+
+    fn search_prefixes() -> Vec<Prefix> {
+        [
+            "10.0.0.0/8",
+            "10.1.1.0/24",
+            "10.1.1.129/32",
+            "10.3.0.0/16",
+            "11.0.0.0/8",
+            "2001:db8::/32",
+            "2001:db8:1:1::1/128",
+            "2001:db8:3::/48",
+        ]
+        .iter()
+        .map(|p| pfx(p))
+        .collect()
+    }
+
+    fn options() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::LongestMatch,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    // The parts of a query result that can outlive the store.
+    type Summary = (Option<Prefix>, Option<u32>, Vec<Prefix>, Vec<Prefix>);
+
+    fn summary(res: QueryResult<PrefixAs>) -> Summary {
+        let sorted = |record_set: Option<RecordSet<PrefixAs>>| {
+            let mut pfxs = record_set
+                .unwrap()
+                .iter()
+                .map(|r| r.prefix)
+                .collect::<Vec<_>>();
+            pfxs.sort_by_key(|p| (p.addr(), p.len()));
+            pfxs
+        };
+        (
+            res.prefix,
+            res.prefix_meta.map(|m| m.0),
+            sorted(res.less_specifics),
+            sorted(res.more_specifics),
+        )
+    }
+
+    #[test]
+    fn test_freeze() -> Result<(), Box<dyn Error>> {
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        for (i, p) in stored_prefixes().iter().enumerate() {
+            store.insert(p, PrefixAs(i as u32))?;
+        }
+
+        let guard = &epoch::pin();
+        let expected = search_prefixes()
+            .iter()
+            .map(|p| summary(store.match_prefix(p, &options(), guard)))
+            .collect::<Vec<_>>();
+
+        let frozen = store.freeze()?;
+        assert_eq!(frozen.prefixes_len(), stored_prefixes().len());
+        assert_eq!(frozen.prefixes_v4_len(), 5);
+        assert_eq!(frozen.prefixes_iter().count(), stored_prefixes().len());
+
+        for (p, exp) in search_prefixes().iter().zip(expected) {
+            assert_eq!(summary(frozen.match_prefix(p, &options())), exp);
+        }
+
+        let res = frozen.more_specifics_from(&pfx("10.1.0.0/16"));
+        assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+        assert_eq!(res.more_specifics.unwrap().len(), 2);
+
+        let res = frozen.less_specifics_from(&pfx("2001:db8:1:1::/127"));
+        assert_eq!(res.prefix, Some(pfx("2001:db8:1:1::/127")));
+        assert_eq!(res.less_specifics.unwrap().len(), 3);
+
+        Ok(())
+    }
+}
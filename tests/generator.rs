@@ -0,0 +1,163 @@
+#[cfg(all(test, feature = "generator"))]
+mod tests {
+    use rotonda_store::generator::{Generator, DFZ_V4_LENGTHS};
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+
+    use std::collections::HashSet;
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn options(match_type: MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        }
+    }
+
+    // Whether `prefix` covers `other`.
+    fn covers(prefix: &Prefix, other: &Prefix) -> bool {
+        if prefix.len() > other.len() {
+            return false;
+        }
+        match (prefix.addr(), other.addr()) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => {
+                let shift = 32 - u32::from(prefix.len());
+                u32::from(a).checked_shr(shift).unwrap_or(0)
+                    == u32::from(b).checked_shr(shift).unwrap_or(0)
+            }
+            (IpAddr::V6(a), IpAddr::V6(b)) => {
+                let shift = 128 - u32::from(prefix.len());
+                u128::from(a).checked_shr(shift).unwrap_or(0)
+                    == u128::from(b).checked_shr(shift).unwrap_or(0)
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_generator_seeded() {
+        let table = |seed| {
+            Generator::new(seed)
+                .v4_prefixes(2_000)
+                .v6_prefixes(500)
+                .generate()
+                .into_iter()
+                .map(|record| (record.prefix, record.meta.0))
+                .collect::<Vec<_>>()
+        };
+        let first = table(7);
+        assert_eq!(first, table(7));
+        assert_ne!(first, table(8));
+
+        assert_eq!(first.len(), 2_500);
+        let unique = first
+            .iter()
+            .map(|(p, _)| (p.addr(), p.len()))
+            .collect::<HashSet<_>>();
+        assert_eq!(unique.len(), first.len());
+        let v4 = first.iter().filter(|(p, _)| p.addr().is_ipv4()).count();
+        assert_eq!(v4, 2_000);
+        assert!(first.iter().all(|(p, asn)| {
+            *asn >= 1
+                && *asn <= 10_000
+                && match p.addr() {
+                    IpAddr::V4(_) => {
+                        DFZ_V4_LENGTHS.iter().any(|(len, _)| *len == p.len())
+                    }
+                    IpAddr::V6(addr) => addr.segments()[0] >> 13 == 1,
+                }
+        }));
+    }
+
+    // The number of prefixes covered by another one with the same
+    // origin.
+    fn covered_with_origin(generator: Generator) -> usize {
+        let records = generator.generate();
+        assert_eq!(records.len(), 1_000);
+        records
+            .iter()
+            .filter(|record| {
+                records.iter().any(|other| {
+                    other.prefix != record.prefix
+                        && covers(&other.prefix, &record.prefix)
+                        && other.meta.0 == record.meta.0
+                })
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_generator_nesting() {
+        let generator = Generator::new(1)
+            .v4_prefixes(1_000)
+            .v6_prefixes(0)
+            .v4_lengths(&[(16, 1), (20, 1), (24, 1)])
+            .same_origin(100);
+
+        // Half of the prefixes are tried as more-specifics, but those of a
+        // /24 or at the maximum depth don't work out.
+        let nested =
+            covered_with_origin(generator.clone().nesting(50).max_depth(2));
+        assert!(nested > 200, "{} nested", nested);
+
+        let flat = covered_with_origin(generator.nesting(0));
+        assert!(flat < 10, "{} nested", flat);
+    }
+
+    #[test]
+    fn test_full_table_synthetic() -> Result<(), Box<dyn Error>> {
+        let records = Generator::new(42)
+            .v4_prefixes(20_000)
+            .v6_prefixes(4_000)
+            .generate();
+
+        let multi = MultiThreadedStore::<PrefixAs>::new()?;
+        let mut single =
+            SingleThreadedStore::<PrefixAs>::new(vec![4], vec![4]);
+        for record in &records {
+            multi.insert(&record.prefix, *record.meta)?;
+            single.insert(&record.prefix, *record.meta)?;
+        }
+        assert_eq!(multi.prefixes_len(), records.len());
+        assert_eq!(single.prefixes_len(), records.len());
+
+        let guard = &epoch::pin();
+        let exact = options(MatchType::ExactMatch);
+        for record in &records {
+            for res in [
+                multi.match_prefix(&record.prefix, &exact, guard),
+                single.match_prefix(&record.prefix, &exact),
+            ] {
+                assert_eq!(res.prefix, Some(record.prefix));
+                assert_eq!(res.prefix_meta.map(|m| m.0), Some(record.meta.0));
+            }
+        }
+
+        // The longest match for the prefixes of another table covers them,
+        // and nothing in the table is a longer match.
+        let longest = options(MatchType::LongestMatch);
+        let others = Generator::new(43)
+            .v4_prefixes(1_000)
+            .v6_prefixes(250)
+            .generate();
+        for other in &others {
+            let res = multi.match_prefix(&other.prefix, &longest, guard);
+            let expected = records
+                .iter()
+                .filter(|record| covers(&record.prefix, &other.prefix))
+                .map(|record| record.prefix)
+                .max_by_key(|prefix| prefix.len());
+            assert_eq!(res.prefix, expected);
+            let res = single.match_prefix(&other.prefix, &longest);
+            assert_eq!(res.prefix, expected);
+        }
+
+        Ok(())
+    }
+}
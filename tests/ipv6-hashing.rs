@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+
+    use std::collections::HashMap;
+    use std::error::Error;
+    use std::net::{IpAddr, Ipv6Addr};
+
+    fn pfx(bits: u128, len: u8) -> Prefix {
+        Prefix::new_relaxed(IpAddr::V6(Ipv6Addr::from(bits)), len).unwrap()
+    }
+
+    fn exact() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::ExactMatch,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        }
+    }
+
+    // Random IPv6 prefixes of length 48 up to and including 128, each
+    // with the index of its first occurrence as meta-data.
+    fn prefixes() -> impl Strategy<Value = HashMap<Prefix, u32>> {
+        prop::collection::vec((any::<u128>(), 48_u8..=128), 1..200).prop_map(
+            |pfxs| {
+                let mut map = HashMap::new();
+                for (i, (bits, len)) in pfxs.into_iter().enumerate() {
+                    map.entry(pfx(bits, len)).or_insert(i as u32);
+                }
+                map
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn test_exact_match_round_trip_mt(pfxs in prefixes()) {
+            let tree = MultiThreadedStore::<PrefixAs>::new().unwrap();
+            for (pfx, asn) in &pfxs {
+                tree.insert(pfx, PrefixAs(*asn)).unwrap();
+            }
+            prop_assert_eq!(tree.prefixes_len(), pfxs.len());
+
+            let guard = &epoch::pin();
+            for (pfx, asn) in &pfxs {
+                let res = tree.match_prefix(pfx, &exact(), guard);
+                prop_assert!(matches!(res.match_type, MatchType::ExactMatch));
+                prop_assert_eq!(res.prefix, Some(*pfx));
+                prop_assert_eq!(res.prefix_meta.map(|m| m.0), Some(*asn));
+            }
+        }
+
+        #[test]
+        fn test_exact_match_round_trip_st(pfxs in prefixes()) {
+            let mut tree =
+                SingleThreadedStore::<PrefixAs>::new(vec![4], vec![8]);
+            for (pfx, asn) in &pfxs {
+                tree.insert(pfx, PrefixAs(*asn)).unwrap();
+            }
+            prop_assert_eq!(tree.prefixes_len(), pfxs.len());
+
+            for (pfx, asn) in &pfxs {
+                let res = tree.match_prefix(pfx, &exact());
+                prop_assert!(matches!(res.match_type, MatchType::ExactMatch));
+                prop_assert_eq!(res.prefix, Some(*pfx));
+                prop_assert_eq!(res.prefix_meta.map(|m| m.0), Some(*asn));
+            }
+        }
+    }
+
+    // Prefixes that only differ in the high bits of the part after the
+    // first node used to be hashed on their low 32 bits only, and ended
+    // up on the same index.
+    #[test]
+    fn test_no_aliasing_on_low_bits() -> Result<(), Box<dyn Error>> {
+        let tree = MultiThreadedStore::<PrefixAs>::new()?;
+        let low = 0x0000_0000_0000_0000_0000_0000_dead_beef_u128;
+        let pfxs = [
+            pfx(0x2001_0db8_0000_0000_0000_0000_0000_0000 | low, 128),
+            pfx(0x2001_0db8_0001_0000_0000_0000_0000_0000 | low, 128),
+            pfx(0x2001_0db8_0000_0001_0000_0000_0000_0000 | low, 128),
+            pfx(0x2001_0db8_0000_0000_0001_0000_0000_0000 | low, 128),
+        ];
+        for (i, pfx) in pfxs.iter().enumerate() {
+            tree.insert(pfx, PrefixAs(i as u32))?;
+        }
+        assert_eq!(tree.prefixes_len(), pfxs.len());
+
+        let guard = &epoch::pin();
+        for (i, pfx) in pfxs.iter().enumerate() {
+            let res = tree.match_prefix(pfx, &exact(), guard);
+            assert_eq!(res.prefix, Some(*pfx));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(i as u32));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+#[cfg(all(test, feature = "journal"))]
+mod tests {
+    use rotonda_store::{
+        epoch, Durability, JournalError, JournaledStore, MatchOptions,
+        MatchType, PrefixAs,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::fs::OpenOptions;
+    use std::net::IpAddr;
+    use std::path::PathBuf;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn prefixes() -> Vec<Prefix> {
+        ["10.0.0.0/8", "10.1.0.0/16", "2001:db8::/32", "2001:db8:1::/48"]
+            .iter()
+            .map(|p| pfx(p))
+            .collect()
+    }
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rotonda-store-journal-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn asn_of(
+        store: &JournaledStore<PrefixAs>,
+        prefix: &Prefix,
+    ) -> Option<u32> {
+        let res = store.store().match_prefix(
+            prefix,
+            &MatchOptions {
+                match_type: MatchType::ExactMatch,
+                include_all_records: false,
+                include_less_specifics: false,
+                include_more_specifics: false,
+            },
+            &epoch::pin(),
+        );
+        res.prefix_meta.map(|m| m.0)
+    }
+
+    #[test]
+    fn test_journal_replay() -> Result<(), Box<dyn Error>> {
+        let dir = tmp_dir("replay");
+        {
+            let store = JournaledStore::open(&dir, Durability::Synced)?;
+            for (i, p) in prefixes().iter().enumerate() {
+                store.insert(p, PrefixAs(i as u32))?;
+            }
+        }
+
+        let store =
+            JournaledStore::<PrefixAs>::open(&dir, Durability::Synced)?;
+        assert_eq!(store.store().prefixes_len(), prefixes().len());
+        for (i, p) in prefixes().iter().enumerate() {
+            assert_eq!(asn_of(&store, p), Some(i as u32));
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_torn_tail() -> Result<(), Box<dyn Error>> {
+        let dir = tmp_dir("torn");
+        {
+            let store = JournaledStore::open(&dir, Durability::Written)?;
+            for (i, p) in prefixes().iter().enumerate() {
+                store.insert(p, PrefixAs(i as u32))?;
+            }
+        }
+
+        // Cut the last entry in half, as a crash in the middle of writing
+        // it would.
+        let file =
+            OpenOptions::new().write(true).open(dir.join("journal"))?;
+        let len = file.metadata()?.len();
+        file.set_len(len - 5)?;
+        drop(file);
+
+        let store = JournaledStore::open(&dir, Durability::Written)?;
+        assert_eq!(store.store().prefixes_len(), prefixes().len() - 1);
+        assert_eq!(asn_of(&store, &prefixes()[3]), None);
+
+        // New entries go after the last valid one, not after the torn one.
+        store.insert(&prefixes()[3], PrefixAs(42))?;
+        drop(store);
+        let store = JournaledStore::open(&dir, Durability::Written)?;
+        assert_eq!(store.store().prefixes_len(), prefixes().len());
+        assert_eq!(asn_of(&store, &prefixes()[3]), Some(42));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_damaged_entry() -> Result<(), Box<dyn Error>> {
+        let dir = tmp_dir("damaged");
+        {
+            let store = JournaledStore::open(&dir, Durability::Synced)?;
+            for (i, p) in prefixes().iter().enumerate() {
+                store.insert(p, PrefixAs(i as u32))?;
+            }
+        }
+
+        // Flip a bit in the address of the first entry, right after the
+        // header of 16 bytes, 8 bytes of framing and 3 of prefix header.
+        let path = dir.join("journal");
+        let mut bytes = std::fs::read(&path)?;
+        bytes[16 + 8 + 3] ^= 1;
+        std::fs::write(&path, &bytes)?;
+
+        // The entries behind the damaged one were synced, so this can't
+        // be a torn tail and the journal must be left alone.
+        assert!(matches!(
+            JournaledStore::<PrefixAs>::open(&dir, Durability::Synced),
+            Err(JournalError::Corrupt(_))
+        ));
+        assert_eq!(std::fs::read(&path)?, bytes);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_snapshot() -> Result<(), Box<dyn Error>> {
+        let dir = tmp_dir("snapshot");
+        let pfxs = prefixes();
+        {
+            let store = JournaledStore::open(&dir, Durability::Synced)?;
+            store.insert(&pfxs[0], PrefixAs(0))?;
+            store.insert(&pfxs[1], PrefixAs(1))?;
+            store.snapshot()?;
+            store.insert(&pfxs[2], PrefixAs(2))?;
+        }
+
+        let store = JournaledStore::open(&dir, Durability::Synced)?;
+        assert_eq!(store.store().prefixes_len(), 3);
+        for (i, p) in pfxs[..3].iter().enumerate() {
+            assert_eq!(asn_of(&store, p), Some(i as u32));
+        }
+        // Only the insert after the snapshot is in the journal: a header
+        // of 16 bytes, and an entry of 8 bytes of framing, 3 bytes of
+        // prefix header, 16 bytes of address and 4 bytes of meta-data.
+        assert_eq!(std::fs::metadata(dir.join("journal"))?.len(), 16 + 31);
+        store.snapshot()?;
+        drop(store);
+
+        // A damaged snapshot is an error, not a silently smaller store.
+        let file =
+            OpenOptions::new().write(true).open(dir.join("snapshot"))?;
+        let len = file.metadata()?.len();
+        file.set_len(len - 1)?;
+        drop(file);
+        assert!(matches!(
+            JournaledStore::<PrefixAs>::open(&dir, Durability::Synced),
+            Err(JournalError::Corrupt(_))
+        ));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use rotonda_store::loader::{LoadError, Loader};
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+    };
+    use routecore::addr::Prefix;
+    use routecore::bgp::PrefixRecord;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn load(
+        loader: Loader,
+        source: &str,
+    ) -> Result<Vec<PrefixRecord<'static, PrefixAs>>, LoadError> {
+        loader.load(source.as_bytes())
+    }
+
+    fn exact() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::ExactMatch,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        }
+    }
+
+    const CSV: &str = "\
+prefix,asn,descr
+10.0.0.0/8,64496,\"a, b\"
+2001:db8::/32,64497,c
+";
+
+    #[cfg(feature = "serde_json")]
+    const JSON_LINES: &str = r#"
+{"prefix": "10.0.0.0/8", "asn": 64496, "descr": "a, b"}
+
+{"asn": 64497, "prefix": "2001:db8::/32", "descr": null}
+"#;
+
+    fn check(store: &impl PrefixStore<PrefixAs>) {
+        let guard = &epoch::pin();
+        assert_eq!(store.prefixes_len(), 2);
+        for (p, asn) in [("10.0.0.0/8", 64496), ("2001:db8::/32", 64497)] {
+            let res = store.match_prefix(&pfx(p), &exact(), guard);
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(asn));
+        }
+    }
+
+    #[test]
+    fn test_load_csv() -> Result<(), Box<dyn Error>> {
+        let records = load(Loader::csv(), CSV)?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].prefix, pfx("2001:db8::/32"));
+
+        let mut store = MultiThreadedStore::<PrefixAs>::new()?;
+        let count = Loader::csv().load_into(CSV.as_bytes(), &mut store)?;
+        assert_eq!(count, 2);
+        check(&store);
+
+        // Separate columns for the address and the length, without a
+        // header line.
+        let loader = Loader::csv()
+            .has_headers(false)
+            .columns(&["ip", "len", "asn"])
+            .prefix_column("ip")
+            .len_column("len");
+        let records = load(loader, "192.0.2.0,24,1\n2001:db8::,48,2\n")?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].prefix, pfx("192.0.2.0/24"));
+        assert_eq!(records[1].prefix, pfx("2001:db8::/48"));
+        assert_eq!(records[1].meta.0, 2);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_load_json_lines() -> Result<(), Box<dyn Error>> {
+        use rotonda_store::SingleThreadedStore;
+
+        let mut store =
+            SingleThreadedStore::<PrefixAs>::new(vec![4], vec![4]);
+        let json_lines = Loader::json_lines();
+        let count =
+            json_lines.load_into(JSON_LINES.as_bytes(), &mut store)?;
+        assert_eq!(count, 2);
+        check(&store);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let csv = "prefix,asn\n10.0.0.0/8,1\n10.0.0.0/33,2\n";
+        assert!(matches!(
+            load(Loader::csv(), csv),
+            Err(LoadError::InvalidValue { line: 3, .. })
+        ));
+
+        let csv = "prefix,as\n10.0.0.0/8,1\n";
+        assert!(matches!(
+            load(Loader::csv(), csv),
+            Err(LoadError::MissingColumn { line: 2, .. })
+        ));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_load_json_lines_errors() {
+        // Host bits set.
+        let json = "{\"prefix\": \"10.0.0.1/8\", \"asn\": 1}";
+        assert!(matches!(
+            load(Loader::json_lines(), json),
+            Err(LoadError::InvalidValue { line: 1, .. })
+        ));
+
+        let json = "{\"prefix\": \"10.0.0.0/8\", \"asn\": 1}\n{\"a\": [";
+        assert!(matches!(
+            load(Loader::json_lines(), json),
+            Err(LoadError::Syntax { line: 2, .. })
+        ));
+
+        let json = "{\"prefix\": \"10.0.0.0/8\", \"asn\": 1, \"a\": [1]}";
+        assert!(matches!(
+            load(Loader::json_lines(), json),
+            Err(LoadError::Syntax { line: 1, .. })
+        ));
+
+        // A high surrogate without a low one after it, and a sign in
+        // the hex digits.
+        for descr in [r"\ud800\u0041", r"\ud800\ud800", r"\u+041"] {
+            let json = format!(
+                "{{\"prefix\": \"10.0.0.0/8\", \"asn\": 1, \
+                 \"descr\": \"{}\"}}",
+                descr
+            );
+            assert!(matches!(
+                load(Loader::json_lines(), &json),
+                Err(LoadError::Syntax { line: 1, .. })
+            ));
+        }
+        let json =
+            r#"{"prefix": "10.0.0.0/8", "asn": 1, "descr": "\ud83d\ude00"}"#;
+        assert!(load(Loader::json_lines(), json).is_ok());
+    }
+}
@@ -0,0 +1,222 @@
+// Model tests for the lock-free parts of the MultiThreadedStore. They only
+// build with loom, which runs them for every interleaving of the threads it
+// can come up with, and take a while:
+//
+// RUSTFLAGS="--cfg loom --cfg crossbeam_loom" \
+//     cargo test --release --test loom
+#[cfg(all(test, loom))]
+mod tests {
+    use loom::model::Builder;
+    use loom::sync::Arc;
+    use loom::thread;
+    use rotonda_store::{
+        prelude::*, BitPrefix, MatchOptions, MatchType,
+        MultiThreadedBitPrefixStore, MultiThreadedStore, PrefixAs,
+    };
+    use routecore::addr::Prefix;
+
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn options(match_type: MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        }
+    }
+
+    // Every insert goes through a few hundred atomic operations, far more
+    // than loom allows by default. Bounding the preemptions keeps the
+    // number of interleavings manageable.
+    fn model(f: impl Fn() + Sync + Send + 'static) {
+        let mut builder = Builder::new();
+        builder.max_branches = 100_000;
+        builder.preemption_bound = Some(2);
+        builder.check(f);
+    }
+
+    fn store() -> Arc<MultiThreadedStore<PrefixAs>> {
+        Arc::new(MultiThreadedStore::new().unwrap())
+    }
+
+    // Two writers race in `upsert_prefix` for the same prefix. It ends up
+    // in the store once, with the meta-data of either of them.
+    #[test]
+    fn test_loom_insert_same_prefix() {
+        model(|| {
+            let store = store();
+            let writers = [1, 2]
+                .into_iter()
+                .map(|asn| {
+                    let store = store.clone();
+                    thread::spawn(move || {
+                        store
+                            .insert(&pfx("10.0.0.0/8"), PrefixAs(asn))
+                            .unwrap();
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+
+            assert_eq!(store.prefixes_len(), 1);
+            let guard = &epoch::pin();
+            let res = store.match_prefix(
+                &pfx("10.0.0.0/8"),
+                &options(MatchType::ExactMatch),
+                guard,
+            );
+            assert_eq!(res.prefix, Some(pfx("10.0.0.0/8")));
+            let asn = res.prefix_meta.map(|meta| meta.0);
+            assert!(matches!(asn, Some(1) | Some(2)), "{:?}", asn);
+        });
+    }
+
+    // Two writers insert prefixes that both need the node below 10.0.0.0/8,
+    // so they race in `store_node_closure!` to create the node with the
+    // same `StrideNodeId`, and then in the CAS loops on its bitmaps. Both
+    // prefixes have to survive that.
+    #[test]
+    fn test_loom_create_same_node() {
+        model(|| {
+            let store = store();
+            let writers = ["10.1.0.0/16", "10.2.0.0/16"]
+                .into_iter()
+                .enumerate()
+                .map(|(asn, prefix)| {
+                    let store = store.clone();
+                    thread::spawn(move || {
+                        store
+                            .insert(&pfx(prefix), PrefixAs(asn as u32))
+                            .unwrap();
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+
+            assert_eq!(store.prefixes_len(), 2);
+            let guard = &epoch::pin();
+            for (asn, prefix) in ["10.1.0.0/16", "10.2.0.0/16"]
+                .into_iter()
+                .enumerate()
+            {
+                let res = store.match_prefix(
+                    &pfx(prefix),
+                    &options(MatchType::ExactMatch),
+                    guard,
+                );
+                assert_eq!(res.prefix, Some(pfx(prefix)));
+                assert_eq!(
+                    res.prefix_meta.map(|meta| meta.0),
+                    Some(asn as u32)
+                );
+            }
+            let res = store.more_specifics_from(&pfx("10.0.0.0/8"), guard);
+            assert_eq!(
+                res.more_specifics.map(|set| set.iter().count()),
+                Some(2)
+            );
+        });
+    }
+
+    // A reader does a longest match while a writer inserts a prefix and
+    // then a more-specific of it. The reader sees the store before, in
+    // between or after the inserts, never half an insert.
+    #[test]
+    fn test_loom_read_during_write() {
+        model(|| {
+            let store = store();
+            let writer = {
+                let store = store.clone();
+                thread::spawn(move || {
+                    store.insert(&pfx("10.1.0.0/16"), PrefixAs(1)).unwrap();
+                    store.insert(&pfx("10.1.1.0/24"), PrefixAs(2)).unwrap();
+                })
+            };
+
+            let guard = &epoch::pin();
+            let res = store.match_prefix(
+                &pfx("10.1.1.1/32"),
+                &options(MatchType::LongestMatch),
+                guard,
+            );
+            let found = res.prefix.zip(res.prefix_meta.map(|meta| meta.0));
+            assert!(
+                found.is_none()
+                    || found == Some((pfx("10.1.0.0/16"), 1))
+                    || found == Some((pfx("10.1.1.0/24"), 2)),
+                "{:?}",
+                found
+            );
+
+            // The /16 went in before the /24, so once the reader has seen
+            // the /24 it sees the /16 as well.
+            let exact = options(MatchType::ExactMatch);
+            let res = store.match_prefix(&pfx("10.1.1.0/24"), &exact, guard);
+            if res.prefix.is_some() {
+                let res =
+                    store.match_prefix(&pfx("10.1.0.0/16"), &exact, guard);
+                assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+            }
+
+            writer.join().unwrap();
+        });
+    }
+
+    // Like `test_loom_create_same_node`, but on the bit prefix store. That
+    // store has no code generated by `rotonda-macros`, so loom sees every
+    // atomic the inserts go through.
+    #[test]
+    fn test_loom_bit_prefix_store() {
+        model(|| {
+            let store = Arc::new(
+                MultiThreadedBitPrefixStore::<u32, PrefixAs>::new().unwrap(),
+            );
+            let prefixes = [
+                BitPrefix::new(0x0a01_0000, 16).unwrap(),
+                BitPrefix::new(0x0a02_0000, 16).unwrap(),
+            ];
+            let writers = prefixes
+                .into_iter()
+                .enumerate()
+                .map(|(asn, prefix)| {
+                    let store = store.clone();
+                    thread::spawn(move || {
+                        store.insert(prefix, PrefixAs(asn as u32)).unwrap();
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+
+            assert_eq!(store.prefixes_len(), 2);
+            let guard = &epoch::pin();
+            for (asn, prefix) in prefixes.into_iter().enumerate() {
+                let res = store.match_prefix(
+                    prefix,
+                    &options(MatchType::ExactMatch),
+                    guard,
+                );
+                assert_eq!(res.prefix, Some(prefix));
+                assert_eq!(
+                    res.prefix_meta.map(|meta| meta.0),
+                    Some(asn as u32)
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,197 @@
+// Runs the same queries against a MappedStore and the store it was written
+// from, and checks that they return the same results.
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MappedStore, MappedStoreError, MatchOptions, MatchType,
+        MultiThreadedStore, PrefixAs, SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+    use routecore::bgp::RecordSet;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+    use std::path::PathBuf;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn stored_prefixes() -> Vec<Prefix> {
+        [
+            "10.0.0.0/8",
+            "10.1.0.0/16",
+            "10.1.1.0/24",
+            "10.1.1.128/25",
+            "10.2.0.0/16",
+            "255.255.255.255/32",
+            "2001:db8::/32",
+            "2001:db8:1::/48",
+            "2001:db8:1:1::/64",
+            "2001:db8:1:1::/127",
+            "2001:db8:2::/48",
+            "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff/128",
+        ]
+        .iter()
+        .map(|p| pfx(p))
+        .collect()
+    }
+
+    fn search_prefixes() -> Vec<Prefix> {
+        [
+            "10.0.0.0/8",
+            "10.1.1.0/24",
+            "10.1.1.129/32",
+            "10.3.0.0/16",
+            "11.0.0.0/8",
+            "255.255.255.0/24",
+            "2001:db8::/32",
+            "2001:db8:1:1::1/128",
+            "2001:db8:3::/48",
+        ]
+        .iter()
+        .map(|p| pfx(p))
+        .collect()
+    }
+
+    fn sorted(
+        record_set: Option<RecordSet<PrefixAs>>,
+    ) -> Vec<(Prefix, u32)> {
+        let mut records = record_set
+            .map(|rs| {
+                rs.iter().map(|r| (r.prefix, r.meta.0)).collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        records.sort_by_key(|(p, _)| (p.addr(), p.len()));
+        records
+    }
+
+    fn options(match_type: MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    fn tmp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "rotonda-store-{}-{}.map",
+            name,
+            std::process::id()
+        ))
+    }
+
+    fn same_results(
+        store: &mut impl PrefixStoreMut<PrefixAs>,
+        name: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        for (i, p) in stored_prefixes().iter().enumerate() {
+            store.insert(p, PrefixAs(i as u32))?;
+        }
+
+        let path = tmp_path(name);
+        MappedStore::write_file(&*store, &path)?;
+        let mapped = MappedStore::<PrefixAs>::open(&path)?;
+
+        assert_eq!(mapped.prefixes_len(), stored_prefixes().len());
+        assert_eq!(mapped.v4_prefixes_len(), 6);
+        assert_eq!(mapped.prefixes_iter().count(), stored_prefixes().len());
+
+        let guard = &epoch::pin();
+        for search in search_prefixes() {
+            for match_type in [
+                MatchType::ExactMatch,
+                MatchType::LongestMatch,
+                MatchType::EmptyMatch,
+            ] {
+                let options = options(match_type);
+                let exp = store.match_prefix(&search, &options, guard);
+                let res = mapped.match_prefix(&search, &options);
+                assert_eq!(res.prefix, exp.prefix, "{}", search);
+                assert_eq!(
+                    res.prefix_meta.map(|m| m.0),
+                    exp.prefix_meta.map(|m| m.0)
+                );
+                assert_eq!(
+                    format!("{}", res.match_type),
+                    format!("{}", exp.match_type)
+                );
+                assert_eq!(
+                    sorted(res.less_specifics),
+                    sorted(exp.less_specifics),
+                    "{}",
+                    search
+                );
+                assert_eq!(
+                    sorted(res.more_specifics),
+                    sorted(exp.more_specifics),
+                    "{}",
+                    search
+                );
+            }
+
+            let exp = store.more_specifics_from(&search, guard);
+            let res = mapped.more_specifics_from(&search);
+            assert_eq!(res.prefix, exp.prefix);
+            assert_eq!(
+                sorted(res.more_specifics),
+                sorted(exp.more_specifics)
+            );
+
+            let exp = store.less_specifics_from(&search, guard);
+            let res = mapped.less_specifics_from(&search);
+            assert_eq!(res.prefix, exp.prefix);
+            assert_eq!(
+                sorted(res.less_specifics),
+                sorted(exp.less_specifics)
+            );
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mapped_multi_threaded() -> Result<(), Box<dyn Error>> {
+        same_results(&mut MultiThreadedStore::<PrefixAs>::new()?, "mt")
+    }
+
+    #[test]
+    fn test_mapped_single_threaded() -> Result<(), Box<dyn Error>> {
+        same_results(
+            &mut SingleThreadedStore::<PrefixAs>::new(vec![4], vec![8]),
+            "st",
+        )
+    }
+
+    #[test]
+    fn test_mapped_invalid_file() -> Result<(), Box<dyn Error>> {
+        let path = tmp_path("invalid");
+
+        std::fs::write(&path, b"not a mapped store file")?;
+        assert!(matches!(
+            MappedStore::<PrefixAs>::open(&path),
+            Err(MappedStoreError::InvalidFormat(_))
+        ));
+
+        // A header that claims a million IPv4 prefixes.
+        let mut bytes = b"RTSTMAP1".to_vec();
+        bytes.extend_from_slice(&1_000_000_u64.to_be_bytes());
+        bytes.extend_from_slice(&0_u64.to_be_bytes());
+        std::fs::write(&path, &bytes)?;
+        assert!(matches!(
+            MappedStore::<PrefixAs>::open(&path),
+            Err(MappedStoreError::Truncated)
+        ));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
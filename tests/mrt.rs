@@ -0,0 +1,130 @@
+#[cfg(all(test, feature = "mrt"))]
+mod tests {
+    use rotonda_store::mrt::{self, MrtError};
+    use rotonda_store::{
+        epoch, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn asn_of(
+        store: &MultiThreadedStore<PrefixAs>,
+        prefix: &Prefix,
+    ) -> Option<u32> {
+        let res = store.match_prefix(
+            prefix,
+            &MatchOptions {
+                match_type: MatchType::ExactMatch,
+                include_all_records: false,
+                include_less_specifics: false,
+                include_more_specifics: false,
+            },
+            &epoch::pin(),
+        );
+        res.prefix_meta.map(|m| m.0)
+    }
+
+    fn record(subtype: u16, body: &[u8]) -> Vec<u8> {
+        let mut record = vec![0, 0, 0, 0, 0, 13];
+        record.extend_from_slice(&subtype.to_be_bytes());
+        record.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        record.extend_from_slice(body);
+        record
+    }
+
+    #[test]
+    fn test_mrt_round_trip() -> Result<(), Box<dyn Error>> {
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        let pfxs = [
+            pfx("10.0.0.0/8"),
+            pfx("10.1.128.0/17"),
+            pfx("192.0.2.1/32"),
+            pfx("2001:db8::/32"),
+            pfx("2001:db8:8000::/33"),
+        ];
+        for (i, p) in pfxs.iter().enumerate() {
+            store.insert(p, PrefixAs(64496 + i as u32))?;
+        }
+
+        let mut bytes = vec![];
+        mrt::dump(&store, &mut bytes, |_, meta| {
+            mrt::origin_as_attributes(meta.0)
+        })?;
+
+        let loaded = MultiThreadedStore::<PrefixAs>::new()?;
+        let count = mrt::load(&loaded, &bytes[..], |entry| {
+            assert_eq!(entry.peer.asn, 0);
+            entry.origin_as().map(PrefixAs)
+        })?;
+        assert_eq!(count, pfxs.len());
+        assert_eq!(loaded.prefixes_len(), pfxs.len());
+        for (i, p) in pfxs.iter().enumerate() {
+            assert_eq!(asn_of(&loaded, p), Some(64496 + i as u32));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mrt_peer_index_table() -> Result<(), Box<dyn Error>> {
+        // Two peers: one with an IPv4 address and a 2-byte AS number, one
+        // with an IPv6 address and a 4-byte AS number.
+        let mut peers = vec![0, 0, 0, 1, 0, 4];
+        peers.extend_from_slice(b"test");
+        peers.extend_from_slice(&[0, 2]);
+        peers.extend_from_slice(&[0x00, 0, 0, 0, 1, 192, 0, 2, 1]);
+        peers.extend_from_slice(&65000_u16.to_be_bytes());
+        peers.extend_from_slice(&[0x03, 0, 0, 0, 2]);
+        peers.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        peers.extend_from_slice(&[0; 11]);
+        peers.push(1);
+        peers.extend_from_slice(&65551_u32.to_be_bytes());
+
+        // 2001:db8:1::/48 from both peers, with an AS_PATH of two ASes
+        // from the second peer, and without one from the first.
+        let mut rib = vec![0, 0, 0, 0, 48, 0x20, 0x01, 0x0d, 0xb8, 0, 1];
+        rib.extend_from_slice(&[0, 2]);
+        rib.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 4]);
+        rib.extend_from_slice(&[0x40, 1, 1, 0]);
+        rib.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 14]);
+        rib.extend_from_slice(&[0x50, 2, 0, 10, 2, 2]);
+        rib.extend_from_slice(&65551_u32.to_be_bytes());
+        rib.extend_from_slice(&64496_u32.to_be_bytes());
+
+        let mut bytes = record(1, &peers);
+        bytes.extend_from_slice(&record(4, &rib));
+
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        let mut seen = vec![];
+        let count = mrt::load(&store, &bytes[..], |entry| {
+            seen.push(*entry.peer);
+            entry.origin_as().map(PrefixAs)
+        })?;
+        assert_eq!(count, 1);
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].asn, 65000);
+        assert_eq!(seen[0].addr, "192.0.2.1".parse::<IpAddr>()?);
+        assert_eq!(seen[1].asn, 65551);
+        assert_eq!(seen[1].addr, "2001:db8::1".parse::<IpAddr>()?);
+        assert_eq!(asn_of(&store, &pfx("2001:db8:1::/48")), Some(64496));
+
+        // A RIB record without a peer index table before it.
+        assert!(matches!(
+            mrt::load(&store, &record(4, &rib)[..], |_| Some(PrefixAs(0))),
+            Err(MrtError::InvalidFormat(_))
+        ));
+
+        Ok(())
+    }
+}
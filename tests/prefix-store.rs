@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn prefixes() -> Vec<Prefix> {
+        vec![
+            Prefix::new_relaxed(
+                std::net::Ipv4Addr::new(130, 55, 240, 0).into(),
+                24,
+            )
+            .unwrap(),
+            Prefix::new_relaxed(
+                std::net::Ipv4Addr::new(130, 55, 240, 0).into(),
+                25,
+            )
+            .unwrap(),
+            Prefix::new_relaxed(
+                std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0)
+                    .into(),
+                32,
+            )
+            .unwrap(),
+            Prefix::new_relaxed(
+                std::net::Ipv6Addr::new(0x2001, 0xdb8, 0xaa, 0, 0, 0, 0, 0)
+                    .into(),
+                48,
+            )
+            .unwrap(),
+        ]
+    }
+
+    // Inserts and queries through the PrefixStore trait only.
+    fn exercise_store(
+        store: &mut impl PrefixStoreMut<PrefixAs>,
+    ) -> Result<(), Box<dyn Error>> {
+        for (i, pfx) in prefixes().iter().enumerate() {
+            store.insert(pfx, PrefixAs(i as u32))?;
+        }
+        assert_eq!(store.prefixes_len(), 4);
+
+        let guard = &epoch::pin();
+        assert_eq!(store.prefixes_iter(guard).count(), 4);
+
+        let v4: Vec<_> =
+            store.prefixes_iter_af(AfSelector::Ipv4, guard).collect();
+        assert_eq!(v4.len(), 2);
+        assert!(v4.iter().all(|p| p.prefix.addr().is_ipv4()));
+
+        let v6: Vec<_> =
+            store.prefixes_iter_af(AfSelector::Ipv6, guard).collect();
+        assert_eq!(v6.len(), 2);
+        assert!(v6.iter().all(|p| !p.prefix.addr().is_ipv4()));
+
+        let both: Vec<_> =
+            store.prefixes_iter_af(AfSelector::Both, guard).collect();
+        assert_eq!(both.len(), 4);
+        assert!(both[..2].iter().all(|p| p.prefix.addr().is_ipv4()));
+
+        for (i, pfx) in prefixes().iter().enumerate() {
+            let res = store.match_prefix(
+                pfx,
+                &MatchOptions {
+                    match_type: MatchType::ExactMatch,
+                    include_all_records: false,
+                    include_less_specifics: false,
+                    include_more_specifics: false,
+                },
+                guard,
+            );
+            assert_eq!(res.prefix, Some(*pfx));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(i as u32));
+        }
+
+        // Longest match for a /64 in 2001:db8:aa::/48 for the IPv6 tree.
+        let res = store.match_prefix(
+            &Prefix::new_relaxed(
+                std::net::Ipv6Addr::new(0x2001, 0xdb8, 0xaa, 1, 0, 0, 0, 0)
+                    .into(),
+                64,
+            )?,
+            &MatchOptions {
+                match_type: MatchType::LongestMatch,
+                include_all_records: false,
+                include_less_specifics: false,
+                include_more_specifics: false,
+            },
+            guard,
+        );
+        assert_eq!(res.prefix, Some(prefixes()[3]));
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+
+        // The families take turns, until IPv6 runs out.
+        store.insert(
+            &Prefix::new_relaxed(
+                std::net::Ipv4Addr::new(192, 0, 2, 0).into(),
+                24,
+            )?,
+            PrefixAs(4),
+        )?;
+        let interleaved: Vec<_> = store
+            .prefixes_iter_af(AfSelector::Interleaved, guard)
+            .map(|p| p.prefix.addr().is_ipv4())
+            .collect();
+        assert_eq!(interleaved, [true, false, true, false, true]);
+
+        Ok(())
+    }
+
+    // Inserts from a thread per prefix into a store they all share, and
+    // queries it through the PrefixStore trait.
+    fn exercise_shared_store<S>(store: S) -> Result<(), Box<dyn Error>>
+    where
+        S: ConcurrentPrefixStore<PrefixAs> + Send + 'static,
+    {
+        let store = Arc::new(store);
+        let threads = prefixes()
+            .into_iter()
+            .enumerate()
+            .map(|(i, pfx)| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    store.insert(&pfx, PrefixAs(i as u32)).unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(store.prefixes_len(), 4);
+
+        let guard = &epoch::pin();
+        for (i, pfx) in prefixes().iter().enumerate() {
+            let res = store.match_prefix(
+                pfx,
+                &MatchOptions {
+                    match_type: MatchType::ExactMatch,
+                    include_all_records: false,
+                    include_less_specifics: false,
+                    include_more_specifics: false,
+                },
+                guard,
+            );
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(i as u32));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_prefix_store() -> Result<(), Box<dyn Error>> {
+        exercise_shared_store(MultiThreadedStore::<PrefixAs>::new()?)
+    }
+
+    #[test]
+    fn test_prefix_store_multi_threaded() -> Result<(), Box<dyn Error>> {
+        exercise_store(&mut MultiThreadedStore::<PrefixAs>::new()?)
+    }
+
+    #[test]
+    fn test_prefix_store_single_threaded() -> Result<(), Box<dyn Error>> {
+        exercise_store(&mut SingleThreadedStore::<PrefixAs>::new(
+            vec![3, 4, 4, 6, 7, 8],
+            vec![4],
+        ))
+    }
+}
@@ -0,0 +1,238 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn stored_prefixes() -> Vec<Prefix> {
+        [
+            "0.0.0.0/0",
+            "10.0.0.0/8",
+            "10.1.0.0/16",
+            "10.1.1.0/24",
+            "10.1.1.128/25",
+            "10.1.1.129/32",
+            "192.0.2.0/24",
+            "2001:db8::/32",
+            "2001:db8:1::/48",
+            "2001:db8:1:1::/64",
+            "2001:db8:1:1::1/128",
+        ]
+        .iter()
+        .map(|p| pfx(p))
+        .collect()
+    }
+
+    fn exact_match() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::ExactMatch,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        }
+    }
+
+    fn longest_match() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::LongestMatch,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    fn assert_present(store: &SingleThreadedStore<PrefixAs>, p: &Prefix) {
+        let res = store.match_prefix(p, &exact_match());
+        assert_eq!(res.prefix, Some(*p));
+        assert!(matches!(res.match_type, MatchType::ExactMatch));
+    }
+
+    fn assert_absent(store: &SingleThreadedStore<PrefixAs>, p: &Prefix) {
+        let res = store.match_prefix(p, &exact_match());
+        assert_eq!(res.prefix, None);
+        assert!(matches!(res.match_type, MatchType::EmptyMatch));
+        assert!(store.prefixes_iter().all(|r| r.prefix != *p));
+    }
+
+    // The number of nodes of both trees, according to their stats.
+    fn stats_nodes_len(store: &SingleThreadedStore<PrefixAs>) -> usize {
+        let stats = store.stats();
+        stats
+            .v4
+            .iter()
+            .chain(stats.v6.iter())
+            .flat_map(|stride| stride.created_nodes.iter())
+            .map(|nodes| nodes.count)
+            .sum()
+    }
+
+    #[test]
+    fn test_remove_and_compact() -> Result<(), Box<dyn Error>> {
+        let mut store =
+            SingleThreadedStore::<PrefixAs>::new(vec![3, 4, 5, 4], vec![4]);
+        for (i, p) in stored_prefixes().iter().enumerate() {
+            store.insert(p, PrefixAs(i as u32))?;
+        }
+        let nodes_len = store.nodes_len();
+        assert_eq!(stats_nodes_len(&store), nodes_len);
+
+        // Removing returns the meta-data, but only the first time.
+        assert_eq!(store.remove(&pfx("10.1.1.0/24")).map(|m| m.0), Some(3));
+        assert!(store.remove(&pfx("10.1.1.0/24")).is_none());
+        // Prefixes that were never there can't be removed.
+        assert!(store.remove(&pfx("10.1.2.0/24")).is_none());
+        assert!(store.remove(&pfx("2001:db8:2::/48")).is_none());
+
+        assert_eq!(store.remove(&pfx("0.0.0.0/0")).map(|m| m.0), Some(0));
+        assert_eq!(
+            store.remove(&pfx("2001:db8:1:1::1/128")).map(|m| m.0),
+            Some(10)
+        );
+        assert_eq!(
+            store.remove(&pfx("2001:db8:1:1::/64")).map(|m| m.0),
+            Some(9)
+        );
+
+        let removed = [
+            pfx("0.0.0.0/0"),
+            pfx("10.1.1.0/24"),
+            pfx("2001:db8:1:1::/64"),
+            pfx("2001:db8:1:1::1/128"),
+        ];
+        let remaining = stored_prefixes()
+            .into_iter()
+            .filter(|p| !removed.contains(p))
+            .collect::<Vec<_>>();
+
+        assert_eq!(store.prefixes_len(), remaining.len());
+        assert_eq!(store.prefixes_v4_len(), 5);
+        assert_eq!(store.prefixes_v6_len(), 2);
+        assert_eq!(store.removed_prefixes_len(), removed.len());
+        assert_eq!(store.prefixes_iter().count(), remaining.len());
+
+        for p in &removed {
+            assert_absent(&store, p);
+        }
+        for p in &remaining {
+            assert_present(&store, p);
+        }
+
+        // The longest match skips over the removed prefixes.
+        let res = store.match_prefix(&pfx("10.1.1.0/24"), &longest_match());
+        assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+        assert_eq!(res.less_specifics.unwrap().len(), 1);
+        assert_eq!(res.more_specifics.unwrap().len(), 2);
+
+        let res =
+            store.match_prefix(&pfx("2001:db8:1:1::1/128"), &longest_match());
+        assert_eq!(res.prefix, Some(pfx("2001:db8:1::/48")));
+
+        // Compacting reclaims the removed prefixes and the nodes that were
+        // only there for the removed IPv6 prefixes, but doesn't change the
+        // contents of the store.
+        store.compact();
+        assert_eq!(store.removed_prefixes_len(), 0);
+        assert_eq!(store.prefixes_len(), remaining.len());
+        assert!(store.nodes_len() < nodes_len);
+        assert_eq!(stats_nodes_len(&store), store.nodes_len());
+
+        let mut iter_pfxs =
+            store.prefixes_iter().map(|r| r.prefix).collect::<Vec<_>>();
+        iter_pfxs.sort_by_key(|p| (p.addr(), p.len()));
+        let mut exp_pfxs = remaining.clone();
+        exp_pfxs.sort_by_key(|p| (p.addr(), p.len()));
+        assert_eq!(iter_pfxs, exp_pfxs);
+
+        for p in &removed {
+            assert_absent(&store, p);
+        }
+        for p in &remaining {
+            assert_present(&store, p);
+        }
+
+        let res = store.match_prefix(&pfx("10.0.0.0/8"), &longest_match());
+        assert_eq!(res.prefix, Some(pfx("10.0.0.0/8")));
+        assert_eq!(res.less_specifics.unwrap().len(), 0);
+        assert_eq!(res.more_specifics.unwrap().len(), 3);
+
+        // The compacted store keeps working as before.
+        for (i, p) in removed.iter().enumerate() {
+            store.insert(p, PrefixAs(100 + i as u32))?;
+        }
+        assert_eq!(store.prefixes_len(), stored_prefixes().len());
+        for p in stored_prefixes().iter() {
+            assert_present(&store, p);
+        }
+        assert_eq!(
+            store.remove(&pfx("2001:db8:1:1::1/128")).map(|m| m.0),
+            Some(103)
+        );
+        store.compact();
+        assert_eq!(store.prefixes_len(), stored_prefixes().len() - 1);
+        assert_eq!(stats_nodes_len(&store), store.nodes_len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_everything() -> Result<(), Box<dyn Error>> {
+        let mut store =
+            SingleThreadedStore::<PrefixAs>::new(vec![4], vec![8]);
+        for (i, p) in stored_prefixes().iter().enumerate() {
+            store.insert(p, PrefixAs(i as u32))?;
+        }
+
+        for p in stored_prefixes().iter().rev() {
+            assert!(store.remove(p).is_some());
+        }
+        assert_eq!(store.prefixes_len(), 0);
+        assert_eq!(store.prefixes_iter().count(), 0);
+
+        // Only the root nodes survive.
+        store.compact();
+        assert_eq!(store.nodes_len(), 2);
+        assert_eq!(stats_nodes_len(&store), 2);
+        assert_eq!(store.removed_prefixes_len(), 0);
+
+        for p in stored_prefixes().iter() {
+            assert_absent(&store, p);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_through_prefix_store() -> Result<(), Box<dyn Error>> {
+        let p = pfx("192.0.2.0/24");
+
+        let mut store =
+            SingleThreadedStore::<PrefixAs>::new(vec![4], vec![4]);
+        PrefixStoreMut::insert(&mut store, &p, PrefixAs(1))?;
+        assert_eq!(
+            PrefixStoreMut::remove(&mut store, &p)?.map(|m| m.0),
+            Some(1)
+        );
+        assert!(PrefixStoreMut::remove(&mut store, &p)?.is_none());
+
+        // The multi-threaded store doesn't do removal.
+        let mut store = MultiThreadedStore::<PrefixAs>::new()?;
+        PrefixStoreMut::insert(&mut store, &p, PrefixAs(1))?;
+        assert!(PrefixStoreMut::remove(&mut store, &p).is_err());
+
+        Ok(())
+    }
+}
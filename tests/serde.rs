@@ -0,0 +1,77 @@
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use rotonda_store::{
+        prelude::*, AfSelector, MatchOptions, MatchType, PrefixAs,
+        SingleThreadedStore,
+    };
+    use routecore::addr::Prefix;
+    use serde_json::json;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_query_result_json() -> Result<(), Box<dyn Error>> {
+        let mut store =
+            SingleThreadedStore::<PrefixAs>::new(vec![4], vec![4]);
+        store.insert(&pfx("192.0.0.0/16"), PrefixAs(1))?;
+        store.insert(&pfx("192.0.2.0/24"), PrefixAs(2))?;
+        store.insert(&pfx("192.0.2.0/25"), PrefixAs(3))?;
+
+        let res = store.match_prefix(
+            &pfx("192.0.2.0/24"),
+            &MatchOptions {
+                match_type: MatchType::LongestMatch,
+                include_all_records: false,
+                include_less_specifics: true,
+                include_more_specifics: false,
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&res)?,
+            json!({
+                "match_type": "exact-match",
+                "prefix": "192.0.2.0/24",
+                "prefix_meta": 2,
+                "less_specifics": [{ "prefix": "192.0.0.0/16", "meta": 1 }],
+                "more_specifics": null,
+            })
+        );
+
+        let res = store.more_specifics_from(&pfx("192.0.2.0/24"));
+        let json = serde_json::to_value(&res)?;
+        assert_eq!(
+            json["more_specifics"],
+            json!([{ "prefix": "192.0.2.0/25", "meta": 3 }])
+        );
+
+        assert!(serde_json::to_value(store.stats())?["v4"].is_array());
+        Ok(())
+    }
+
+    #[test]
+    fn test_options_round_trip() -> Result<(), Box<dyn Error>> {
+        let options: MatchOptions = serde_json::from_value(json!({
+            "match_type": "empty-match",
+            "include_all_records": false,
+            "include_less_specifics": true,
+            "include_more_specifics": false,
+        }))?;
+        assert!(options.match_type.is_empty());
+        assert!(options.include_less_specifics);
+
+        let af: AfSelector = serde_json::from_str("\"ipv6\"")?;
+        assert_eq!(af, AfSelector::Ipv6);
+        assert_eq!(serde_json::to_string(&PrefixAs(64496))?, "64496");
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        BitPrefix, BitPrefixStore, IPv4, MatchOptions, MatchType, PrefixAs,
+        SlabStorage,
+    };
+
+    use std::error::Error;
+
+    type SlabStore =
+        BitPrefixStore<IPv4, PrefixAs, SlabStorage<IPv4, PrefixAs>>;
+
+    fn pfx(net: u32, len: u8) -> BitPrefix<IPv4> {
+        BitPrefix::new_relaxed(net, len).unwrap()
+    }
+
+    fn options() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::LongestMatch,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    // Every /24 in 10.0.0.0/16, and the /16 itself.
+    fn prefixes() -> Vec<BitPrefix<IPv4>> {
+        let mut pfxs = vec![pfx(0x0a00_0000, 16)];
+        pfxs.extend((0..256).map(|i| pfx(0x0a00_0000 | i << 8, 24)));
+        pfxs
+    }
+
+    #[test]
+    fn test_slab_same_results() -> Result<(), Box<dyn Error>> {
+        let mut vec_store = BitPrefixStore::<IPv4, PrefixAs>::new(vec![4]);
+        let mut slab_store = SlabStore::new(vec![4]);
+        for (i, p) in prefixes().into_iter().enumerate() {
+            vec_store.insert(p, PrefixAs(i as u32))?;
+            slab_store.insert(p, PrefixAs(i as u32))?;
+        }
+        assert_eq!(vec_store.prefixes_len(), slab_store.prefixes_len());
+        assert_eq!(vec_store.nodes_len(), slab_store.nodes_len());
+
+        for search in [
+            pfx(0x0a00_0000, 16),
+            pfx(0x0a00_1000, 24),
+            pfx(0x0a00_1001, 32),
+            pfx(0x0a01_0000, 24),
+        ] {
+            let vec_res = vec_store.match_prefix(search, &options());
+            let slab_res = slab_store.match_prefix(search, &options());
+            assert_eq!(vec_res.prefix, slab_res.prefix);
+            assert_eq!(
+                vec_res.prefix_meta.map(|m| m.0),
+                slab_res.prefix_meta.map(|m| m.0)
+            );
+            assert_eq!(
+                vec_res.less_specifics.map(|r| r.len()),
+                slab_res.less_specifics.map(|r| r.len())
+            );
+            assert_eq!(
+                vec_res.more_specifics.map(|r| r.len()),
+                slab_res.more_specifics.map(|r| r.len())
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_slab_reuses_slots() -> Result<(), Box<dyn Error>> {
+        let mut store = SlabStore::new(vec![4]);
+        let pfxs = prefixes();
+        for p in &pfxs {
+            store.insert(*p, PrefixAs(1))?;
+        }
+        let nodes_len = store.nodes_len();
+
+        // Removed prefixes are gone right away, without compacting.
+        for p in &pfxs[1..] {
+            assert_eq!(store.remove(*p).map(|m| m.0), Some(1));
+        }
+        assert!(store.remove(pfxs[1]).is_none());
+        assert_eq!(store.prefixes_len(), 1);
+        assert_eq!(store.prefixes_iter().count(), 1);
+
+        // Compacting frees the nodes that held the /24s only.
+        store.compact();
+        assert!(store.nodes_len() < nodes_len);
+        let res = store.match_prefix(pfx(0x0a00_1000, 24), &options());
+        assert_eq!(res.prefix, Some(pfxs[0]));
+        assert!(res.more_specifics.unwrap().is_empty());
+
+        // New prefixes and nodes take over the freed slots.
+        for p in &pfxs[1..] {
+            store.insert(*p, PrefixAs(2))?;
+        }
+        assert_eq!(store.prefixes_len(), pfxs.len());
+        assert_eq!(store.nodes_len(), nodes_len);
+        for p in &pfxs[1..] {
+            let res = store.match_prefix(*p, &options());
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(2));
+        }
+
+        Ok(())
+    }
+}
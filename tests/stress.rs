@@ -0,0 +1,463 @@
+// A stress test with many writers and readers on one MultiThreadedStore.
+// Every operation is recorded with the ticks of a global clock at its start
+// and end, and in the end the history is checked for results that no
+// linearization of the writes could have produced.
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore,
+    };
+    use routecore::addr::Prefix;
+    use routecore::record::MergeUpdate;
+
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    const WRITERS: u32 = 4;
+    const READERS: u32 = 4;
+    const ROUNDS: u32 = 3;
+
+    static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+    fn tick() -> u64 {
+        CLOCK.fetch_add(1, Ordering::SeqCst)
+    }
+
+    // The meta-data is the list of all the writes to a prefix, as pairs of
+    // writer and round, in the order they were merged. So it shows every
+    // update that got lost, and the order of the ones that didn't.
+    #[derive(Clone, Debug)]
+    struct Writes(Vec<(u32, u32)>);
+
+    impl MergeUpdate for Writes {
+        fn merge_update(
+            &mut self,
+            update_record: Writes,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.extend(update_record.0);
+            Ok(())
+        }
+
+        fn clone_merge_update(
+            &self,
+            update_meta: &Self,
+        ) -> Result<Self, Box<dyn std::error::Error>>
+        where
+            Self: std::marker::Sized,
+        {
+            let mut writes = self.0.clone();
+            writes.extend(update_meta.0.iter().copied());
+            Ok(Writes(writes))
+        }
+    }
+
+    impl fmt::Display for Writes {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    // A /8 with 16 /16s, each with 4 /24s, and the same in IPv6.
+    fn prefixes() -> Vec<Prefix> {
+        let mut prefixes = vec![
+            Prefix::new(Ipv4Addr::new(10, 0, 0, 0).into(), 8).unwrap(),
+            Prefix::new(
+                Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0).into(),
+                32,
+            )
+            .unwrap(),
+        ];
+        for i in 0..16 {
+            prefixes.push(
+                Prefix::new(Ipv4Addr::new(10, i, 0, 0).into(), 16).unwrap(),
+            );
+            prefixes.push(
+                Prefix::new(
+                    Ipv6Addr::new(0x2001, 0xdb8, i.into(), 0, 0, 0, 0, 0)
+                        .into(),
+                    48,
+                )
+                .unwrap(),
+            );
+            for j in 0..4 {
+                prefixes.push(
+                    Prefix::new(Ipv4Addr::new(10, i, j, 0).into(), 24)
+                        .unwrap(),
+                );
+                prefixes.push(
+                    Prefix::new(
+                        Ipv6Addr::new(
+                            0x2001,
+                            0xdb8,
+                            i.into(),
+                            j.into(),
+                            0,
+                            0,
+                            0,
+                            0,
+                        )
+                        .into(),
+                        64,
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+        prefixes
+    }
+
+    // Whether `prefix` covers `other`.
+    fn covers(prefix: &Prefix, other: &Prefix) -> bool {
+        if prefix.len() > other.len() {
+            return false;
+        }
+        match (prefix.addr(), other.addr()) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => {
+                let shift = 32 - u32::from(prefix.len());
+                u32::from(a).checked_shr(shift).unwrap_or(0)
+                    == u32::from(b).checked_shr(shift).unwrap_or(0)
+            }
+            (IpAddr::V6(a), IpAddr::V6(b)) => {
+                let shift = 128 - u32::from(prefix.len());
+                u128::from(a).checked_shr(shift).unwrap_or(0)
+                    == u128::from(b).checked_shr(shift).unwrap_or(0)
+            }
+            _ => false,
+        }
+    }
+
+    // A host address in one of the prefixes, or somewhere close to them.
+    fn host(prefixes: &[Prefix], rnd: u64) -> Prefix {
+        let base = prefixes[rnd as usize % prefixes.len()];
+        match base.addr() {
+            IpAddr::V4(addr) => {
+                let bits = (rnd >> 32) as u32 & 0x3f_ffff;
+                let addr = u32::from(addr) ^ bits;
+                Prefix::new(Ipv4Addr::from(addr).into(), 32).unwrap()
+            }
+            IpAddr::V6(addr) => {
+                let bits = u128::from(rnd >> 40) << 64;
+                let addr = u128::from(addr) ^ bits;
+                Prefix::new(Ipv6Addr::from(addr).into(), 128).unwrap()
+            }
+        }
+    }
+
+    // Xorshift, so every reader does something else.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn options(match_type: MatchType) -> MatchOptions {
+        MatchOptions {
+            match_type,
+            include_all_records: false,
+            include_less_specifics: false,
+            include_more_specifics: false,
+        }
+    }
+
+    struct Write {
+        prefix: Prefix,
+        writer: u32,
+        round: u32,
+        start: u64,
+        end: u64,
+    }
+
+    enum Read {
+        // The writes seen by an exact match.
+        Exact {
+            prefix: Prefix,
+            seen: Vec<(u32, u32)>,
+            start: u64,
+            end: u64,
+        },
+        // The prefix found by a longest match for a host address.
+        Longest {
+            host: Prefix,
+            found: Option<Prefix>,
+            start: u64,
+            end: u64,
+        },
+    }
+
+    fn write(
+        store: &MultiThreadedStore<Writes>,
+        writer: u32,
+    ) -> Vec<Write> {
+        let mut prefixes = prefixes();
+        let mut history = vec![];
+        for round in 0..ROUNDS {
+            // Every writer goes through the prefixes in another order, so
+            // they meet at different prefixes all the time.
+            prefixes.rotate_left(writer as usize * 7 + round as usize);
+            for prefix in &prefixes {
+                let start = tick();
+                store
+                    .insert(prefix, Writes(vec![(writer, round)]))
+                    .unwrap();
+                history.push(Write {
+                    prefix: *prefix,
+                    writer,
+                    round,
+                    start,
+                    end: tick(),
+                });
+            }
+        }
+        history
+    }
+
+    fn read(
+        store: &MultiThreadedStore<Writes>,
+        reader: u32,
+        done: &AtomicBool,
+    ) -> Vec<Read> {
+        let prefixes = prefixes();
+        let mut rng = Rng(0x9e37_79b9 + u64::from(reader));
+        let mut history = vec![];
+        while !done.load(Ordering::SeqCst) {
+            let rnd = rng.next();
+            let guard = &epoch::pin();
+            match rnd % 64 {
+                // Every prefix the iterator shows has to be there for an
+                // exact match too.
+                0 => {
+                    let seen = store
+                        .prefixes_iter(guard)
+                        .map(|record| record.prefix)
+                        .collect::<Vec<_>>();
+                    let exact = options(MatchType::ExactMatch);
+                    for prefix in seen {
+                        let res = store.match_prefix(&prefix, &exact, guard);
+                        assert_eq!(res.prefix, Some(prefix));
+                    }
+                }
+                1..=31 => {
+                    let idx = (rnd >> 8) as usize % prefixes.len();
+                    let prefix = prefixes[idx];
+                    let start = tick();
+                    let res = store.match_prefix(
+                        &prefix,
+                        &options(MatchType::ExactMatch),
+                        guard,
+                    );
+                    let end = tick();
+                    history.push(Read::Exact {
+                        prefix,
+                        seen: res
+                            .prefix_meta
+                            .map_or(vec![], |meta| meta.0.clone()),
+                        start,
+                        end,
+                    });
+                }
+                _ => {
+                    let host = host(&prefixes, rnd >> 8);
+                    let start = tick();
+                    let res = store.match_prefix(
+                        &host,
+                        &options(MatchType::LongestMatch),
+                        guard,
+                    );
+                    let end = tick();
+                    history.push(Read::Longest {
+                        host,
+                        found: res.prefix,
+                        start,
+                        end,
+                    });
+                }
+            }
+        }
+        history
+    }
+
+    // All writers insert the same new prefix at once, so that they race
+    // for its empty slot. The ones that lose must merge their write into
+    // the record of the winner, not fail or drop it.
+    #[test]
+    fn test_stress_first_insert() {
+        let store = Arc::new(MultiThreadedStore::<Writes>::new().unwrap());
+        let barrier = Arc::new(Barrier::new(WRITERS as usize));
+        let prefixes = (0..=255)
+            .map(|i| {
+                Prefix::new(Ipv4Addr::new(192, 0, i, 0).into(), 24).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let writers = (0..WRITERS)
+            .map(|writer| {
+                let store = store.clone();
+                let barrier = barrier.clone();
+                let prefixes = prefixes.clone();
+                thread::spawn(move || {
+                    for prefix in &prefixes {
+                        barrier.wait();
+                        store
+                            .insert(prefix, Writes(vec![(writer, 0)]))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let guard = &epoch::pin();
+        let exact = options(MatchType::ExactMatch);
+        for prefix in &prefixes {
+            let res = store.match_prefix(prefix, &exact, guard);
+            let mut merged = res.prefix_meta.unwrap().0.clone();
+            merged.sort_unstable();
+            let all = (0..WRITERS).map(|w| (w, 0)).collect::<Vec<_>>();
+            assert_eq!(merged, all, "lost updates for {}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_stress_linearizable() {
+        let store = Arc::new(MultiThreadedStore::<Writes>::new().unwrap());
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers = (0..READERS)
+            .map(|reader| {
+                let store = store.clone();
+                let done = done.clone();
+                thread::spawn(move || read(&store, reader, &done))
+            })
+            .collect::<Vec<_>>();
+        let writers = (0..WRITERS)
+            .map(|writer| {
+                let store = store.clone();
+                thread::spawn(move || write(&store, writer))
+            })
+            .collect::<Vec<_>>();
+
+        let writes = writers
+            .into_iter()
+            .flat_map(|writer| writer.join().unwrap())
+            .map(|write| ((write.prefix, write.writer, write.round), write))
+            .collect::<HashMap<_, _>>();
+        done.store(true, Ordering::SeqCst);
+        let reads = readers
+            .into_iter()
+            .flat_map(|reader| reader.join().unwrap())
+            .collect::<Vec<_>>();
+
+        // No update got lost, and the updates were merged in an order that
+        // doesn't go against the order in which they happened.
+        let guard = &epoch::pin();
+        let exact = options(MatchType::ExactMatch);
+        let merged = prefixes()
+            .into_iter()
+            .map(|prefix| {
+                let res = store.match_prefix(&prefix, &exact, guard);
+                let merged = res.prefix_meta.unwrap().0.clone();
+                (prefix, merged)
+            })
+            .collect::<HashMap<_, _>>();
+        for (prefix, merged) in &merged {
+            let mut sorted = merged.clone();
+            sorted.sort_unstable();
+            let all = (0..WRITERS)
+                .flat_map(|w| (0..ROUNDS).map(move |r| (w, r)))
+                .collect::<Vec<_>>();
+            assert_eq!(sorted, all, "lost updates for {}", prefix);
+
+            for (i, (w, r)) in merged.iter().enumerate() {
+                let first = &writes[&(*prefix, *w, *r)];
+                for (w, r) in &merged[i + 1..] {
+                    let later = &writes[&(*prefix, *w, *r)];
+                    assert!(
+                        later.end > first.start,
+                        "{} merged out of order",
+                        prefix
+                    );
+                }
+            }
+        }
+
+        // When the first write to a prefix started, and when the first one
+        // ended, so from when on it was in the store for sure.
+        let lifetime = |prefix: &Prefix| {
+            writes.values().filter(|write| write.prefix == *prefix).fold(
+                (u64::MAX, u64::MAX),
+                |(start, end), write| {
+                    (start.min(write.start), end.min(write.end))
+                },
+            )
+        };
+
+        for read in &reads {
+            match read {
+                // What an exact match saw is what was merged up to some
+                // point between its start and end.
+                Read::Exact {
+                    prefix,
+                    seen,
+                    start,
+                    end,
+                } => {
+                    assert!(
+                        merged[prefix].starts_with(seen),
+                        "{} saw writes that never happened like that",
+                        prefix
+                    );
+                    for write in writes.values() {
+                        if write.prefix != *prefix {
+                            continue;
+                        }
+                        let key = (write.writer, write.round);
+                        if write.end < *start {
+                            assert!(seen.contains(&key), "{} stale", prefix);
+                        }
+                        if seen.contains(&key) {
+                            assert!(write.start < *end, "{} early", prefix);
+                        }
+                    }
+                }
+                // A longest match finds a prefix that was inserted by the
+                // time it ended, and no longer match was in the store
+                // already before it started.
+                Read::Longest {
+                    host,
+                    found,
+                    start,
+                    end,
+                } => {
+                    if let Some(found) = found {
+                        assert!(covers(found, host), "{} {}", found, host);
+                        assert!(lifetime(found).0 < *end, "{} early", found);
+                    }
+                    let found_len = found.map_or(0, |found| found.len());
+                    for prefix in merged.keys() {
+                        let longer =
+                            found.is_none() || prefix.len() > found_len;
+                        if longer && covers(prefix, host) {
+                            assert!(
+                                lifetime(prefix).1 > *start,
+                                "{} missed for {}",
+                                prefix,
+                                host
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use rotonda_store::AddressFamily;
+    use rotonda_store::IpAddressFamily;
     use rotonda_store::{
         prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
     };
@@ -0,0 +1,249 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, MatchOptions, MatchType, MultiThreadedStore, PrefixAs,
+        UpsertReport, Upserted,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn meta(
+        store: &MultiThreadedStore<PrefixAs>,
+        prefix: &Prefix,
+    ) -> Option<u32> {
+        let guard = &epoch::pin();
+        let res = store.match_prefix(
+            prefix,
+            &MatchOptions {
+                match_type: MatchType::ExactMatch,
+                include_all_records: false,
+                include_less_specifics: false,
+                include_more_specifics: false,
+            },
+            guard,
+        );
+        res.prefix_meta.map(|meta| meta.0)
+    }
+
+    #[test]
+    fn test_upsert_with() -> Result<(), Box<dyn Error>> {
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        for prefix in ["0.0.0.0/0", "10.0.0.0/8", "::/0", "2001:db8::/32"] {
+            let prefix = pfx(prefix);
+
+            // Leaving an absent prefix absent.
+            let old = store.upsert_with(&prefix, |_| None)?;
+            assert_eq!(old, None);
+            assert_eq!(meta(&store, &prefix), None);
+
+            // Creating it.
+            let old = store.upsert_with(&prefix, |current| {
+                assert_eq!(current, None);
+                Some(PrefixAs(1))
+            })?;
+            assert_eq!(old, None);
+            assert_eq!(meta(&store, &prefix), Some(1));
+
+            // Updating it, with the old value returned.
+            let old = store.upsert_with(&prefix, |current| {
+                current.map(|meta| PrefixAs(meta.0 + 1))
+            })?;
+            assert_eq!(old, Some(PrefixAs(1)));
+            assert_eq!(meta(&store, &prefix), Some(2));
+
+            // Leaving it as it is.
+            let old = store.upsert_with(&prefix, |_| None)?;
+            assert_eq!(old, None);
+            assert_eq!(meta(&store, &prefix), Some(2));
+        }
+        assert_eq!(store.prefixes_len(), 4);
+
+        // The default routes are found as less-specifics again.
+        let guard = &epoch::pin();
+        let res = store.match_prefix(
+            &pfx("192.0.2.0/24"),
+            &MatchOptions {
+                match_type: MatchType::LongestMatch,
+                include_all_records: false,
+                include_less_specifics: false,
+                include_more_specifics: false,
+            },
+            guard,
+        );
+        assert_eq!(res.prefix, Some(pfx("0.0.0.0/0")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_upsert_report() -> Result<(), Box<dyn Error>> {
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        for prefix in ["0.0.0.0/0", "192.0.2.0/24", "::/0", "2001:db8::/32"] {
+            let prefix = pfx(prefix);
+            assert_eq!(
+                store.upsert(&prefix, PrefixAs(1))?,
+                UpsertReport {
+                    upserted: Upserted::Created,
+                    cas_count: 0,
+                }
+            );
+            assert_eq!(
+                store.upsert(&prefix, PrefixAs(2))?,
+                UpsertReport {
+                    upserted: Upserted::Updated(PrefixAs(1)),
+                    cas_count: 0,
+                }
+            );
+            assert_eq!(meta(&store, &prefix), Some(2));
+        }
+        assert_eq!(store.prefixes_len(), 4);
+        Ok(())
+    }
+
+    // Of many threads inserting the same prefix at once, exactly one
+    // creates it.
+    #[test]
+    fn test_upsert_report_concurrent() -> Result<(), Box<dyn Error>> {
+        const THREADS: u32 = 8;
+
+        let store = Arc::new(MultiThreadedStore::<PrefixAs>::new()?);
+        let prefix = pfx("192.0.2.0/24");
+        let threads = (0..THREADS)
+            .map(|asn| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    store.upsert(&prefix, PrefixAs(asn)).unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        let reports = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+
+        let created = reports
+            .iter()
+            .filter(|report| report.upserted == Upserted::Created)
+            .count();
+        assert_eq!(created, 1);
+        assert!(reports.iter().all(|report| matches!(
+            report.upserted,
+            Upserted::Created | Upserted::Updated(_)
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_meta() -> Result<(), Box<dyn Error>> {
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        for prefix in ["0.0.0.0/0", "192.0.2.0/24", "::/0", "2001:db8::/32"] {
+            let prefix = pfx(prefix);
+
+            // An absent prefix isn't created.
+            let err = store.update_meta(&prefix, PrefixAs(1)).unwrap_err();
+            assert_eq!(err.to_string(), "Error: Prefix not found.");
+            assert_eq!(meta(&store, &prefix), None);
+
+            store.insert(&prefix, PrefixAs(1))?;
+            assert_eq!(
+                store.update_meta(&prefix, PrefixAs(2))?,
+                UpsertReport {
+                    upserted: Upserted::Updated(PrefixAs(1)),
+                    cas_count: 0,
+                }
+            );
+            assert_eq!(meta(&store, &prefix), Some(2));
+        }
+        assert_eq!(store.prefixes_len(), 4);
+
+        // A more-specific of a prefix that is in the store isn't in it.
+        let err = store.update_meta(&pfx("192.0.2.0/25"), PrefixAs(3));
+        assert!(err.is_err());
+        assert_eq!(store.prefixes_len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_and_set() -> Result<(), Box<dyn Error>> {
+        let store = MultiThreadedStore::<PrefixAs>::new()?;
+        let prefix = pfx("192.0.2.0/24");
+
+        // Inserting only if absent.
+        assert!(store.compare_and_set(&prefix, None, PrefixAs(1))?);
+        assert!(!store.compare_and_set(&prefix, None, PrefixAs(2))?);
+        assert_eq!(meta(&store, &prefix), Some(1));
+
+        // Replacing only if equal.
+        assert!(!store.compare_and_set(
+            &prefix,
+            Some(&PrefixAs(2)),
+            PrefixAs(3)
+        )?);
+        assert_eq!(meta(&store, &prefix), Some(1));
+        assert!(store.compare_and_set(
+            &prefix,
+            Some(&PrefixAs(1)),
+            PrefixAs(3)
+        )?);
+        assert_eq!(meta(&store, &prefix), Some(3));
+
+        // An absent prefix doesn't equal anything.
+        let other = pfx("198.51.100.0/24");
+        assert!(!store.compare_and_set(
+            &other,
+            Some(&PrefixAs(3)),
+            PrefixAs(4)
+        )?);
+        assert_eq!(meta(&store, &other), None);
+        assert_eq!(store.prefixes_len(), 1);
+        Ok(())
+    }
+
+    // Counting with a read-modify-write from many threads at once loses
+    // none of the increments.
+    #[test]
+    fn test_upsert_with_concurrent() -> Result<(), Box<dyn Error>> {
+        const THREADS: u32 = 8;
+        const INCREMENTS: u32 = 1_000;
+
+        let store = Arc::new(MultiThreadedStore::<PrefixAs>::new()?);
+        let prefixes = [pfx("192.0.2.0/24"), pfx("2001:db8::/32")];
+        let threads = (0..THREADS)
+            .map(|_| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for _ in 0..INCREMENTS {
+                        for prefix in &prefixes {
+                            store
+                                .upsert_with(prefix, |current| {
+                                    let count = current.map_or(0, |m| m.0);
+                                    Some(PrefixAs(count + 1))
+                                })
+                                .unwrap();
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        for prefix in &prefixes {
+            assert_eq!(meta(&store, prefix), Some(THREADS * INCREMENTS));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+#[cfg(test)]
+mod tests {
+    use rotonda_store::{
+        prelude::*, AddressFamily, MatchOptions, MatchType,
+        MultiThreadedVpnStore, PrefixAs, RouteDistinguisher,
+        SingleThreadedStore, VpnIPv4, VpnIPv6,
+    };
+    use routecore::addr::Prefix;
+
+    use std::error::Error;
+    use std::net::IpAddr;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn pfx(s: &str) -> Prefix {
+        let (addr, len) = s.split_once('/').unwrap();
+        Prefix::new_relaxed(
+            addr.parse::<IpAddr>().unwrap(),
+            len.parse::<u8>().unwrap(),
+        )
+        .unwrap()
+    }
+
+    // Type 0 RD, 2-byte ASN 65000, assigned number `n`.
+    fn rd(n: u32) -> RouteDistinguisher {
+        let mut bytes = [0_u8, 0, 0xfd, 0xe8, 0, 0, 0, 0];
+        bytes[4..].copy_from_slice(&n.to_be_bytes());
+        RouteDistinguisher::new(bytes)
+    }
+
+    fn options() -> MatchOptions {
+        MatchOptions {
+            match_type: MatchType::LongestMatch,
+            include_all_records: false,
+            include_less_specifics: true,
+            include_more_specifics: true,
+        }
+    }
+
+    #[test]
+    fn test_route_distinguisher() {
+        assert_eq!(rd(1).to_string(), "65000:1");
+        assert_eq!(rd(1).rd_type(), 0);
+        assert_eq!(
+            RouteDistinguisher::new([0, 1, 192, 0, 2, 1, 0, 10]).to_string(),
+            "192.0.2.1:10"
+        );
+        assert_eq!(
+            RouteDistinguisher::new([0, 2, 0, 3, 0x0d, 0x40, 0, 7])
+                .to_string(),
+            "200000:7"
+        );
+        assert_eq!(u64::from(RouteDistinguisher::from(42_u64)), 42);
+    }
+
+    #[test]
+    fn test_vpn_address_families() {
+        let net = VpnIPv4::new(rd(7), "192.0.2.128".parse().unwrap());
+        assert_eq!(net.rd(), rd(7));
+        assert_eq!(net.addr(), "192.0.2.128".parse::<IpAddr>().unwrap());
+        assert_eq!(net.into_ipaddr(), net.addr());
+        assert_eq!(VpnIPv4::fmt_net(net), "65000:7:192.0.2.128");
+        assert_eq!(net.into_prefix(64 + 24), pfx("192.0.2.0/24"));
+        assert_eq!(
+            net.truncate_to_len(64 + 24),
+            VpnIPv4::new(rd(7), "192.0.2.0".parse().unwrap())
+        );
+        // The first nibble is the start of the RD, the type 0 RD starts
+        // with 16 zero bits.
+        assert_eq!(VpnIPv4::get_nibble(net, 0, 8), 0);
+        assert_eq!(VpnIPv4::get_nibble(net, 64, 8), 192);
+        assert_eq!(VpnIPv4::get_nibble(net, 88, 8), 128);
+
+        let net = VpnIPv6::new(rd(7), "2001:db8::1".parse().unwrap());
+        assert_eq!(net.rd(), rd(7));
+        assert_eq!(net.addr(), "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(VpnIPv6::fmt_net(net), "65000:7:2001:db8::1");
+        assert_eq!(net.into_prefix(64 + 32), pfx("2001:db8::/32"));
+        assert_eq!(
+            net.truncate_to_len(64 + 32),
+            VpnIPv6::new(rd(7), "2001:db8::".parse().unwrap())
+        );
+        assert_eq!(VpnIPv6::get_nibble(net, 16, 16), 0xfde8);
+        assert_eq!(VpnIPv6::get_nibble(net, 64, 16), 0x2001);
+        assert_eq!(VpnIPv6::get_nibble(net, 184, 8), 1);
+
+        let (net, len) = VpnIPv6::new(rd(7), "2001::".parse().unwrap())
+            .add_nibble(64 + 16, 0xdb8, 16);
+        assert_eq!(len, 64 + 32);
+        assert_eq!(net, VpnIPv6::new(rd(7), "2001:db8::".parse().unwrap()));
+
+        // Shifting by the whole width or more leaves no bits.
+        assert_eq!(
+            (net << VpnIPv6::from(64_u32)).rd(),
+            RouteDistinguisher::from(0x2001_0db8_0000_0000_u64)
+        );
+        assert_eq!(net << VpnIPv6::from(256_u32), VpnIPv6::from(0_u32));
+        assert_eq!(net << VpnIPv6::from(300_u32), VpnIPv6::from(0_u32));
+        let net = VpnIPv4::new(rd(7), "192.0.2.128".parse().unwrap());
+        assert_eq!(net << VpnIPv4::from(128_u32), VpnIPv4::from(0_u32));
+    }
+
+    fn vpn_store(
+        mut store: SingleThreadedStore<PrefixAs>,
+    ) -> Result<(), Box<dyn Error>> {
+        // The same prefixes in different VPNs, and outside of any VPN.
+        for vpn in 1..=2 {
+            store.insert_vpn(rd(vpn), &pfx("10.0.0.0/8"), PrefixAs(vpn))?;
+            store.insert_vpn(rd(vpn), &pfx("10.1.0.0/16"), PrefixAs(vpn))?;
+            store.insert_vpn(
+                rd(vpn),
+                &pfx("2001:db8::/32"),
+                PrefixAs(vpn),
+            )?;
+        }
+        store.insert_vpn(rd(2), &pfx("10.1.1.0/24"), PrefixAs(2))?;
+        store.insert_vpn(rd(2), &pfx("2001:db8:1::/48"), PrefixAs(2))?;
+        store.insert(&pfx("10.0.0.0/8"), PrefixAs(0))?;
+
+        assert_eq!(store.vpn_prefixes_len(), 8);
+        assert_eq!(store.prefixes_len(), 1);
+        assert_eq!(store.vpn_prefixes_iter().count(), 8);
+        assert_eq!(
+            store
+                .vpn_prefixes_iter()
+                .filter(|(vpn, _)| *vpn == rd(2))
+                .count(),
+            5
+        );
+
+        // Exact matches are per VPN.
+        for vpn in 1..=2 {
+            let res = store.match_vpn_prefix(
+                rd(vpn),
+                &pfx("10.1.0.0/16"),
+                &options(),
+            );
+            assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(vpn));
+            assert_eq!(res.less_specifics.unwrap().len(), 1);
+            assert_eq!(
+                res.more_specifics.unwrap().len(),
+                if vpn == 2 { 1 } else { 0 }
+            );
+        }
+
+        // Longest matches don't cross VPNs.
+        let res =
+            store.match_vpn_prefix(rd(1), &pfx("10.1.1.0/24"), &options());
+        assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+        assert_eq!(res.prefix_meta.map(|m| m.0), Some(1));
+
+        let res = store.match_vpn_prefix(
+            rd(1),
+            &pfx("2001:db8:1::/48"),
+            &options(),
+        );
+        assert_eq!(res.prefix, Some(pfx("2001:db8::/32")));
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+
+        let res = store.match_vpn_prefix(
+            rd(2),
+            &pfx("2001:db8:1:1::/64"),
+            &options(),
+        );
+        assert_eq!(res.prefix, Some(pfx("2001:db8:1::/48")));
+        assert_eq!(res.less_specifics.unwrap().len(), 1);
+
+        // Unknown VPNs are empty.
+        let res =
+            store.match_vpn_prefix(rd(3), &pfx("10.1.0.0/16"), &options());
+        assert_eq!(res.prefix, None);
+        assert!(matches!(res.match_type, MatchType::EmptyMatch));
+
+        // The plain IPv4 tree doesn't see any VPN prefixes.
+        let res = store.match_prefix(&pfx("10.1.0.0/16"), &options());
+        assert_eq!(res.prefix, Some(pfx("10.0.0.0/8")));
+        assert_eq!(res.prefix_meta.map(|m| m.0), Some(0));
+
+        // Removal
+        assert_eq!(
+            store.remove_vpn(rd(2), &pfx("10.1.0.0/16")).map(|m| m.0),
+            Some(2)
+        );
+        assert!(store.remove_vpn(rd(3), &pfx("10.0.0.0/8")).is_none());
+        store.compact();
+        assert_eq!(store.vpn_prefixes_len(), 7);
+        let res =
+            store.match_vpn_prefix(rd(2), &pfx("10.1.1.0/24"), &options());
+        assert_eq!(res.prefix, Some(pfx("10.1.1.0/24")));
+        assert_eq!(res.less_specifics.unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vpn_store() -> Result<(), Box<dyn Error>> {
+        vpn_store(SingleThreadedStore::new(vec![4], vec![4]))
+    }
+
+    // Strides that cover IPv4 and IPv6 addresses, but not the longer VPN
+    // keys, don't get in the way of the VPN trees.
+    #[test]
+    fn test_vpn_store_ip_strides() -> Result<(), Box<dyn Error>> {
+        let mut v6_strides = vec![5; 25];
+        v6_strides.push(3);
+        vpn_store(SingleThreadedStore::new(
+            vec![4, 4, 4, 4, 4, 4, 5, 3],
+            v6_strides,
+        ))
+    }
+
+    #[test]
+    fn test_vpn_store_vpn_strides() -> Result<(), Box<dyn Error>> {
+        vpn_store(SingleThreadedStore::new_with_vpn_strides(
+            vec![4],
+            vec![4],
+            vec![8, 8, 8, 8],
+            vec![8, 7, 6, 5, 3, 3],
+        ))
+    }
+
+    #[test]
+    fn test_multi_threaded_vpn_store() -> Result<(), Box<dyn Error>> {
+        let store = Arc::new(MultiThreadedVpnStore::<PrefixAs>::new()?);
+
+        // Every VPN gets its prefixes from its own thread.
+        let writers = (1..=2)
+            .map(|vpn| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || {
+                    let mut pfxs =
+                        vec!["10.0.0.0/8", "10.1.0.0/16", "2001:db8::/32"];
+                    if vpn == 2 {
+                        pfxs.extend(["10.1.1.0/24", "2001:db8:1::/48"]);
+                    }
+                    for p in pfxs {
+                        store
+                            .insert_vpn(rd(vpn), &pfx(p), PrefixAs(vpn))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(store.vpn_prefixes_len(), 8);
+
+        let guard = &epoch::pin();
+
+        // Exact matches are per VPN.
+        for vpn in 1..=2 {
+            let res = store.match_vpn_prefix(
+                rd(vpn),
+                &pfx("10.1.0.0/16"),
+                &options(),
+                guard,
+            );
+            assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+            assert!(matches!(res.match_type, MatchType::ExactMatch));
+            assert_eq!(res.prefix_meta.map(|m| m.0), Some(vpn));
+            assert_eq!(res.less_specifics.unwrap().len(), 1);
+            assert_eq!(
+                res.more_specifics.unwrap().len(),
+                if vpn == 2 { 1 } else { 0 }
+            );
+        }
+
+        // Longest matches don't cross VPNs.
+        let res = store.match_vpn_prefix(
+            rd(1),
+            &pfx("10.1.1.0/24"),
+            &options(),
+            guard,
+        );
+        assert_eq!(res.prefix, Some(pfx("10.1.0.0/16")));
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+        assert_eq!(res.prefix_meta.map(|m| m.0), Some(1));
+
+        let res = store.match_vpn_prefix(
+            rd(1),
+            &pfx("2001:db8:1::/48"),
+            &options(),
+            guard,
+        );
+        assert_eq!(res.prefix, Some(pfx("2001:db8::/32")));
+        assert!(matches!(res.match_type, MatchType::LongestMatch));
+
+        let res = store.match_vpn_prefix(
+            rd(2),
+            &pfx("2001:db8:1:1::/64"),
+            &options(),
+            guard,
+        );
+        assert_eq!(res.prefix, Some(pfx("2001:db8:1::/48")));
+        assert_eq!(res.less_specifics.unwrap().len(), 1);
+
+        // Unknown VPNs are empty.
+        let res = store.match_vpn_prefix(
+            rd(3),
+            &pfx("10.1.0.0/16"),
+            &options(),
+            guard,
+        );
+        assert_eq!(res.prefix, None);
+        assert!(matches!(res.match_type, MatchType::EmptyMatch));
+
+        Ok(())
+    }
+}